@@ -171,6 +171,7 @@ function! clap#_exit() abort
   call g:clap.display.clear()
 
   call clap#sign#reset()
+  call clap#query_history#reset()
 
   call clap#state#clear_post()
 
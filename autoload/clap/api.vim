@@ -441,6 +441,9 @@ function! s:init_provider() abort
   function! s:read_from_file_or_pipe(ext_filter_cmd, input_file) abort
     if clap#filter#async#external#using_maple()
       let cmd = printf('%s --input %s', a:ext_filter_cmd, a:input_file)
+    elseif clap#maple#is_available()
+      " The cached output of maple may be compressed.
+      let cmd = printf('%s cache cat %s | %s', clap#maple#binary(), a:input_file, a:ext_filter_cmd)
     else
       let cmd = printf('%s %s | %s', s:cat_or_type, a:input_file, a:ext_filter_cmd)
     endif
@@ -623,6 +626,9 @@ function! s:init_provider() abort
         let extra['debounce'] = v:false
       endif
       call clap#client#notify_on_init('on_init', extra)
+      if has_key(g:clap.context, 'resume')
+        call clap#client#resume()
+      endif
     endif
     " Try to fill the preview window.
     if clap#preview#is_enabled()
@@ -41,9 +41,44 @@ function! s:process_filter_message(msg) abort
   endif
 endfunction
 
+" Progress of the filtering job streamed from the Rust side.
+function! s:process_progress(msg) abort
+  call s:process_filter_message(a:msg)
+  if a:msg.done && a:msg.query ==# g:clap.input.get()
+    call clap#spinner#set_idle()
+  endif
+endfunction
+
+" Method call or notification initiated from the Rust side.
+function! s:handle_rpc_request(decoded) abort
+  let params = a:decoded.params
+  if type(params) == v:t_dict
+    let params = [params]
+  elseif type(params) != v:t_list
+    let params = []
+  endif
+
+  if !has_key(a:decoded, 'id')
+    call call(a:decoded.method, params)
+    return
+  endif
+
+  try
+    let reply = {'id': a:decoded.id, 'result': call(a:decoded.method, params)}
+  catch
+    let reply = {'id': a:decoded.id, 'error': {'code': -32603, 'message': v:exception}}
+  endtry
+  call clap#job#daemon#send_message(json_encode(reply))
+endfunction
+
 function! clap#client#handle(msg) abort
   let decoded = json_decode(a:msg)
 
+  if has_key(decoded, 'params')
+    call s:handle_rpc_request(decoded)
+    return
+  endif
+
   if has_key(decoded, 'method')
     call call(decoded.method, [decoded])
     return
@@ -75,6 +110,9 @@ function! s:base_params() abort
         \   'no_cache': has_key(g:clap.context, 'no-cache') ? v:true : v:false,
         \   'source_fpath': expand('#'.g:clap.start.bufnr.':p'),
         \   'display_winwidth': winwidth(g:clap.display.winid),
+        \   'fuzzy_algo': get(g:, 'clap_fuzzy_algo', 'fzy'),
+        \   'case_matching': has_key(g:clap.context, 'ignorecase') ? 'ignore' : 'smart',
+        \   'enable_resume': get(g:, 'clap_enable_resume', 0) ? v:true : v:false,
         \ }
   if has_key(g:clap.preview, 'winid')
         \ && clap#api#floating_win_is_valid(g:clap.preview.winid)
@@ -135,6 +173,31 @@ function! s:send_method_call(method, params) abort
         \ }))
 endfunction
 
+function! s:handle_resume(result, error) abort
+  if a:error isnot v:null || a:result is v:null || !g:clap.display.win_is_valid()
+    return
+  endif
+  call g:clap.input.set(a:result.query)
+  " The results of the providers filtered outside of the session are not saved, filter again.
+  if empty(a:result.lines)
+    call g:clap.provider.on_typed()
+    return
+  endif
+  call clap#state#process_filter_message(a:result, v:true)
+  let lnum = min([a:result.lnum, g:clap.display.line_count()])
+  call g:clap.display.set_cursor(lnum, 1)
+  let g:__clap_display_curlnum = lnum
+  call clap#sign#toggle_cursorline()
+  if get(a:result, 'truncated', v:false)
+    call clap#helper#echo_info('Only the top '.len(a:result.lines).' of '.a:result.total.' results are restored, change the query to filter again')
+  endif
+endfunction
+
+" Restores the query and results of the last session of current provider.
+function! clap#client#resume() abort
+  call clap#client#call('resume', function('s:handle_resume'), {})
+endfunction
+
 function! clap#client#notify_recent_file() abort
   if &buftype ==# 'nofile'
     return
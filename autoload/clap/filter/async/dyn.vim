@@ -47,6 +47,9 @@ function! s:prepare_grep_cmd() abort
         \ '--winwidth', winwidth(g:clap.display.winid),
         \ 'grep', g:clap.input.get(),
         \ ]
+  if clap#maple#use_ripgrep()
+    call add(opts, '--ripgrep')
+  endif
   return subcmd + opts
 endfunction
 
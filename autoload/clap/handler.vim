@@ -68,6 +68,11 @@ function! clap#handler#sink() abort
     return
   endif
 
+  " The quickfix entries are resolved by maple asynchronously.
+  if clap#selection#try_sink_star()
+    return
+  endif
+
   let [Sink, sink_args] = clap#selection#get_sink_or_sink_star_params()
 
   call clap#handler#internal_exit()
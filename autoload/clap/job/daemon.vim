@@ -135,11 +135,12 @@ endfunction
 
 function! clap#job#daemon#start(MessageHandler) abort
   let s:MessageHandler = a:MessageHandler
-  call s:start_service_job(clap#maple#build_cmd('rpc'))
+  call s:start_service_job(clap#maple#build_rpc_cmd())
   call clap#client#notify('initialize_global_env', {
       \   'is_nvim': has('nvim') ? v:true : v:false,
       \   'enable_icon': g:clap_enable_icon ? v:true : v:false,
       \   'clap_preview_size': g:clap_preview_size,
+      \   'grep_backend': get(g:, 'clap_grep_backend', 'native'),
       \ })
   call clap#client#call('init_ext_map', v:null, {'autocmd_filetypedetect': execute('autocmd filetypedetect')})
   return
@@ -154,7 +154,7 @@ endfunction
 
 function! clap#job#stdio#start_rpc_service(MessageHandler) abort
   let s:MessageHandler = a:MessageHandler
-  call s:start_service_job(clap#maple#build_cmd('rpc'))
+  call s:start_service_job(clap#maple#build_rpc_cmd())
   return
 endfunction
 
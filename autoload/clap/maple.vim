@@ -24,7 +24,12 @@ endif
 if s:maple_bin isnot v:null
   function! clap#maple#clean_up() abort
     call clap#job#regular#maple#stop()
-    call clap#client#notify('exit', {})
+    " The final query is sent as it may never reach the session, e.g., grep2 is filtered by the
+    " external maple job.
+    call clap#client#notify('exit', {
+          \ 'query': g:clap.input.get(),
+          \ 'lnum': get(g:, '__clap_display_curlnum', 1),
+          \ })
   endfunction
 else
   function! clap#maple#clean_up() abort
@@ -44,6 +49,19 @@ function! clap#maple#build_cmd(...) abort
   return [s:maple_bin] + a:000
 endfunction
 
+function! clap#maple#build_rpc_cmd() abort
+  let cmd = clap#maple#build_cmd('rpc')
+  if get(g:, 'clap_enable_watcher', 0)
+    call add(cmd, '--watch')
+  endif
+  return cmd
+endfunction
+
+" Returns v:true if the grep providers should run rg instead of the built-in searcher of maple.
+function! clap#maple#use_ripgrep() abort
+  return get(g:, 'clap_grep_backend', 'native') ==# 'rg'
+endfunction
+
 function! clap#maple#build_cmd_list(cmd_list) abort
   return insert(a:cmd_list, s:maple_bin)
 endfunction
@@ -58,6 +58,10 @@ function! clap#maple#command#ripgrep_forerunner() abort
         \ '--output-threshold', s:cache_threshold,
         \ ]
 
+  if clap#maple#use_ripgrep()
+    call add(subcommand, '--ripgrep')
+  endif
+
   return [s:maple_bin] + global_opts + subcommand
 endfunction
 
@@ -147,6 +147,8 @@ let s:move_manager["\<C-G>"] = s:move_manager["\<Esc>"]
 let s:move_manager["\<C-U>"] = s:move_manager.ctrl_u
 let s:move_manager["\<C-L>"] = s:move_manager.ctrl_l
 let s:move_manager["\<S-TAB>"] = { _winid -> clap#action#invoke() }
+let s:move_manager["\<C-P>"] = { _winid -> clap#query_history#recall(1) }
+let s:move_manager["\<C-N>"] = { _winid -> clap#query_history#recall(-1) }
 
 function! s:define_open_action_filter() abort
   for k in keys(g:clap_open_action)
@@ -18,7 +18,9 @@ function! clap#provider#filer#hi_empty_dir() abort
 endfunction
 
 function! s:handle_error(error) abort
-  let s:filer_error_cache[a:error.dir] = a:error.message
+  if has_key(a:error, 'data')
+    let s:filer_error_cache[a:error.data.dir] = a:error.message
+  endif
   call g:clap.preview.show([a:error.message])
 endfunction
 
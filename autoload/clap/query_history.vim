@@ -0,0 +1,73 @@
+" Author: liuchengxu <xuliuchengxlc@gmail.com>
+" Description: Recall the queries recorded by maple on exit, ranked by frecency.
+
+let s:save_cpo = &cpoptions
+set cpoptions&vim
+
+" Maximum number of the queries fetched for recalling.
+let s:MAX_QUERIES = 100
+
+" The recorded queries of current provider matching the input typed before recalling, v:null if
+" they have not been fetched yet.
+let s:queries = v:null
+" Index of the recalled query in s:queries, -1 means the typed input.
+let s:index = -1
+let s:typed = ''
+
+function! clap#query_history#reset() abort
+  let s:queries = v:null
+  let s:index = -1
+  let s:typed = ''
+endfunction
+
+function! s:set_query(query) abort
+  call g:clap.input.set(a:query)
+  if has('nvim')
+    call nvim_win_set_cursor(g:clap.input.winid, [1, strlen(a:query)])
+  endif
+  call g:clap.provider.on_typed()
+endfunction
+
+function! s:recall(step) abort
+  let index = s:index + a:step
+  if index < -1 || index >= len(s:queries)
+    return
+  endif
+  let s:index = index
+  call s:set_query(index == -1 ? s:typed : s:queries[index])
+endfunction
+
+function! s:handle_query_history(step, result, error) abort
+  if a:error isnot v:null || type(a:result) != v:t_dict || !g:clap.display.win_is_valid()
+    return
+  endif
+  let s:queries = a:result.queries
+  call s:recall(a:step)
+endfunction
+
+" Replaces the input with the older (step 1) or newer (step -1) query recorded for current
+" provider, the queries are fuzzy filtered by the input typed before recalling.
+function! clap#query_history#recall(step) abort
+  if !clap#maple#is_available()
+    return ''
+  endif
+
+  let current = s:queries is v:null || s:index == -1 ? s:typed : s:queries[s:index]
+  " Fetch them again if the input has been edited since the last recall.
+  if s:queries is v:null || g:clap.input.get() !=# current
+    let s:typed = g:clap.input.get()
+    let s:index = -1
+    call clap#client#call('query_history', function('s:handle_query_history', [a:step]), {
+          \ 'provider_id': g:clap.provider.id,
+          \ 'query': s:typed,
+          \ 'number': s:MAX_QUERIES,
+          \ })
+  else
+    call s:recall(a:step)
+  endif
+
+  return ''
+endfunction
+
+let &cpoptions = s:save_cpo
+unlet s:save_cpo
@@ -7,6 +7,8 @@ set cpoptions&vim
 let s:multi_select_enabled = v:false
 let s:support_multi_select = v:false
 let s:UNUSABLE_MULTI_SELECTION = '<Tab> is unusable, set g:clap_multi_selection_warning_silent = 1 to suppress this warning.'
+" The selected lines of these providers can be resolved into locations by maple.
+let s:LOCATION_PROVIDERS = ['files', 'git_files', 'grep', 'grep2', 'dumb_jump', 'proj_tags', 'blines']
 
 function! clap#selection#get_sink_or_sink_star_params() abort
   let selected = clap#sign#get()
@@ -68,6 +70,94 @@ function! s:get_opaque_lines() abort
   endif
 endfunction
 
+" Returns the line numbers of the (selected) lines if they can be resolved into locations by maple,
+" otherwise an empty List.
+function! s:get_resolvable_lnums() abort
+  if !clap#maple#is_available() || index(s:LOCATION_PROVIDERS, g:clap.provider.id) == -1
+    return []
+  endif
+  let selected = clap#sign#get()
+  if s:multi_select_enabled && !empty(selected)
+    return copy(selected)
+  else
+    return [g:__clap_display_curlnum]
+  endif
+endfunction
+
+" The displayed lines are sent along with their line numbers as the results may be filtered
+" outside of the maple session.
+function! s:selection_params(lnums, lines) abort
+  return {
+        \ 'lnums': a:lnums,
+        \ 'lines': a:lines,
+        \ 'icon_added': get(g:, '__clap_icon_added', v:false) ? v:true : v:false,
+        \ }
+endfunction
+
+" Returns v:true if the locations resolved by maple are complete, otherwise the lines have to be
+" parsed by the provider itself.
+function! s:is_resolved(lines, result, error) abort
+  return a:error is v:null
+        \ && type(a:result) == v:t_dict
+        \ && len(get(a:result, 'locations', [])) == len(a:lines)
+endfunction
+
+function! s:handle_sink_star(Sink, lines, provider, result, error) abort
+  try
+    if s:is_resolved(a:lines, a:result, a:error)
+      call clap#util#open_quickfix(a:result.locations)
+    else
+      call a:Sink(a:lines)
+    endif
+  catch
+    call clap#helper#echo_error('clap#selection#try_sink_star: '.v:exception.', throwpoint:'.v:throwpoint)
+  finally
+    call a:provider.on_exit()
+    silent doautocmd <nomodeline> User ClapOnExit
+  endtry
+endfunction
+
+" Sends the selected lines to the quickfix list using the locations resolved by maple, which
+" falls back to the sink* of provider if they can not be resolved.
+"
+" Returns v:false if the multi-selection is not resolvable by maple.
+function! clap#selection#try_sink_star() abort
+  if !s:multi_select_enabled || empty(clap#sign#get())
+    return v:false
+  endif
+  let lnums = s:get_resolvable_lnums()
+  if empty(lnums)
+    return v:false
+  endif
+
+  let [Sink, sink_args] = clap#selection#get_sink_or_sink_star_params()
+  call clap#client#call('selection',
+        \ function('s:handle_sink_star', [Sink, sink_args, g:clap.provider]),
+        \ s:selection_params(lnums, sink_args))
+  call clap#handler#internal_exit()
+  return v:true
+endfunction
+
+function! s:handle_open(open_action, lines, provider, result, error) abort
+  let g:clap.open_action = a:open_action
+  try
+    if s:is_resolved(a:lines, a:result, a:error)
+      for location in a:result.locations
+        call clap#sink#open_file(location.filename, location.lnum, location.col)
+      endfor
+    else
+      for line in a:lines
+        call a:provider.sink(line)
+      endfor
+    endif
+  catch
+    call clap#helper#echo_error('clap#selection#try_open: '.v:exception.', throwpoint:'.v:throwpoint)
+  finally
+    call remove(g:clap, 'open_action')
+    silent doautocmd <nomodeline> User ClapOnExit
+  endtry
+endfunction
+
 " Apply the open action specified by `g:clap_open_action` given the (selected) lines.
 function! clap#selection#try_open(action) abort
   if !has_key(g:clap_open_action, a:action)
@@ -87,6 +177,17 @@ function! clap#selection#try_open(action) abort
     endfor
     call clap#_exit()
 
+  elseif g:clap.provider.support_open_action() && !empty(s:get_resolvable_lnums())
+
+    let lnums = s:get_resolvable_lnums()
+    let lines = s:get_opaque_lines()
+    call clap#client#call('selection',
+          \ function('s:handle_open', [g:clap_open_action[a:action], lines, g:clap.provider]),
+          \ s:selection_params(lnums, lines))
+    call g:clap.start.goto_win()
+    call clap#_exit()
+    return
+
   elseif g:clap.provider.support_open_action()
 
     call g:clap.start.goto_win()
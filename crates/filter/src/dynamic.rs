@@ -1,4 +1,6 @@
 use std::io::BufRead;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
@@ -8,8 +10,10 @@ use icon::{Icon, ICON_LEN};
 use types::{FilteredItem, Query, SourceItem};
 use utility::{println_json, println_json_with_length};
 
-use super::{source_iter_exec, source_iter_file, source_iter_list, source_iter_stdin};
-use crate::{sort_initial_filtered, FilterContext, Source};
+use super::{
+    source_iter_exec, source_iter_file, source_iter_list, source_iter_stdin, source_iter_walk,
+};
+use crate::{sort_initial_filtered, FilterContext, LineIndex, Source};
 
 /// The constant to define the length of `top_` queues.
 const ITEMS_TO_SHOW: usize = 40;
@@ -74,9 +78,9 @@ type SelectedTopItemsInfo = (usize, [Score; ITEMS_TO_SHOW], [usize; ITEMS_TO_SHO
 fn select_top_items_to_show(
     buffer: &mut Vec<FilteredItem>,
     iter: &mut impl Iterator<Item = FilteredItem>,
-) -> std::result::Result<usize, SelectedTopItemsInfo> {
-    let mut top_scores: [Score; ITEMS_TO_SHOW] = [Score::min_value(); ITEMS_TO_SHOW];
-    let mut top_results: [usize; ITEMS_TO_SHOW] = [usize::min_value(); ITEMS_TO_SHOW];
+) -> std::result::Result<usize, Box<SelectedTopItemsInfo>> {
+    let mut top_scores: [Score; ITEMS_TO_SHOW] = [Score::MIN; ITEMS_TO_SHOW];
+    let mut top_results: [usize; ITEMS_TO_SHOW] = [usize::MIN; ITEMS_TO_SHOW];
 
     let mut total = 0;
     let res = iter.try_for_each(|filtered_item| {
@@ -100,7 +104,7 @@ fn select_top_items_to_show(
     if res.is_ok() {
         Ok(total)
     } else {
-        Err((total, top_scores, top_results))
+        Err(Box::new((total, top_scores, top_results)))
     }
 }
 
@@ -117,6 +121,42 @@ fn find_best_score_idx(top_scores: &[Score; ITEMS_TO_SHOW], score: Score) -> Opt
         .map(|(idx, _)| idx)
 }
 
+/// Snapshot of the dynamic filtering progress.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    /// Best results so far, the item with highest score first.
+    pub top_items: Vec<FilteredItem>,
+    /// Number of matched items so far.
+    pub matched: usize,
+    /// Number of processed source items so far.
+    pub processed: usize,
+    /// Whether the whole source has been processed.
+    pub done: bool,
+}
+
+/// Receiver of the [`Progress`] of a dynamic filtering job.
+///
+/// The intermediate progress is sent periodically, the last one is sent once the job is
+/// finished and not cancelled. At most `ITEMS_TO_SHOW` top items are sent each time.
+#[derive(Clone)]
+pub struct ProgressSink(Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl ProgressSink {
+    pub fn new(f: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    fn send(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl std::fmt::Debug for ProgressSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressSink")
+    }
+}
+
 /// Watch and send the dynamic filtering progress when neccessary.
 #[derive(Clone, Debug)]
 pub struct Watcher {
@@ -128,15 +168,29 @@ pub struct Watcher {
     icon: Icon,
     /// Lines we sent last time.
     last_lines: Vec<String>,
+    /// Whether to print the progress, the caller may want to send the results on its own.
+    notify: bool,
+    /// Number of processed source items, shared with the scorer.
+    processed: Arc<AtomicUsize>,
+    /// Receiver of the progress apart from stdout.
+    progress_sink: Option<ProgressSink>,
 }
 
 impl Watcher {
-    pub fn new(initial_total: usize, icon: Icon) -> Self {
+    pub fn new(
+        icon: Icon,
+        notify: bool,
+        processed: Arc<AtomicUsize>,
+        progress_sink: Option<ProgressSink>,
+    ) -> Self {
         Self {
             past: Instant::now(),
-            total: initial_total,
+            total: 0,
             icon,
             last_lines: Vec::with_capacity(ITEMS_TO_SHOW),
+            notify,
+            processed,
+            progress_sink,
         }
     }
 
@@ -146,38 +200,53 @@ impl Watcher {
     ///
     /// Printing to stdout is to send the content to the client.
     pub fn maybe_notify(&mut self, top_results: &[usize; ITEMS_TO_SHOW], buffer: &[FilteredItem]) {
-        if self.total % 16 == 0 {
+        if self.total.is_multiple_of(16) {
             let now = Instant::now();
             if now > self.past + UPDATE_INTERVAL {
-                let mut indices = Vec::with_capacity(ITEMS_TO_SHOW);
-                let mut lines = Vec::with_capacity(ITEMS_TO_SHOW);
-                for &idx in top_results.iter() {
-                    let filtered_item = std::ops::Index::index(buffer, idx);
-                    let text = if let Some(painter) = self.icon.painter() {
-                        indices.push(filtered_item.shifted_indices(ICON_LEN));
-                        painter.paint(filtered_item.display_text())
-                    } else {
-                        indices.push(filtered_item.match_indices.clone());
-                        filtered_item.display_text().to_owned()
-                    };
-                    lines.push(text);
+                if self.notify {
+                    self.print_top_results(top_results, buffer);
                 }
 
-                let total = self.total;
-
-                #[allow(non_upper_case_globals)]
-                const method: &str = "s:process_filter_message";
-                if self.last_lines != lines.as_slice() {
-                    println_json_with_length!(total, lines, indices, method);
-                    self.past = now;
-                    self.last_lines = lines;
-                } else {
-                    self.past = now;
-                    println_json_with_length!(total, method);
+                if let Some(ref progress_sink) = self.progress_sink {
+                    progress_sink.send(Progress {
+                        top_items: top_results.iter().map(|&idx| buffer[idx].clone()).collect(),
+                        matched: self.total,
+                        processed: self.processed.load(Ordering::Relaxed),
+                        done: false,
+                    });
                 }
+
+                self.past = now;
             }
         }
     }
+
+    fn print_top_results(&mut self, top_results: &[usize; ITEMS_TO_SHOW], buffer: &[FilteredItem]) {
+        let mut indices = Vec::with_capacity(ITEMS_TO_SHOW);
+        let mut lines = Vec::with_capacity(ITEMS_TO_SHOW);
+        for &idx in top_results.iter() {
+            let filtered_item = std::ops::Index::index(buffer, idx);
+            let text = if let Some(painter) = self.icon.painter() {
+                indices.push(filtered_item.shifted_indices(ICON_LEN));
+                painter.paint(filtered_item.display_text())
+            } else {
+                indices.push(filtered_item.match_indices.clone());
+                filtered_item.display_text().to_owned()
+            };
+            lines.push(text);
+        }
+
+        let total = self.total;
+
+        #[allow(non_upper_case_globals)]
+        const method: &str = "s:process_filter_message";
+        if self.last_lines != lines.as_slice() {
+            println_json_with_length!(total, lines, indices, method);
+            self.last_lines = lines;
+        } else {
+            println_json_with_length!(total, method);
+        }
+    }
 }
 
 /// To get dynamic updates, not so much should be changed, actually.
@@ -197,7 +266,10 @@ impl Watcher {
 /// VecDeque for this iterator.
 ///
 /// So, this particular function won't work in parallel context at all.
-fn dyn_collect_all(mut iter: impl Iterator<Item = FilteredItem>, icon: Icon) -> Vec<FilteredItem> {
+fn dyn_collect_all(
+    mut iter: impl Iterator<Item = FilteredItem>,
+    mut watcher: Watcher,
+) -> Vec<FilteredItem> {
     let mut buffer = Vec::with_capacity({
         let (low, high) = iter.size_hint();
         high.unwrap_or(low)
@@ -207,10 +279,10 @@ fn dyn_collect_all(mut iter: impl Iterator<Item = FilteredItem>, icon: Icon) ->
 
     let (total, mut top_scores, mut top_results) = match top_selected_result {
         Ok(_) => return buffer,
-        Err((t, top_scores, top_results)) => (t, top_scores, top_results),
+        Err(top_selected) => *top_selected,
     };
 
-    let mut watcher = Watcher::new(total, icon);
+    watcher.total = total;
 
     // Now we have the full queue and can just pair `.pop_back()` with `.insert()` to keep
     // the queue with best results the same size.
@@ -243,7 +315,7 @@ fn dyn_collect_all(mut iter: impl Iterator<Item = FilteredItem>, icon: Icon) ->
 fn dyn_collect_number(
     mut iter: impl Iterator<Item = FilteredItem>,
     number: usize,
-    icon: Icon,
+    mut watcher: Watcher,
 ) -> (usize, Vec<FilteredItem>) {
     // To not have problems with queues after sorting and truncating the buffer,
     // buffer has the lowest bound of `ITEMS_TO_SHOW * 2`, not `number * 2`.
@@ -253,10 +325,10 @@ fn dyn_collect_number(
 
     let (total, mut top_scores, mut top_results) = match top_selected_result {
         Ok(t) => return (t, buffer),
-        Err((t, top_scores, top_results)) => (t, top_scores, top_results),
+        Err(top_selected) => *top_selected,
     };
 
-    let mut watcher = Watcher::new(total, icon);
+    watcher.total = total;
 
     // Now we have the full queue and can just pair `.pop_back()` with
     // `.insert()` to keep the queue with best results the same size.
@@ -286,42 +358,115 @@ fn dyn_collect_number(
     (watcher.total, buffer)
 }
 
-/// Returns the ranked results after applying fuzzy filter given the query string and a list of candidates.
-pub fn dyn_run<I: Iterator<Item = SourceItem>>(
+/// Returns the total number of matched items and the ranked results, only the top `number`
+/// results are guaranteed to be the best ones if `number` is specified.
+fn dyn_collect_ranked<I: Iterator<Item = SourceItem>>(
     query: &str,
     source: Source<I>,
     FilterContext {
         icon,
         number,
-        winwidth,
         matcher,
-    }: FilterContext,
-) -> Result<()> {
+        cancellation_token: token,
+        progress_sink,
+        ..
+    }: &FilterContext,
+    notify: bool,
+) -> Result<(usize, Vec<FilteredItem>)> {
+    let token = token.clone();
     let query: Query = query.into();
-    let scorer = |item: &SourceItem| matcher.match_query(item, &query);
-    if let Some(number) = number {
-        let (total, filtered) = match source {
-            Source::Stdin => dyn_collect_number(source_iter_stdin!(scorer), number, icon),
-            Source::Exec(exec) => dyn_collect_number(source_iter_exec!(scorer, exec), number, icon),
-            Source::File(fpath) => {
-                dyn_collect_number(source_iter_file!(scorer, fpath), number, icon)
+    let processed = Arc::new(AtomicUsize::new(0));
+    let scorer = |item: &SourceItem| {
+        processed.fetch_add(1, Ordering::Relaxed);
+        matcher.match_query(item, &query)
+    };
+    let watcher = Watcher::new(*icon, notify, processed.clone(), progress_sink.clone());
+    let (total, filtered) = if let Some(number) = *number {
+        match source {
+            Source::Stdin => dyn_collect_number(source_iter_stdin!(scorer, token), number, watcher),
+            Source::Exec(exec) => {
+                dyn_collect_number(source_iter_exec!(scorer, exec, token), number, watcher)
             }
-            Source::List(list) => dyn_collect_number(source_iter_list!(scorer, list), number, icon),
-        };
-
-        let ranked = sort_initial_filtered(filtered);
-
-        printer::print_dyn_filter_results(ranked, total, number, winwidth.unwrap_or(100), icon);
+            Source::File(fpath) => match LineIndex::open(&fpath) {
+                Some(index) => {
+                    processed.fetch_add(index.len(), Ordering::Relaxed);
+                    let filtered = index.par_filter(matcher, &query, &token);
+                    dyn_collect_number(filtered.into_iter(), number, watcher)
+                }
+                None => {
+                    dyn_collect_number(source_iter_file!(scorer, fpath, token), number, watcher)
+                }
+            },
+            Source::List(list) => {
+                dyn_collect_number(source_iter_list!(scorer, list, token), number, watcher)
+            }
+            Source::Walk(walker) => {
+                dyn_collect_number(source_iter_walk!(scorer, walker, token), number, watcher)
+            }
+        }
     } else {
         let filtered = match source {
-            Source::Stdin => dyn_collect_all(source_iter_stdin!(scorer), icon),
-            Source::Exec(exec) => dyn_collect_all(source_iter_exec!(scorer, exec), icon),
-            Source::File(fpath) => dyn_collect_all(source_iter_file!(scorer, fpath), icon),
-            Source::List(list) => dyn_collect_all(source_iter_list!(scorer, list), icon),
+            Source::Stdin => dyn_collect_all(source_iter_stdin!(scorer, token), watcher),
+            Source::Exec(exec) => dyn_collect_all(source_iter_exec!(scorer, exec, token), watcher),
+            Source::File(fpath) => match LineIndex::open(&fpath) {
+                Some(index) => {
+                    processed.fetch_add(index.len(), Ordering::Relaxed);
+                    let filtered = index.par_filter(matcher, &query, &token);
+                    dyn_collect_all(filtered.into_iter(), watcher)
+                }
+                None => dyn_collect_all(source_iter_file!(scorer, fpath, token), watcher),
+            },
+            Source::List(list) => dyn_collect_all(source_iter_list!(scorer, list, token), watcher),
+            Source::Walk(walker) => {
+                dyn_collect_all(source_iter_walk!(scorer, walker, token), watcher)
+            }
         };
+        (filtered.len(), filtered)
+    };
+
+    let ranked = sort_initial_filtered(filtered);
+
+    if let Some(progress_sink) = progress_sink {
+        if !token.is_cancelled() {
+            progress_sink.send(Progress {
+                top_items: ranked
+                    .iter()
+                    .take(number.map_or(ITEMS_TO_SHOW, |n| n.min(ITEMS_TO_SHOW)))
+                    .cloned()
+                    .collect(),
+                matched: total,
+                processed: processed.load(Ordering::Relaxed),
+                done: true,
+            });
+        }
+    }
 
-        let ranked = sort_initial_filtered(filtered);
+    Ok((total, ranked))
+}
 
+/// Returns the ranked results after applying fuzzy filter given the query string and a list of candidates.
+pub fn dyn_run<I: Iterator<Item = SourceItem>>(
+    query: &str,
+    source: Source<I>,
+    filter_context: FilterContext,
+) -> Result<()> {
+    let (total, ranked) = dyn_collect_ranked(query, source, &filter_context, true)?;
+
+    // The results of a cancelled query are stale.
+    if filter_context.cancellation_token.is_cancelled() {
+        return Ok(());
+    }
+
+    let FilterContext {
+        icon,
+        number,
+        winwidth,
+        ..
+    } = filter_context;
+
+    if let Some(number) = number {
+        printer::print_dyn_filter_results(ranked, total, number, winwidth.unwrap_or(100), icon);
+    } else {
         for FilteredItem {
             source_item,
             match_indices,
@@ -338,6 +483,26 @@ pub fn dyn_run<I: Iterator<Item = SourceItem>>(
     Ok(())
 }
 
+/// Same as [`dyn_run`], but returns the total number of matched items and the top `number`
+/// ranked results instead of printing anything, `None` if the job has been cancelled.
+pub fn dyn_collect<I: Iterator<Item = SourceItem>>(
+    query: &str,
+    source: Source<I>,
+    filter_context: FilterContext,
+) -> Result<Option<(usize, Vec<FilteredItem>)>> {
+    let (total, mut ranked) = dyn_collect_ranked(query, source, &filter_context, false)?;
+
+    if filter_context.cancellation_token.is_cancelled() {
+        return Ok(None);
+    }
+
+    if let Some(number) = filter_context.number {
+        ranked.truncate(number);
+    }
+
+    Ok(Some((total, ranked)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -389,7 +554,7 @@ mod tests {
                     }
 
                     total_lines_created += 1;
-                    if total_lines_created % 99999_usize.next_power_of_two() == 0 {
+                    if total_lines_created.is_multiple_of(99999_usize.next_power_of_two()) {
                         println!("Total lines created: {}", total_lines_created)
                     }
 
@@ -397,10 +562,59 @@ mod tests {
                         .unwrap()
                         .into()
                 })
-                .take(usize::max_value() >> 8),
+                .take(usize::MAX >> 8),
             ),
             FilterContext::default().number(Some(100)),
         )
         .unwrap()
     }
+
+    #[test]
+    fn progress_sink_receives_the_final_progress() {
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let progress_sink = {
+            let received = received.clone();
+            ProgressSink::new(move |progress| received.lock().unwrap().push(progress))
+        };
+
+        let source_items = (0..100).map(|i| SourceItem::from(format!("abc{}", i % 3)));
+        let (total, _) = dyn_collect(
+            "abc2",
+            Source::List(source_items),
+            FilterContext::default()
+                .number(Some(10))
+                .progress_sink(progress_sink),
+        )
+        .unwrap()
+        .unwrap();
+
+        let received = received.lock().unwrap();
+        let last = received.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.processed, 100);
+        assert_eq!(last.matched, total);
+        assert_eq!(last.top_items.len(), 10);
+    }
+
+    #[test]
+    fn cancelled_job_stops_consuming_source() {
+        let token = crate::CancellationToken::new();
+        token.cancel();
+
+        let mut consumed = 0;
+        dyn_run(
+            "abc",
+            Source::List(std::iter::repeat_with(|| {
+                consumed += 1;
+                SourceItem::from(String::from("abc"))
+            })),
+            FilterContext::default()
+                .number(Some(100))
+                .cancellation_token(token),
+        )
+        .unwrap();
+
+        // `take_while` has to pull one item before checking the token.
+        assert!(consumed <= 1);
+    }
 }
@@ -0,0 +1,357 @@
+//! Binary index of a cached source, which is stored next to the cached file as `<file>.idx`.
+//!
+//! The index consists of a header and the table of lines, each entry of the table has the byte
+//! range of a line in the cached file and the byte range of the text to apply the fuzzy match
+//! algorithm to, e.g., the line content of a grep line. Both files are memory mapped on filtering
+//! and the lines are scanned in parallel, the lines are neither parsed nor allocated again until
+//! they are matched.
+//!
+//! All the integers are little endian.
+//!
+//! ```text
+//! magic: [u8; 8], version: u32, match_scope: u32, text_len: u64, text_mtime: u64, lines: u64
+//! [offset: u64, len: u32, span_start: u32, span_len: u32] * lines
+//! ```
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use matcher::{MatchResult, Matcher};
+use types::{FilteredItem, FuzzyText, MatchScope, MatchingText, Query, SourceItem};
+
+use crate::CancellationToken;
+
+const MAGIC: &[u8; 8] = b"MAPLEIDX";
+
+const VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 40;
+
+const ENTRY_LEN: usize = 20;
+
+/// `span_start` of the lines whose match scope can not be extracted.
+const NO_SPAN: u32 = u32::MAX;
+
+fn encode_match_scope(match_scope: MatchScope) -> u32 {
+    match match_scope {
+        MatchScope::Full => 0,
+        MatchScope::TagName => 1,
+        MatchScope::FileName => 2,
+        MatchScope::GrepLine => 3,
+    }
+}
+
+fn decode_match_scope(value: u32) -> Option<MatchScope> {
+    match value {
+        0 => Some(MatchScope::Full),
+        1 => Some(MatchScope::TagName),
+        2 => Some(MatchScope::FileName),
+        3 => Some(MatchScope::GrepLine),
+        _ => None,
+    }
+}
+
+#[inline]
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+}
+
+#[inline]
+fn read_u64(bytes: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap())
+}
+
+/// Returns the path of the index of cached file `path`.
+pub fn index_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".idx");
+    path.with_file_name(file_name)
+}
+
+/// Returns the size and the modification time in nanoseconds of the opened `file`, which
+/// identifies the content of the cached file as it's always replaced instead of being modified in
+/// place.
+fn file_stamp(file: &File) -> io::Result<(u64, u64)> {
+    let metadata = file.metadata()?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    Ok((metadata.len(), mtime))
+}
+
+/// Returns the byte ranges of the lines in `text`, the same lines as [`std::io::BufRead::lines`].
+fn line_ranges(text: &[u8]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for end in memchr::memchr_iter(b'\n', text) {
+        ranges.push((start, end));
+        start = end + 1;
+    }
+    if start < text.len() {
+        ranges.push((start, text.len()));
+    }
+    for (start, end) in ranges.iter_mut() {
+        if *end > *start && text[*end - 1] == b'\r' {
+            *end -= 1;
+        }
+    }
+    ranges
+}
+
+/// Builds the index of the cached file `path`.
+///
+/// The content is read from the opened file so that the stamp in the header always belongs to the
+/// indexed content even if the cached file is being replaced by another process meanwhile.
+pub fn build_index(path: &Path, match_scope: MatchScope) -> io::Result<()> {
+    let file = File::open(path)?;
+    let (text_len, text_mtime) = file_stamp(&file)?;
+    // SAFETY: the cached file is never modified in place.
+    let text = unsafe { Mmap::map(&file) }?;
+    let text = &text[..];
+
+    let entries = line_ranges(text)
+        .into_par_iter()
+        .map(|(start, end)| {
+            let mut entry = [0u8; ENTRY_LEN];
+            entry[..8].copy_from_slice(&(start as u64).to_le_bytes());
+            entry[8..12].copy_from_slice(&((end - start) as u32).to_le_bytes());
+
+            // The span of the lines with invalid UTF-8 is extracted on filtering as they are
+            // decoded lossily.
+            let span = std::str::from_utf8(&text[start..end])
+                .ok()
+                .and_then(|line| {
+                    let fuzzy_text = match_scope.extract(line)?;
+                    let span_start = fuzzy_text.text.as_ptr() as usize - line.as_ptr() as usize;
+                    Some((span_start as u32, fuzzy_text.text.len() as u32))
+                })
+                .unwrap_or((NO_SPAN, 0));
+            entry[12..16].copy_from_slice(&span.0.to_le_bytes());
+            entry[16..20].copy_from_slice(&span.1.to_le_bytes());
+
+            entry
+        })
+        .collect::<Vec<_>>();
+
+    let mut buffer = Vec::with_capacity(HEADER_LEN + entries.len() * ENTRY_LEN);
+    buffer.extend_from_slice(MAGIC);
+    buffer.extend_from_slice(&VERSION.to_le_bytes());
+    buffer.extend_from_slice(&encode_match_scope(match_scope).to_le_bytes());
+    buffer.extend_from_slice(&text_len.to_le_bytes());
+    buffer.extend_from_slice(&text_mtime.to_le_bytes());
+    buffer.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    entries
+        .iter()
+        .for_each(|entry| buffer.extend_from_slice(entry));
+
+    // Written to a unique temporary file first as the same cache can be indexed concurrently.
+    let index_path = index_path(path);
+    let index_dir = index_path.parent().unwrap_or_else(|| Path::new("."));
+    let mut tmp_file = tempfile::NamedTempFile::new_in(index_dir)?;
+    tmp_file.write_all(&buffer)?;
+    tmp_file.persist(&index_path).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+/// Line in the memory mapped cached file.
+struct IndexedLine<'a> {
+    text: &'a str,
+    fuzzy_text: Option<FuzzyText<'a>>,
+}
+
+impl MatchingText for IndexedLine<'_> {
+    fn full_text(&self) -> &str {
+        self.text
+    }
+
+    fn fuzzy_text(&self, _match_scope: &MatchScope) -> Option<FuzzyText<'_>> {
+        self.fuzzy_text.clone()
+    }
+}
+
+/// Byte range of the fuzzy text in a line.
+type Span = (usize, usize);
+
+/// Memory mapped cached file and its index.
+#[derive(Debug)]
+pub struct LineIndex {
+    text: Mmap,
+    index: Mmap,
+    match_scope: MatchScope,
+    lines: usize,
+}
+
+impl LineIndex {
+    /// Opens the index of the cached file `path`, returns `None` if the index does not exist or
+    /// is outdated.
+    pub fn open(path: &Path) -> Option<Self> {
+        let index_file = File::open(index_path(path)).ok()?;
+        // SAFETY: the cached file and the index are never modified in place, they are replaced by
+        // renaming a new file, the mapped old files remain intact.
+        let index = unsafe { Mmap::map(&index_file) }.ok()?;
+
+        if index.len() < HEADER_LEN || &index[..8] != MAGIC || read_u32(&index, 8) != VERSION {
+            return None;
+        }
+
+        let match_scope = decode_match_scope(read_u32(&index, 12))?;
+        let lines = read_u64(&index, 32) as usize;
+        if index.len() != HEADER_LEN + lines * ENTRY_LEN {
+            return None;
+        }
+
+        // The index is stale if the cached file has been replaced since it was built.
+        let text_file = File::open(path).ok()?;
+        if file_stamp(&text_file).ok()? != (read_u64(&index, 16), read_u64(&index, 24)) {
+            return None;
+        }
+        let text = unsafe { Mmap::map(&text_file) }.ok()?;
+        if text.len() as u64 != read_u64(&index, 16) {
+            return None;
+        }
+
+        Some(Self {
+            text,
+            index,
+            match_scope,
+            lines,
+        })
+    }
+
+    /// Returns the number of lines.
+    pub fn len(&self) -> usize {
+        self.lines
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines == 0
+    }
+
+    /// Returns the bytes of line at `idx` and the byte range of its fuzzy text.
+    fn line(&self, idx: usize) -> Option<(&[u8], Option<Span>)> {
+        let pos = HEADER_LEN + idx * ENTRY_LEN;
+        let start = read_u64(&self.index, pos) as usize;
+        let len = read_u32(&self.index, pos + 8) as usize;
+        let line = self.text.get(start..start.checked_add(len)?)?;
+
+        let span_start = read_u32(&self.index, pos + 12);
+        let span = if span_start == NO_SPAN {
+            None
+        } else {
+            let span_start = span_start as usize;
+            let span_end = span_start + read_u32(&self.index, pos + 16) as usize;
+            (span_end <= len).then_some((span_start, span_end))
+        };
+
+        Some((line, span))
+    }
+
+    /// Filters all the lines in parallel, returns the matched items in the order of lines.
+    pub fn par_filter(
+        &self,
+        matcher: &Matcher,
+        query: &Query,
+        token: &CancellationToken,
+    ) -> Vec<FilteredItem> {
+        let match_scope = matcher.match_scope();
+        let use_spans = match_scope == self.match_scope;
+
+        (0..self.lines)
+            .into_par_iter()
+            .filter_map(|idx| {
+                if token.is_cancelled() {
+                    return None;
+                }
+
+                let (bytes, span) = self.line(idx)?;
+
+                let text = match std::str::from_utf8(bytes) {
+                    Ok(text) => text,
+                    Err(_) => {
+                        let item: SourceItem = String::from_utf8_lossy(bytes).into_owned().into();
+                        return matcher
+                            .match_query(&item, query)
+                            .map(|match_result| match_result.into_filtered_item(item));
+                    }
+                };
+
+                let fuzzy_text = if use_spans {
+                    span.and_then(|(start, end)| {
+                        let matching_start = match match_scope {
+                            MatchScope::TagName => 0,
+                            _ => start,
+                        };
+                        text.get(start..end)
+                            .map(|s| FuzzyText::new(s, matching_start))
+                    })
+                } else {
+                    match_scope.extract(text)
+                };
+
+                let line = IndexedLine { text, fuzzy_text };
+
+                matcher
+                    .match_query(&line, query)
+                    .map(|MatchResult { score, indices }| {
+                        FilteredItem::new(String::from(text), score, indices)
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grep_cache");
+
+        let text = b"src/lib.rs:1:1:fn foo() {}\r\nsrc/foo.rs:2:1:fn bar() {}\n\xffinvalid foo\nno position foo";
+        std::fs::write(&path, text).unwrap();
+        build_index(&path, MatchScope::GrepLine).unwrap();
+
+        let index = LineIndex::open(&path).unwrap();
+        assert_eq!(index.len(), 4);
+
+        let matcher = Matcher::default().set_match_scope(MatchScope::GrepLine);
+        let token = CancellationToken::new();
+
+        let lines = |query: &str| {
+            index
+                .par_filter(&matcher, &query.into(), &token)
+                .into_iter()
+                .map(|item| item.source_item.raw)
+                .collect::<Vec<_>>()
+        };
+
+        // The file path is not in the match scope of grep lines.
+        assert_eq!(lines("foo"), vec!["src/lib.rs:1:1:fn foo() {}"]);
+        assert_eq!(lines("bar"), vec!["src/foo.rs:2:1:fn bar() {}"]);
+
+        // Consistent with filtering the lines without the index.
+        let expected = text
+            .split(|b| *b == b'\n')
+            .map(|line| String::from_utf8_lossy(line).trim_end().to_string())
+            .filter(|line| {
+                let item: SourceItem = line.clone().into();
+                matcher.match_query(&item, &"'foo".into()).is_some()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lines("'foo"), expected);
+
+        // Outdated once the cached file is replaced.
+        std::fs::write(&path, "src/lib.rs:1:1:foo\n").unwrap();
+        assert!(LineIndex::open(&path).is_none());
+    }
+}
@@ -7,20 +7,47 @@
 //! 3. print the top rated filtered lines to stdout.
 
 mod dynamic;
+mod index;
 mod source;
+mod walker;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use anyhow::Result;
 use rayon::prelude::*;
 
 use icon::Icon;
-use matcher::{MatchResult, MatchScope, Matcher};
+use matcher::{FuzzyAlgorithm, MatchResult, MatchScope, Matcher};
 
-pub use self::dynamic::dyn_run;
+pub use self::dynamic::{dyn_collect, dyn_run, Progress, ProgressSink};
+pub use self::index::{build_index, index_path, LineIndex};
 pub use self::source::Source;
+pub use self::walker::{WalkConfig, Walker};
 pub use matcher;
 pub use subprocess;
 pub use types::{CaseMatching, FilteredItem, Query, SourceItem};
 
+/// Token for cancelling an in-flight filtering job.
+///
+/// The job stops consuming the source and discards its results once the token is cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
 /// Context for running the filter.
 #[derive(Debug, Clone, Default)]
 pub struct FilterContext {
@@ -28,6 +55,8 @@ pub struct FilterContext {
     number: Option<usize>,
     winwidth: Option<usize>,
     matcher: Matcher,
+    cancellation_token: CancellationToken,
+    progress_sink: Option<ProgressSink>,
 }
 
 impl FilterContext {
@@ -42,6 +71,8 @@ impl FilterContext {
             number,
             winwidth,
             matcher,
+            cancellation_token: Default::default(),
+            progress_sink: None,
         }
     }
 
@@ -60,10 +91,25 @@ impl FilterContext {
         self
     }
 
+    pub fn fuzzy_algo(mut self, fuzzy_algo: FuzzyAlgorithm) -> Self {
+        self.matcher = self.matcher.set_fuzzy_algo(fuzzy_algo);
+        self
+    }
+
     pub fn match_scope(mut self, match_scope: MatchScope) -> Self {
         self.matcher = self.matcher.set_match_scope(match_scope);
         self
     }
+
+    pub fn cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    pub fn progress_sink(mut self, progress_sink: ProgressSink) -> Self {
+        self.progress_sink.replace(progress_sink);
+        self
+    }
 }
 
 /// Sorts the filtered result by the filter score.
@@ -105,3 +151,66 @@ pub fn par_filter(
         .collect();
     sort_initial_filtered(filtered)
 }
+
+/// Performs the synchorous matching in parallel on the items of `lines` at `candidates`, all the
+/// lines are matched if `candidates` is `None`.
+///
+/// Returns the unsorted matched items, which allows the caller to measure the matching and
+/// sorting separately, and the sorted indices of all the matched lines, which can be used as the
+/// `candidates` of the next run if the new query is a refinement of `query`.
+pub fn par_match_subset<T: AsRef<str> + Sync>(
+    query: &Query,
+    lines: &[T],
+    candidates: Option<&[usize]>,
+    fuzzy_matcher: &Matcher,
+) -> (Vec<FilteredItem>, Vec<usize>) {
+    let try_match = |idx: usize| {
+        let item: SourceItem = lines[idx].as_ref().into();
+        fuzzy_matcher
+            .match_query(&item, query)
+            .map(|MatchResult { score, indices }| (idx, (item, score, indices).into()))
+    };
+
+    let matched: Vec<(usize, FilteredItem)> = match candidates {
+        Some(candidates) => candidates
+            .par_iter()
+            .filter_map(|idx| try_match(*idx))
+            .collect(),
+        None => (0..lines.len())
+            .into_par_iter()
+            .filter_map(try_match)
+            .collect(),
+    };
+
+    let (matched_indices, filtered): (Vec<usize>, Vec<FilteredItem>) = matched.into_iter().unzip();
+
+    (filtered, matched_indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_match_subset() {
+        let lines = vec!["src/lib.rs", "src/main.rs", "Cargo.toml", "README.md"];
+        let matcher = Matcher::default();
+
+        let (filtered, matched) = par_match_subset(&"a".into(), &lines, None, &matcher);
+        assert_eq!(matched, vec![1, 2, 3]);
+        assert_eq!(filtered.len(), 3);
+
+        let (filtered, matched) =
+            par_match_subset(&"a .rs$".into(), &lines, Some(&matched), &matcher);
+        assert_eq!(matched, vec![1]);
+        assert_eq!(
+            filtered.len(),
+            par_filter(
+                "a .rs$",
+                lines.iter().map(|s| (*s).into()).collect(),
+                &matcher
+            )
+            .len()
+        );
+    }
+}
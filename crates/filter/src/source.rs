@@ -7,6 +7,9 @@ use subprocess::Exec;
 use matcher::Matcher;
 use types::{FilteredItem, Query, SourceItem};
 
+use crate::walker::Walker;
+use crate::{CancellationToken, LineIndex};
+
 /// Source is anything that can produce an iterator of String.
 #[derive(Debug)]
 pub enum Source<I: Iterator<Item = SourceItem>> {
@@ -14,6 +17,7 @@ pub enum Source<I: Iterator<Item = SourceItem>> {
     Exec(Box<Exec>),
     File(PathBuf),
     List(I),
+    Walk(Box<Walker>),
 }
 
 impl<I: Iterator<Item = SourceItem>> From<PathBuf> for Source<I> {
@@ -28,30 +32,41 @@ impl<I: Iterator<Item = SourceItem>> From<Exec> for Source<I> {
     }
 }
 
+impl<I: Iterator<Item = SourceItem>> From<Walker> for Source<I> {
+    fn from(walker: Walker) -> Self {
+        Self::Walk(Box::new(walker))
+    }
+}
+
 /// macros for `dyn_collect_number` and `dyn_collect_number`
 ///
 /// Generate an iterator of [`FilteredItem`] from [`Source::Stdin`].
 #[macro_export]
 macro_rules! source_iter_stdin {
-    ( $scorer:ident ) => {
-        std::io::stdin().lock().lines().filter_map(|lines_iter| {
-            lines_iter
-                .ok()
-                .map(Into::<SourceItem>::into)
-                .and_then(|item| {
-                    $scorer(&item).map(|match_result| match_result.into_filtered_item(item))
-                })
-                .map(Into::into)
-        })
+    ( $scorer:ident, $token:ident ) => {
+        std::io::stdin()
+            .lock()
+            .lines()
+            .take_while(|_| !$token.is_cancelled())
+            .filter_map(|lines_iter| {
+                lines_iter
+                    .ok()
+                    .map(Into::<SourceItem>::into)
+                    .and_then(|item| {
+                        $scorer(&item).map(|match_result| match_result.into_filtered_item(item))
+                    })
+                    .map(Into::into)
+            })
     };
 }
 
 /// Generate an iterator of [`FilteredItem`] from [`Source::Exec`].
 #[macro_export]
 macro_rules! source_iter_exec {
-    ( $scorer:ident, $exec:ident ) => {
+    ( $scorer:ident, $exec:ident, $token:ident ) => {
         std::io::BufReader::new($exec.stream_stdout()?)
             .lines()
+            .take_while(|_| !$token.is_cancelled())
             .filter_map(|lines_iter| {
                 lines_iter
                     .ok()
@@ -67,27 +82,40 @@ macro_rules! source_iter_exec {
 /// Generate an iterator of [`FilteredItem`] from [`Source::File`].
 #[macro_export]
 macro_rules! source_iter_file {
-    ( $scorer:ident, $fpath:ident ) => {
-        // To avoid Err(Custom { kind: InvalidData, error: "stream did not contain valid UTF-8" })
-        // The line stream can contain invalid UTF-8 data.
-        std::io::BufReader::new(std::fs::File::open($fpath)?)
-            .lines()
-            .filter_map(|x| {
-                x.ok()
-                    .map(Into::<SourceItem>::into)
-                    .and_then(|item| {
-                        $scorer(&item).map(|match_result| match_result.into_filtered_item(item))
-                    })
-                    .map(Into::into)
-            })
+    ( $scorer:ident, $fpath:ident, $token:ident ) => {
+        // The file can contain invalid UTF-8 data, which would be dropped by `lines()`.
+        utility::lossy_lines(std::io::BufReader::new(utility::lz4::open_decompressed(
+            $fpath,
+        )?))
+        .take_while(|_| !$token.is_cancelled())
+        .filter_map(|line| {
+            let item: SourceItem = line.into();
+            $scorer(&item).map(|match_result| match_result.into_filtered_item(item))
+        })
+        .map(Into::into)
     };
 }
 
 /// Generate an iterator of [`FilteredItem`] from [`Source::List(list)`].
 #[macro_export]
 macro_rules! source_iter_list {
-    ( $scorer:ident, $list:ident ) => {
+    ( $scorer:ident, $list:ident, $token:ident ) => {
         $list
+            .take_while(|_| !$token.is_cancelled())
+            .filter_map(|item| {
+                $scorer(&item).map(|match_result| match_result.into_filtered_item(item))
+            })
+            .map(Into::into)
+    };
+}
+
+/// Generate an iterator of [`FilteredItem`] from [`Source::Walk`].
+#[macro_export]
+macro_rules! source_iter_walk {
+    ( $scorer:ident, $walker:ident, $token:ident ) => {
+        $walker
+            .source_items()
+            .take_while(|_| !$token.is_cancelled())
             .filter_map(|item| {
                 $scorer(&item).map(|match_result| match_result.into_filtered_item(item))
             })
@@ -101,12 +129,17 @@ impl<I: Iterator<Item = SourceItem>> Source<I> {
     /// This is kind of synchronous filtering, can be used for multi-staged processing.
     pub fn filter_and_collect(self, matcher: Matcher, query: &Query) -> Result<Vec<FilteredItem>> {
         let scorer = |item: &SourceItem| matcher.match_query(item, query);
+        let token = CancellationToken::new();
 
         let filtered = match self {
-            Self::Stdin => source_iter_stdin!(scorer).collect(),
-            Self::Exec(exec) => source_iter_exec!(scorer, exec).collect(),
-            Self::File(fpath) => source_iter_file!(scorer, fpath).collect(),
-            Self::List(list) => source_iter_list!(scorer, list).collect(),
+            Self::Stdin => source_iter_stdin!(scorer, token).collect(),
+            Self::Exec(exec) => source_iter_exec!(scorer, exec, token).collect(),
+            Self::File(fpath) => match LineIndex::open(&fpath) {
+                Some(index) => index.par_filter(&matcher, query, &token),
+                None => source_iter_file!(scorer, fpath, token).collect(),
+            },
+            Self::List(list) => source_iter_list!(scorer, list, token).collect(),
+            Self::Walk(walker) => source_iter_walk!(scorer, walker, token).collect(),
         };
 
         Ok(filtered)
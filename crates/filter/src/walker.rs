@@ -0,0 +1,199 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{DirEntry, WalkBuilder, WalkState};
+
+use types::SourceItem;
+
+/// Maximum number of the found files buffered ahead of the consumer, the walking threads are
+/// blocked once it's full.
+const CHANNEL_CAPACITY: usize = 4096;
+
+/// Options for walking a directory tree natively.
+#[derive(Debug, Clone)]
+pub struct WalkConfig {
+    /// Include the hidden files and directories.
+    pub hidden: bool,
+    /// Follow the symbolic links.
+    pub follow_links: bool,
+    /// Maximum depth to descend, `None` for unlimited.
+    pub max_depth: Option<usize>,
+    /// Respect the `.gitignore`, `.ignore` and global git exclude rules.
+    pub git_ignore: bool,
+}
+
+impl Default for WalkConfig {
+    fn default() -> Self {
+        Self {
+            hidden: false,
+            follow_links: false,
+            max_depth: None,
+            git_ignore: true,
+        }
+    }
+}
+
+/// Parallel, gitignore-aware directory walker which produces the file paths
+/// relative to the root directory, similar to `fd --type f`.
+#[derive(Debug, Clone)]
+pub struct Walker {
+    root: PathBuf,
+    config: WalkConfig,
+    overrides: Option<Override>,
+}
+
+impl Walker {
+    pub fn new(root: impl AsRef<Path>, config: WalkConfig) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            config,
+            overrides: None,
+        }
+    }
+
+    /// Only walks the files matching `globs`, the globs prefixed with `!` exclude the matched
+    /// files instead, the same as `rg --glob`.
+    pub fn globs<'a>(mut self, globs: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut builder = OverrideBuilder::new(&self.root);
+        for glob in globs {
+            builder.add(glob)?;
+        }
+        self.overrides.replace(builder.build()?);
+        Ok(self)
+    }
+
+    fn build(&self) -> WalkBuilder {
+        let WalkConfig {
+            hidden,
+            follow_links,
+            max_depth,
+            git_ignore,
+        } = self.config;
+
+        let mut builder = WalkBuilder::new(&self.root);
+        builder
+            .hidden(!hidden)
+            .follow_links(follow_links)
+            .max_depth(max_depth)
+            .ignore(git_ignore)
+            .git_ignore(git_ignore)
+            .git_global(git_ignore)
+            .git_exclude(git_ignore)
+            .parents(git_ignore)
+            // The content of `.git` is never what we want even when the hidden files are included.
+            .filter_entry(|entry| entry.file_name() != ".git");
+        if let Some(ref overrides) = self.overrides {
+            builder.overrides(overrides.clone());
+        }
+        builder
+    }
+
+    /// Starts walking in a background thread and returns the stream of found files.
+    ///
+    /// The walk stops early once the returned iterator is dropped.
+    pub fn source_items(self) -> impl Iterator<Item = SourceItem> {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+
+        std::thread::spawn(move || {
+            let root = &self.root;
+            self.build().build_parallel().run(|| {
+                let sender = sender.clone();
+                Box::new(move |entry| {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(_) => return WalkState::Continue,
+                    };
+
+                    if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                        return WalkState::Continue;
+                    }
+
+                    let path = entry
+                        .path()
+                        .strip_prefix(root)
+                        .unwrap_or_else(|_| entry.path());
+
+                    match sender.send(path.to_string_lossy().into_owned().into()) {
+                        Ok(()) => WalkState::Continue,
+                        Err(_) => WalkState::Quit,
+                    }
+                })
+            });
+        });
+
+        receiver.into_iter()
+    }
+
+    /// Walks the whole tree and returns all the found files.
+    pub fn lines(self) -> Vec<String> {
+        self.source_items().map(|item| item.raw).collect()
+    }
+
+    /// Returns the directories in the tree in the sequential order, including the root.
+    fn dir_entries(&self) -> impl Iterator<Item = DirEntry> {
+        self.build()
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+    }
+
+    /// Returns the paths of the directories in the tree, including the root.
+    pub fn dirs(&self) -> impl Iterator<Item = PathBuf> {
+        self.dir_entries().map(DirEntry::into_path)
+    }
+
+    /// Returns the latest modification time of the directories in the tree and the number of
+    /// the visited directories, at most `max_dirs` directories are visited.
+    ///
+    /// A directory is modified when an entry is created, removed or renamed in it.
+    pub fn latest_dir_mtime(&self, max_dirs: usize) -> (Option<SystemTime>, usize) {
+        self.dir_entries()
+            .take(max_dirs)
+            .fold((None, 0), |(latest, count), entry| {
+                let mtime = entry.metadata().ok().and_then(|m| m.modified().ok());
+                (latest.max(mtime), count + 1)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_crate_dir() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let this_file = Path::new("src").join("walker.rs");
+
+        let lines = Walker::new(root, WalkConfig::default()).lines();
+        assert!(lines.iter().any(|l| Path::new(l) == this_file));
+        assert!(lines.iter().any(|l| l == "Cargo.toml"));
+
+        let config = WalkConfig {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let lines = Walker::new(root, config).lines();
+        assert!(lines.iter().all(|l| Path::new(l) != this_file));
+        assert!(lines.iter().any(|l| l == "Cargo.toml"));
+
+        // The blocked walking threads quit once the receiver is dropped.
+        let walker = Walker::new(root.parent().unwrap(), WalkConfig::default());
+        assert_eq!(walker.source_items().take(1).count(), 1);
+
+        let lines = Walker::new(root, WalkConfig::default())
+            .globs(["*.toml"])
+            .unwrap()
+            .lines();
+        assert_eq!(lines, vec!["Cargo.toml".to_string()]);
+        let lines = Walker::new(root, WalkConfig::default())
+            .globs(["!*.rs"])
+            .unwrap()
+            .lines();
+        assert!(lines.iter().all(|l| !l.ends_with(".rs")));
+        assert!(lines.iter().any(|l| l == "Cargo.toml"));
+    }
+}
@@ -12,18 +12,13 @@ pub const DEFAULT_FILER_ICON: char = '';
 // Each added icon length is 4 bytes.
 pub const ICON_LEN: usize = 4;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub enum Icon {
+    #[default]
     Null,
     Enabled(IconKind),
 }
 
-impl Default for Icon {
-    fn default() -> Self {
-        Self::Null
-    }
-}
-
 impl Icon {
     pub fn painter(&self) -> Option<&IconKind> {
         match self {
@@ -137,7 +132,7 @@ pub fn get_icon_or<P: AsRef<Path>>(path: P, default: IconType) -> IconType {
 
 pub fn file_icon(line: &str) -> IconType {
     let path = Path::new(line);
-    get_icon_or(&path, DEFAULT_ICON)
+    get_icon_or(path, DEFAULT_ICON)
 }
 
 pub fn prepend_icon(line: &str) -> String {
@@ -161,7 +156,7 @@ pub fn tags_kind_icon(kind: &str) -> IconType {
 
 fn buffer_tags_icon(line: &str) -> IconType {
     pattern::extract_buffer_tags_kind(line)
-        .map(|kind| tags_kind_icon(kind))
+        .map(tags_kind_icon)
         .unwrap_or(DEFAULT_ICON)
 }
 
@@ -180,7 +175,7 @@ fn proj_tags_icon(line: &str) -> IconType {
 #[inline]
 fn grep_icon(line: &str) -> IconType {
     pattern::extract_fpath_from_grep_line(line)
-        .map(|fpath| file_icon(fpath))
+        .map(file_icon)
         .unwrap_or(DEFAULT_ICON)
 }
 
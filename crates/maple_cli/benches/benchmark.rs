@@ -3,7 +3,7 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use filter::{FilteredItem, Query, SourceItem};
 use matcher::{FuzzyAlgorithm, MatchScope, Matcher};
 
-use maple_cli::command::ctags::recursive::build_recursive_ctags_cmd;
+use maple_cli::command::ctags::recursive_tags::build_recursive_ctags_cmd;
 
 fn prepare_source_items() -> Vec<SourceItem> {
     use std::io::BufRead;
@@ -19,8 +19,7 @@ fn prepare_source_items() -> Vec<SourceItem> {
 fn filter(list: Vec<SourceItem>, matcher: &Matcher, query: &Query) -> Vec<FilteredItem> {
     let scorer = |item: &SourceItem| matcher.match_query(item, query);
     list.into_iter()
-        .filter_map(|item| scorer(&item).map(|(score, indices)| (item, score, indices)))
-        .map(Into::into)
+        .filter_map(|item| scorer(&item).map(|match_result| match_result.into_filtered_item(item)))
         .collect()
 }
 
@@ -30,8 +29,7 @@ fn par_filter(list: Vec<SourceItem>, matcher: &Matcher, query: &Query) -> Vec<Fi
 
     let scorer = |item: &SourceItem| matcher.match_query(item, query);
     list.into_par_iter()
-        .filter_map(|item| scorer(&item).map(|(score, indices)| (item, score, indices)))
-        .map(Into::into)
+        .filter_map(|item| scorer(&item).map(|match_result| match_result.into_filtered_item(item)))
         .collect()
 }
 
@@ -14,7 +14,7 @@ pub enum Cmd {
     Version,
     /// Start the stdio-based service, currently there is only filer support.
     #[clap(name = "rpc")]
-    Rpc,
+    Rpc(Rpc),
     /// Execute the grep command to avoid the escape issue
     #[clap(name = "grep")]
     Grep(command::grep::Grep),
@@ -32,6 +32,9 @@ pub enum Cmd {
     /// Interact with the cache info.
     #[clap(name = "cache")]
     Cache(command::cache::Cache),
+    /// Check the external tools and directories.
+    #[clap(name = "doctor")]
+    Doctor(command::doctor::Doctor),
     /// Fuzzy filter the input.
     #[clap(name = "filter")]
     Filter(command::filter::Filter),
@@ -41,6 +44,12 @@ pub enum Cmd {
     /// Generate vim help tags.
     #[clap(name = "helptags")]
     Helptags(command::helptags::Helptags),
+    /// List the query history of a provider.
+    #[clap(name = "query-history")]
+    QueryHistory(command::query_history::QueryHistory),
+    /// Aggregate the timings traced by the RPC service.
+    #[clap(name = "stats")]
+    Stats(command::stats::Stats),
     /// Start the forerunner job of grep.
     #[clap(name = "ripgrep-forerunner")]
     RipGrepForerunner(command::grep::RipGrepForerunner),
@@ -92,6 +101,31 @@ pub struct Params {
     pub no_cache: bool,
 }
 
+#[derive(Parser, Debug)]
+pub struct Rpc {
+    /// Serve the clients connected to a unix socket or TCP address instead of stdio.
+    ///
+    /// The value is treated as a TCP address if it can be parsed as one, e.g., `127.0.0.1:6666`,
+    /// otherwise as the path of a unix socket. Only the loopback TCP addresses are accepted and
+    /// the unix socket is only accessible to the current user, as the clients can run arbitrary
+    /// source commands.
+    #[clap(long)]
+    pub listen: Option<String>,
+
+    /// Append the timings of the session events to this JSON-lines file.
+    ///
+    /// Use `maple stats <trace>` to aggregate it.
+    #[clap(long, parse(from_os_str))]
+    pub trace: Option<std::path::PathBuf>,
+
+    /// Watch the project roots served by grep2, proj_tags and dumb_jump, keep their caches and
+    /// tags DB fresh in the background.
+    ///
+    /// Only supported on Linux.
+    #[clap(long)]
+    pub watch: bool,
+}
+
 impl Params {
     pub fn into_filter_context(self) -> FilterContext {
         FilterContext::default()
@@ -110,20 +144,35 @@ impl Maple {
             Cmd::Ctags(ctags) => ctags.run(self.params)?,
             Cmd::Gtags(gtags) => gtags.run(self.params)?,
             Cmd::Cache(cache) => cache.run()?,
+            Cmd::Doctor(doctor) => doctor.run(self.log)?,
             Cmd::Blines(blines) => blines.run(self.params)?,
             Cmd::Filter(filter) => filter.run(self.params)?,
             Cmd::Helptags(helptags) => helptags.run()?,
+            Cmd::QueryHistory(query_history) => query_history.run(self.params)?,
+            Cmd::Stats(stats) => stats.run()?,
             Cmd::DumbJump(dumb_jump) => dumb_jump.run().await?,
             Cmd::RipGrepForerunner(rip_grep_forerunner) => rip_grep_forerunner.run(self.params)?,
-            Cmd::Rpc => {
+            Cmd::Rpc(rpc) => {
                 if let Some(ref log_path) = self.log {
                     crate::logger::init(log_path)?;
                 } else if let Ok(log_path) = std::env::var("VIM_CLAP_LOG_PATH") {
                     crate::logger::init(log_path)?;
                 }
 
-                crate::stdio_server::run_forever(std::io::BufReader::new(std::io::stdin()));
-                // crate::stdio_server::start()?;
+                if let Some(ref trace_path) = rpc.trace {
+                    crate::metrics::enable_trace(trace_path)?;
+                }
+
+                if rpc.watch {
+                    if let Err(error) = crate::watcher::enable() {
+                        tracing::error!(?error, "Failed to enable the watcher");
+                    }
+                }
+
+                match rpc.listen {
+                    Some(addr) => crate::stdio_server::listen(&addr).await?,
+                    None => crate::stdio_server::start()?,
+                }
             }
         };
         Ok(())
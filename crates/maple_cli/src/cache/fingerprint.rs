@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use filter::{WalkConfig, Walker};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of directories visited when the cwd is not in a git repo.
+const MAX_DIRS: usize = 10_000;
+
+/// The fingerprint of a directory is reused within this duration, e.g., by the lookups of the
+/// several caches of a project on creating a session.
+const REUSE_DURATION: Duration = Duration::from_secs(2);
+
+/// Fingerprint of a directory and the time of computing it.
+type RecentFingerprint = (Instant, Option<Fingerprint>);
+
+static RECENT_FINGERPRINTS: Lazy<Mutex<HashMap<PathBuf, RecentFingerprint>>> =
+    Lazy::new(Default::default);
+
+/// Cheap fingerprint of the inputs of a command executed in a directory.
+///
+/// It changes once the project has changed, in which case the cached results are outdated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fingerprint {
+    /// The directory is in a git repo.
+    Git {
+        /// Commit of HEAD, or the content of HEAD if it can not be resolved.
+        head: String,
+        /// Modification time of the index in microseconds since the epoch.
+        index_mtime: u64,
+    },
+    /// The directory is not in a git repo.
+    Dirs {
+        /// Latest modification time of the directories in microseconds since the epoch.
+        latest_mtime: u64,
+        /// Number of the directories.
+        count: usize,
+    },
+}
+
+impl Fingerprint {
+    /// Returns the current fingerprint of `dir`.
+    pub fn of(dir: &Path) -> Option<Self> {
+        match find_git_dir(dir) {
+            Some(git_dir) => Self::of_git_repo(&git_dir),
+            None => {
+                let walker = Walker::new(dir, WalkConfig::default());
+                let (latest_mtime, count) = walker.latest_dir_mtime(MAX_DIRS);
+                Some(Self::Dirs {
+                    latest_mtime: as_micros(latest_mtime?),
+                    count,
+                })
+            }
+        }
+    }
+
+    /// Same as [`Self::of`], but the fingerprint computed within [`REUSE_DURATION`] is reused.
+    ///
+    /// The reused one may be older than the changes made meanwhile, which only makes the cache
+    /// considered outdated on the next lookup.
+    pub fn recent(dir: &Path) -> Option<Self> {
+        if let Some((computed_at, fingerprint)) = RECENT_FINGERPRINTS.lock().get(dir) {
+            if computed_at.elapsed() < REUSE_DURATION {
+                return fingerprint.clone();
+            }
+        }
+
+        let computed_at = Instant::now();
+        let fingerprint = Self::of(dir);
+
+        let mut recent_fingerprints = RECENT_FINGERPRINTS.lock();
+        recent_fingerprints.retain(|_, (computed_at, _)| computed_at.elapsed() < REUSE_DURATION);
+        recent_fingerprints.insert(dir.to_path_buf(), (computed_at, fingerprint.clone()));
+
+        fingerprint
+    }
+
+    fn of_git_repo(git_dir: &Path) -> Option<Self> {
+        let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+
+        // Refs are shared by all the worktrees.
+        let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+            .map(|common_dir| git_dir.join(common_dir.trim()))
+            .unwrap_or_else(|_| git_dir.to_path_buf());
+
+        let head = match head.strip_prefix("ref: ") {
+            Some(reference) => resolve_ref(&common_dir, reference).unwrap_or_else(|| head.into()),
+            None => head.into(),
+        };
+
+        let index_mtime = std::fs::metadata(git_dir.join("index"))
+            .and_then(|m| m.modified())
+            .map(as_micros)
+            .unwrap_or_default();
+
+        Some(Self::Git { head, index_mtime })
+    }
+}
+
+fn as_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+/// Returns the git directory of the repo containing `dir`.
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find_map(|ancestor| {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            Some(dot_git)
+        } else if dot_git.is_file() {
+            // `gitdir: <path>` of the worktrees and submodules.
+            let content = std::fs::read_to_string(&dot_git).ok()?;
+            let git_dir = content.trim().strip_prefix("gitdir:")?.trim();
+            Some(ancestor.join(git_dir))
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns the commit `reference` points to, e.g., `refs/heads/master`.
+fn resolve_ref(common_dir: &Path, reference: &str) -> Option<String> {
+    if let Ok(commit) = std::fs::read_to_string(common_dir.join(reference)) {
+        return Some(commit.trim().into());
+    }
+
+    std::fs::read_to_string(common_dir.join("packed-refs"))
+        .ok()?
+        .lines()
+        .find_map(|line| {
+            let (commit, name) = line.split_once(' ')?;
+            (name == reference).then(|| commit.into())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_fingerprint() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path();
+        let git_dir = repo.join(".git");
+        std::fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(git_dir.join("packed-refs"), "aaa refs/heads/main\n").unwrap();
+
+        let head = |dir: &Path| match Fingerprint::of(dir) {
+            Some(Fingerprint::Git { head, .. }) => head,
+            fingerprint => panic!("Unexpected fingerprint: {:?}", fingerprint),
+        };
+
+        assert_eq!(head(&repo.join("src")), "aaa");
+
+        std::fs::write(git_dir.join("refs/heads/main"), "bbb\n").unwrap();
+        assert_eq!(head(repo), "bbb");
+    }
+}
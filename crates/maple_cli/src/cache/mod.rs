@@ -1,14 +1,72 @@
+mod fingerprint;
+
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use chrono::prelude::*;
 
+pub use self::fingerprint::Fingerprint;
 use crate::datastore::CACHE_INFO_IN_MEMORY;
 use crate::process::BaseCommand;
 use crate::utils::UtcTime;
 
 pub const MAX_DIGESTS: usize = 100;
 
+/// Default budget of the disk usage of all the cached outputs, 1GiB.
+pub const DEFAULT_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Compression of the newly cached outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    /// LZ4 frame format, fast enough to not slow down the filtering noticeably.
+    Lz4,
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Lz4 => write!(f, "lz4"),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "lz4" => Ok(Self::Lz4),
+            _ => Err(anyhow!("Unknown compression {}, expected none or lz4", s)),
+        }
+    }
+}
+
+/// Settings of the cache store configured by `maple cache config`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CacheSettings {
+    /// The stalest cached outputs are evicted once their total size exceeds this budget.
+    pub max_bytes: u64,
+    pub compression: Compression,
+    /// Whether to build the line index of the large uncompressed cached outputs.
+    pub index: bool,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+            compression: Compression::None,
+            index: false,
+        }
+    }
+}
+
 /// Digest of a cached command execution.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Digest {
@@ -27,16 +85,27 @@ pub struct Digest {
     pub total: usize,
     /// File persistent on the disk for caching the results.
     pub cached_path: PathBuf,
+    /// Fingerprint of the cwd on last execution, `None` for the digests created before the
+    /// fingerprint was introduced.
+    #[serde(default)]
+    pub fingerprint: Option<Fingerprint>,
 }
 
 impl Digest {
-    /// Creates an instance of [`Digest`].
-    pub fn new(base: BaseCommand, total: usize, cached_path: PathBuf) -> Self {
+    /// Creates an instance of [`Digest`], `fingerprint` is the fingerprint of the cwd captured
+    /// before executing the command.
+    pub fn new(
+        base: BaseCommand,
+        total: usize,
+        cached_path: PathBuf,
+        fingerprint: Option<Fingerprint>,
+    ) -> Self {
         let now = Utc::now();
         Self {
             base,
             total,
             cached_path,
+            fingerprint,
             last_visit: now,
             total_visits: 1,
             total_executions: 1,
@@ -57,18 +126,61 @@ impl Digest {
         stale_duration.num_seconds()
     }
 
+    /// Returns true if the cached results can be served, even if they may be outdated.
     pub fn is_usable(&self) -> bool {
-        let now = Utc::now();
+        // The digests without a fingerprint can not tell whether the project has changed.
+        if self.fingerprint.is_none() {
+            let now = Utc::now();
+
+            const EXECUTION_EXPIRATION_DAYS: i64 = 3;
 
-        const EXECUTION_EXPIRATION_DAYS: i64 = 3;
+            if now.signed_duration_since(self.execution_time).num_days() > EXECUTION_EXPIRATION_DAYS
+            {
+                return false;
+            }
+        }
+
+        self.cached_path.exists()
+    }
 
-        if now.signed_duration_since(self.execution_time).num_days() > EXECUTION_EXPIRATION_DAYS {
-            return false;
+    /// Returns true if the project has not changed since last execution, given the current
+    /// `fingerprint` of the cwd.
+    pub fn is_up_to_date(&self, fingerprint: Option<&Fingerprint>) -> bool {
+        match self.fingerprint {
+            Some(ref f) => Some(f) == fingerprint,
+            None => true,
         }
+    }
 
-        // TODO: when the preview content mismatches the line, the cache is outdated and should be updated.
+    fn visit(&mut self) {
+        self.total_visits += 1;
+        self.last_visit = Utc::now();
+    }
 
-        self.cached_path.exists()
+    /// Returns the size of the cached file and its index on the disk, 0 if it does not exist.
+    pub fn disk_size(&self) -> u64 {
+        [
+            self.cached_path.clone(),
+            filter::index_path(&self.cached_path),
+        ]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|m| m.len())
+        .sum()
+    }
+
+    /// Removes the cached file and its index, which are fine to be missing already.
+    fn remove_cached_file(&self) {
+        for path in [
+            self.cached_path.clone(),
+            filter::index_path(&self.cached_path),
+        ] {
+            if let Err(err) = std::fs::remove_file(&path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    tracing::error!(?err, ?path, "Failed to remove the cached file");
+                }
+            }
+        }
     }
 }
 
@@ -85,36 +197,107 @@ impl CacheInfo {
         }
     }
 
+    pub fn digests(&self) -> &[Digest] {
+        &self.digests
+    }
+
+    /// Evicts the stale digests immediately if the total size exceeds `max_bytes`, e.g., the
+    /// budget has just been shrunk.
+    ///
+    /// Returns the evicted digests.
+    pub fn fit_in_budget(&mut self, max_bytes: u64) -> Vec<Digest> {
+        self.evict_over_budget(max_bytes, None)
+    }
+
+    /// Removes the digests matching `predicate`.
+    ///
+    /// Returns the removed digests.
+    pub fn remove_digests(&mut self, predicate: impl Fn(&Digest) -> bool) -> Vec<Digest> {
+        let (removed, kept) = std::mem::take(&mut self.digests)
+            .into_iter()
+            .partition::<Vec<_>, _>(|d| predicate(d));
+        self.digests = kept;
+        removed
+    }
+
+    /// Evicts the stalest digests until the total size of the cached files fits in the budget.
+    ///
+    /// The digest of `keep` is never evicted, e.g., the one just pushed. Returns the evicted
+    /// digests.
+    fn evict_over_budget(&mut self, max_bytes: u64, keep: Option<&BaseCommand>) -> Vec<Digest> {
+        let mut total_bytes = self.digests.iter().map(Digest::disk_size).sum::<u64>();
+
+        let mut evicted_digests = Vec::new();
+        while total_bytes > max_bytes {
+            let stalest = self
+                .digests
+                .iter()
+                .enumerate()
+                .filter(|(_, d)| Some(&d.base) != keep)
+                .max_by_key(|(_, d)| d.stale_score())
+                .map(|(index, _)| index);
+
+            match stalest {
+                Some(index) => {
+                    let evicted = self.digests.swap_remove(index);
+                    total_bytes -= evicted.disk_size();
+                    tracing::debug!(base = ?evicted.base, "Evicted the stale cache digest");
+                    evicted_digests.push(evicted);
+                }
+                None => break,
+            }
+        }
+
+        evicted_digests
+    }
+
     /// Finds the digest given `base_cmd`.
     fn find_digest(&self, base_cmd: &BaseCommand) -> Option<usize> {
         self.digests.iter().position(|d| &d.base == base_cmd)
     }
 
-    /// Finds the usable digest given `base_cmd`.
-    pub fn find_digest_usable(&mut self, base_cmd: &BaseCommand) -> Option<Digest> {
-        match self.find_digest(base_cmd) {
-            Some(index) => {
-                let mut d = &mut self.digests[index];
-                if d.is_usable() {
-                    d.total_visits += 1;
-                    d.last_visit = Utc::now();
-                    // FIXME: save the latest state?
-                    Some(d.clone())
-                } else {
-                    if let Err(err) = self.prune_stale(index) {
-                        tracing::error!(?err, "Failed to prune the stale cache digest");
-                    }
-                    None
-                }
-            }
-            _ => None,
+    /// Finds the usable digest given `base_cmd`, `fingerprint` is the current fingerprint of the
+    /// cwd of `base_cmd`.
+    ///
+    /// The outdated digest is rejected but not pruned, it can still be served by
+    /// [`Self::find_digest_outdated`] until the cache is refreshed. The unusable digest is
+    /// pruned on the next write, see [`Self::merge`].
+    pub fn find_digest_usable(
+        &mut self,
+        base_cmd: &BaseCommand,
+        fingerprint: Option<&Fingerprint>,
+    ) -> Option<Digest> {
+        let index = self.find_digest(base_cmd)?;
+        let d = &mut self.digests[index];
+        if d.is_usable() && d.is_up_to_date(fingerprint) {
+            d.visit();
+            // FIXME: save the latest state?
+            Some(d.clone())
+        } else {
+            None
         }
     }
 
-    /// Pushes `digest` to the digests queue with max capacity constraint.
+    /// Finds the usable digest given `base_cmd` regardless of whether it's outdated.
+    pub fn find_digest_outdated(&mut self, base_cmd: &BaseCommand) -> Option<Digest> {
+        let index = self.find_digest(base_cmd)?;
+        let d = &mut self.digests[index];
+        if d.is_usable() {
+            d.visit();
+            Some(d.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Pushes `digest` to the digests queue with max capacity and disk usage constraints.
     ///
-    /// Also writes the memory cached info back to the disk.
-    pub fn limited_push(&mut self, digest: Digest) -> Result<()> {
+    /// Returns the evicted digests.
+    pub fn limited_push(&mut self, digest: Digest, max_bytes: u64) -> Vec<Digest> {
+        let base = digest.base.clone();
+
+        let mut evicted = Vec::new();
+
         // The digest already exists.
         if let Some(index) = self.find_digest(&digest.base) {
             let old_executions = self.digests[index].total_executions;
@@ -126,35 +309,155 @@ impl CacheInfo {
 
             if self.digests.len() > MAX_DIGESTS {
                 self.digests.sort_unstable_by_key(|k| k.stale_score());
-                self.digests.pop();
+                evicted.extend(self.digests.pop());
             }
         }
 
-        crate::datastore::store_cache_info(self)?;
+        evicted.extend(self.evict_over_budget(max_bytes, Some(&base)));
 
-        Ok(())
+        evicted
     }
 
-    /// Prunes the stale digest at index of `stale_index`.
-    pub fn prune_stale(&mut self, stale_index: usize) -> Result<()> {
-        self.digests.swap_remove(stale_index);
+    /// Merges the digests on the disk, which may have been written by the other maple processes
+    /// since loaded.
+    ///
+    /// The most recently executed one wins for each command. The unusable digests are dropped,
+    /// including the ones whose cached file has been removed by the other processes, e.g.,
+    /// `maple cache remove`, otherwise they would come back on each write.
+    pub fn merge(mut self, on_disk: Self) -> Self {
+        for digest in on_disk.digests {
+            match self.find_digest(&digest.base) {
+                Some(index) => {
+                    if digest.execution_time > self.digests[index].execution_time {
+                        self.digests[index] = digest;
+                    }
+                }
+                None => self.digests.push(digest),
+            }
+        }
 
-        crate::datastore::store_cache_info(self)?;
+        let (mut kept, stale) = self
+            .digests
+            .into_iter()
+            .partition::<Vec<_>, _>(Digest::is_usable);
+        stale.iter().for_each(Digest::remove_cached_file);
 
-        Ok(())
+        if kept.len() > MAX_DIGESTS {
+            kept.sort_unstable_by_key(|k| k.stale_score());
+            kept.drain(MAX_DIGESTS..)
+                .for_each(|evicted| evicted.remove_cached_file());
+        }
+
+        Self { digests: kept }
     }
 }
 
+/// Updates [`CACHE_INFO_IN_MEMORY`] with `update`, which returns the digests it has removed.
+///
+/// The cached files of the removed digests are deleted and the digests are written back to the
+/// disk after releasing the in-memory mutex, so that the cache lookups are never blocked by the
+/// disk I/O. Returns the removed digests.
+pub fn update_cache_info(
+    update: impl FnOnce(&mut CacheInfo) -> Vec<Digest>,
+) -> Result<Vec<Digest>> {
+    let (removed, cache_info) = {
+        let mut cache_info = CACHE_INFO_IN_MEMORY.lock();
+        let removed = update(&mut cache_info);
+        (removed, cache_info.clone())
+    };
+
+    removed.iter().for_each(Digest::remove_cached_file);
+
+    crate::datastore::store_cache_info(&cache_info)?;
+
+    Ok(removed)
+}
+
 /// Pushes the digest of the results of new fresh run to [`CACHE_INFO_IN_MEMORY`].
+///
+/// The in-memory digests must be updated immediately, otherwise the refreshed cache is not visible
+/// to this process and the outdated one keeps being served.
 pub fn push_cache_digest(digest: Digest) -> Result<()> {
-    let cache_info = CACHE_INFO_IN_MEMORY.lock();
+    let max_bytes = crate::datastore::load_cache_settings().max_bytes;
+    update_cache_info(|cache_info| cache_info.limited_push(digest, max_bytes))?;
+    Ok(())
+}
 
-    let mut cache_info_cloned = cache_info.clone();
-    tokio::spawn(async move {
-        if let Err(e) = cache_info_cloned.limited_push(digest) {
-            tracing::error!(?e, "Failed to push the cache digest");
-        }
-    });
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(())
+    #[test]
+    fn test_evict_over_budget() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+
+        let digest = |name: &str, hours_ago: i64| {
+            let cached_path = dir.join(name);
+            std::fs::write(&cached_path, vec![b'x'; 100]).unwrap();
+            let base = BaseCommand::new(format!("rg {}", name), dir.clone());
+            let fingerprint = Fingerprint::of(&dir);
+            let mut digest = Digest::new(base, 1, cached_path, fingerprint);
+            digest.execution_time = Utc::now() - chrono::Duration::hours(hours_ago);
+            digest.last_visit = digest.execution_time;
+            digest
+        };
+
+        let mut info = CacheInfo::with_capacity(MAX_DIGESTS);
+        info.digests = vec![digest("old", 10), digest("new", 1), digest("oldest", 20)];
+
+        // The stalest one is kept if it's the one just pushed.
+        let oldest = info.digests[2].base.clone();
+        let evicted = info.evict_over_budget(250, Some(&oldest));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].base.command, "rg old");
+        evicted.iter().for_each(Digest::remove_cached_file);
+
+        let mut names = info
+            .digests()
+            .iter()
+            .map(|d| d.base.command.as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["rg new", "rg oldest"]);
+        assert!(!dir.join("old").exists());
+    }
+
+    #[test]
+    fn test_merge_cache_info() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+
+        let digest = |name: &str, total: usize, hours_ago: i64| {
+            let cached_path = dir.join(name);
+            std::fs::write(&cached_path, name).unwrap();
+            let base = BaseCommand::new(format!("rg {}", name), dir.clone());
+            let mut digest = Digest::new(base, total, cached_path, None);
+            digest.execution_time = Utc::now() - chrono::Duration::hours(hours_ago);
+            digest
+        };
+
+        let in_memory = CacheInfo {
+            digests: vec![
+                digest("a", 1, 1),
+                digest("b", 1, 5),
+                digest("removed", 1, 1),
+            ],
+        };
+        let on_disk = CacheInfo {
+            digests: vec![digest("a", 2, 2), digest("b", 2, 3), digest("c", 2, 1)],
+        };
+
+        // Removed by the other process.
+        std::fs::remove_file(dir.join("removed")).unwrap();
+
+        let merged = in_memory.merge(on_disk);
+        let mut digests = merged
+            .digests()
+            .iter()
+            .map(|d| (d.base.command.as_str(), d.total))
+            .collect::<Vec<_>>();
+        digests.sort_unstable();
+        assert_eq!(digests, [("rg a", 1), ("rg b", 2), ("rg c", 2)]);
+    }
 }
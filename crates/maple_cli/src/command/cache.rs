@@ -1,12 +1,14 @@
 use std::fs::read_dir;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{self, Path, PathBuf};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use clap::{Parser, Subcommand};
 
 use utility::{clap_cache_dir, remove_dir_contents};
 
+use crate::cache::{update_cache_info, CacheSettings, Compression, Digest};
 use crate::datastore::CACHE_INFO_IN_MEMORY;
 
 /// List and remove all the cached contents.
@@ -19,10 +21,153 @@ pub struct Cache {
     /// Purge all the cached contents.
     #[clap(short, long)]
     purge: bool,
+
+    #[clap(subcommand)]
+    cmd: Option<CacheCommand>,
+}
+
+/// Manage the cached outputs of the commands.
+#[derive(Subcommand, Debug, Clone)]
+enum CacheCommand {
+    /// Show the size, age and command of each cached output.
+    Entries,
+    /// Remove the cached outputs of a project or a command.
+    Remove(Remove),
+    /// Check that each cached output is readable and complete.
+    Verify(Verify),
+    /// Show or change the disk budget, the compression and the indexing of the cached outputs.
+    Config(Config),
+    /// Print the cached output to stdout, which is decompressed if it's compressed.
+    Cat(Cat),
+}
+
+#[derive(Parser, Debug, Clone)]
+struct Remove {
+    /// Remove the cached outputs of the commands executed in this directory or its subdirectories.
+    #[clap(long, parse(from_os_str))]
+    project: Option<PathBuf>,
+
+    /// Remove the cached outputs of the commands containing this string.
+    #[clap(long)]
+    command: Option<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+struct Verify {
+    /// Remove the broken cached outputs.
+    #[clap(long)]
+    fix: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+struct Cat {
+    /// Path of the cached output.
+    #[clap(index = 1, parse(from_os_str))]
+    path: PathBuf,
+}
+
+#[derive(Parser, Debug, Clone)]
+struct Config {
+    /// Maximum disk usage of the cached outputs, e.g., 512M, 2G.
+    #[clap(long, parse(try_from_str = parse_size))]
+    max_size: Option<u64>,
+
+    /// Compression of the outputs cached from now on: none or lz4.
+    #[clap(long)]
+    compression: Option<Compression>,
+
+    /// Build the line index of the large outputs cached from now on: true or false.
+    ///
+    /// The indexed outputs are memory mapped and filtered in parallel, only the uncompressed
+    /// outputs can be indexed.
+    #[clap(long)]
+    index: Option<bool>,
+}
+
+/// Parses the human readable size like `512M` into the number of bytes.
+fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len()));
+    let multiplier: u64 = match unit.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" => 1024,
+        "M" => 1024 * 1024,
+        "G" => 1024 * 1024 * 1024,
+        _ => return Err(anyhow!("Unknown size unit {}, expected K, M or G", unit)),
+    };
+    Ok(number.trim().parse::<u64>()? * multiplier)
+}
+
+fn readable_size(size: u64) -> String {
+    if size > 1024 * 1024 * 1024 {
+        format!("{:.1}GB", size as f64 / 1024.0 / 1024.0 / 1024.0)
+    } else if size > 1024 * 1024 {
+        format!("{}MB", size / 1024 / 1024)
+    } else if size > 1024 {
+        format!("{}KB", size / 1024)
+    } else {
+        format!("{}B", size)
+    }
+}
+
+fn readable_age(digest: &Digest) -> String {
+    let age = Utc::now().signed_duration_since(digest.execution_time);
+    if age.num_days() > 0 {
+        format!("{}d", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h", age.num_hours())
+    } else if age.num_minutes() > 0 {
+        format!("{}m", age.num_minutes())
+    } else {
+        format!("{}s", age.num_seconds().max(0))
+    }
+}
+
+fn is_compressed(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map(|()| utility::lz4::is_lz4_frame(&magic))
+        .unwrap_or(false)
+}
+
+/// Returns the number of newlines and the number of lines of the cached output.
+fn count_lines(path: &Path) -> std::io::Result<(usize, usize)> {
+    let mut reader = utility::lz4::open_decompressed(path)?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut newlines = 0;
+    let mut last_byte = None;
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => break,
+            n => {
+                newlines += bytecount::count(&buffer[..n], b'\n');
+                last_byte = Some(buffer[n - 1]);
+            }
+        }
+    }
+    let lines = match last_byte {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    };
+    Ok((newlines, lines))
+}
+
+/// Returns the reason if the cached output of `digest` is broken.
+fn check_digest(digest: &Digest) -> Option<String> {
+    match count_lines(&digest.cached_path) {
+        // The outputs are cached with or without the trailing newline.
+        Ok((newlines, lines)) if newlines == digest.total || lines == digest.total => None,
+        Ok((_, lines)) => Some(format!(
+            "expected {} lines, found {} lines",
+            digest.total, lines
+        )),
+        Err(err) => Some(err.to_string()),
+    }
 }
 
 // The cache directory is not huge and pretty deep, hence the recursive version is acceptable.
-fn dir_size(path: impl Into<PathBuf>) -> std::io::Result<u64> {
+pub(crate) fn dir_size(path: impl Into<PathBuf>) -> std::io::Result<u64> {
     fn dir_size(mut dir: std::fs::ReadDir) -> std::io::Result<u64> {
         dir.try_fold(0, |acc, file| {
             let file = file?;
@@ -42,17 +187,10 @@ impl Cache {
         let cache_dir = clap_cache_dir()?;
         if self.purge {
             if let Ok(cache_size) = dir_size(&cache_dir) {
-                let readable_size = if cache_size > 1024 * 1024 {
-                    format!("{}MB", cache_size / 1024 / 1024)
-                } else if cache_size > 1024 {
-                    format!("{}KB", cache_size / 1024)
-                } else {
-                    format!("{}B", cache_size)
-                };
-                println!("Cache size: {:?}", readable_size);
+                println!("Cache size: {:?}", readable_size(cache_size));
             }
             if let Some(f) = crate::datastore::CACHE_JSON_PATH.as_deref() {
-                std::fs::remove_file(f)?;
+                crate::utils::remove_json(f)?;
                 println!("Cache metadata {} has been deleted", f.display());
             }
             remove_dir_contents(&cache_dir)?;
@@ -65,6 +203,58 @@ impl Cache {
         if self.list {
             self.list(&cache_dir)?;
         }
+        match &self.cmd {
+            Some(CacheCommand::Entries) => self.entries()?,
+            Some(CacheCommand::Remove(remove)) => remove.run()?,
+            Some(CacheCommand::Verify(verify)) => verify.run()?,
+            Some(CacheCommand::Config(config)) => config.run()?,
+            Some(CacheCommand::Cat(cat)) => cat.run()?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<()> {
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+
+        let mut digests = CACHE_INFO_IN_MEMORY.lock().digests().to_vec();
+        digests.sort_unstable_by_key(|d| d.stale_score());
+
+        writeln!(
+            lock,
+            "{:>8}  {:>5}  {:>6}  {:>8}  {:<4}  COMMAND",
+            "SIZE", "AGE", "VISITS", "TOTAL", "LZ4"
+        )?;
+
+        let mut total_size = 0;
+        for digest in &digests {
+            let size = digest.disk_size();
+            total_size += size;
+            writeln!(
+                lock,
+                "{:>8}  {:>5}  {:>6}  {:>8}  {:<4}  {} [{}]",
+                readable_size(size),
+                readable_age(digest),
+                digest.total_visits,
+                digest.total,
+                if is_compressed(&digest.cached_path) {
+                    "yes"
+                } else {
+                    "no"
+                },
+                digest.base.command,
+                digest.base.cwd.display()
+            )?;
+        }
+
+        writeln!(
+            lock,
+            "\nTotal size: {} of {}",
+            readable_size(total_size),
+            readable_size(crate::datastore::load_cache_settings().max_bytes)
+        )?;
+
         Ok(())
     }
 
@@ -102,3 +292,138 @@ impl Cache {
         Ok(())
     }
 }
+
+impl Remove {
+    fn run(&self) -> Result<()> {
+        if self.project.is_none() && self.command.is_none() {
+            return Err(anyhow!(
+                "Specify --project or --command, use --purge to remove everything"
+            ));
+        }
+
+        let project = self
+            .project
+            .as_ref()
+            .map(|p| p.canonicalize().unwrap_or_else(|_| p.clone()));
+
+        let removed = update_cache_info(|cache_info| {
+            cache_info.remove_digests(|d| {
+                project
+                    .as_ref()
+                    .map(|p| d.base.cwd.starts_with(p))
+                    .unwrap_or(true)
+                    && self
+                        .command
+                        .as_ref()
+                        .map(|c| d.base.command.contains(c.as_str()))
+                        .unwrap_or(true)
+            })
+        })?;
+
+        for digest in &removed {
+            println!(
+                "Removed: {} [{}]",
+                digest.base.command,
+                digest.base.cwd.display()
+            );
+        }
+        println!("{} cached outputs have been removed", removed.len());
+
+        Ok(())
+    }
+}
+
+impl Verify {
+    fn run(&self) -> Result<()> {
+        let digests = CACHE_INFO_IN_MEMORY.lock().digests().to_vec();
+
+        let broken = digests
+            .iter()
+            .filter_map(|digest| {
+                check_digest(digest).map(|reason| {
+                    println!(
+                        "Broken: {} [{}]: {}",
+                        digest.base.command,
+                        digest.base.cwd.display(),
+                        reason
+                    );
+                    digest.cached_path.clone()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if broken.is_empty() {
+            println!("All {} cached outputs are fine", digests.len());
+        } else if self.fix {
+            let removed = update_cache_info(|cache_info| {
+                cache_info.remove_digests(|d| broken.contains(&d.cached_path))
+            })?;
+            println!("{} broken cached outputs have been removed", removed.len());
+        } else {
+            println!(
+                "{} broken cached outputs found, rerun with --fix to remove them",
+                broken.len()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Cat {
+    fn run(&self) -> Result<()> {
+        let mut reader = utility::lz4::open_decompressed(&self.path)?;
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+        match std::io::copy(&mut reader, &mut lock) {
+            // The downstream filter may exit before reading everything.
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            res => res.map(|_| ()).map_err(Into::into),
+        }
+    }
+}
+
+impl Config {
+    fn run(&self) -> Result<()> {
+        let settings = crate::datastore::load_cache_settings();
+
+        let settings =
+            if self.max_size.is_some() || self.compression.is_some() || self.index.is_some() {
+                let settings = CacheSettings {
+                    max_bytes: self.max_size.unwrap_or(settings.max_bytes),
+                    compression: self.compression.unwrap_or(settings.compression),
+                    index: self.index.unwrap_or(settings.index),
+                };
+                crate::datastore::store_cache_settings(&settings)?;
+                update_cache_info(|cache_info| cache_info.fit_in_budget(settings.max_bytes))?;
+                settings
+            } else {
+                settings
+            };
+
+        let CacheSettings {
+            max_bytes,
+            compression,
+            index,
+        } = settings;
+        println!("Max size: {}", readable_size(max_bytes));
+        println!("Compression: {}", compression);
+        println!("Index: {}", index);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("512M").unwrap(), 512 * 1024 * 1024);
+        assert_eq!(parse_size("2gb").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("M").is_err());
+    }
+}
@@ -248,7 +248,6 @@ fn buffer_tags_lines_inner(
     let max_name_len = AtomicUsize::new(0);
 
     let tags = crate::utils::lines(cmd)?
-        .flatten()
         .par_bridge()
         .filter_map(|s| {
             let maybe_tag_info = parse_fn(&s);
@@ -273,7 +272,6 @@ fn collect_superset_context_tags(
     target_lnum: usize,
 ) -> Result<Vec<BufferTagInfo>> {
     let mut tags = crate::utils::lines(cmd)?
-        .flatten()
         .par_bridge()
         .filter_map(|s| parse_fn(&s))
         // the line of method/function name is lower.
@@ -0,0 +1,310 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use serde::Serialize;
+
+use crate::command::cache::dir_size;
+use crate::tools::ctags::{CTAGS_HAS_JSON_FEATURE, LANG_MAPS};
+use crate::tools::gtags::GTAGS_EXISTS;
+use crate::tools::ripgrep::RG_HAS_JSON_FEATURE;
+use crate::utils::PROJECT_DIRS;
+use crate::watcher::{unwatched_roots, UnwatchedRoot};
+
+/// Check the external tools and directories maple depends on.
+#[derive(Parser, Debug, Clone)]
+pub struct Doctor {
+    /// Print the report in JSON.
+    #[clap(long)]
+    json: bool,
+}
+
+/// Optional capability of an external tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct Feature {
+    pub name: &'static str,
+    pub available: bool,
+    pub detail: Option<String>,
+}
+
+impl Feature {
+    fn new(name: &'static str, available: bool) -> Self {
+        Self {
+            name,
+            available,
+            detail: None,
+        }
+    }
+}
+
+/// Status of an external executable.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolStatus {
+    pub name: &'static str,
+    /// First line of the version info, `None` if the executable can not be executed.
+    pub version: Option<String>,
+    pub features: Vec<Feature>,
+    /// Used by these providers.
+    pub used_by: &'static str,
+    pub error: Option<String>,
+}
+
+impl ToolStatus {
+    fn probe(
+        name: &'static str,
+        used_by: &'static str,
+        probe_features: impl FnOnce(&str) -> Result<Vec<Feature>>,
+    ) -> Self {
+        match run_tool(name, &["--version"]) {
+            Ok(output) => {
+                let (features, error) = match probe_features(&output) {
+                    Ok(features) => (features, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                Self {
+                    name,
+                    version: output.lines().next().map(|line| line.trim().into()),
+                    features,
+                    used_by,
+                    error,
+                }
+            }
+            Err(e) => Self {
+                name,
+                version: None,
+                features: Vec::new(),
+                used_by,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Status of a directory maple writes to.
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryStatus {
+    pub name: &'static str,
+    pub path: Option<PathBuf>,
+    /// Size in bytes.
+    pub size: Option<u64>,
+    pub writable: bool,
+}
+
+impl DirectoryStatus {
+    fn probe(name: &'static str, path: Option<PathBuf>) -> Self {
+        let size = path.as_ref().and_then(|p| dir_size(p).ok());
+        let writable = path.as_deref().map(is_writable).unwrap_or(false);
+        Self {
+            name,
+            path,
+            size,
+            writable,
+        }
+    }
+}
+
+/// Diagnostics report of the environment maple runs in.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub tools: Vec<ToolStatus>,
+    pub directories: Vec<DirectoryStatus>,
+    /// Project roots the watcher has given up, their caches are not kept fresh.
+    pub unwatched_roots: Vec<UnwatchedRoot>,
+    pub log_path: Option<PathBuf>,
+}
+
+/// Runs `program` with `args`, returns the stdout.
+fn run_tool(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow!("failed to execute `{}`: {}", program, e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`{} {}` exited with {}",
+            program,
+            args.join(" "),
+            output.status
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(".maple_doctor");
+    let writable = std::fs::write(&probe, b"").is_ok();
+    let _ = std::fs::remove_file(&probe);
+    writable
+}
+
+fn ctags_features(_version: &str) -> Result<Vec<Feature>> {
+    // Distinct languages having the file extension maps.
+    let languages = LANG_MAPS.values().collect::<HashSet<_>>().len();
+
+    Ok(vec![
+        Feature::new("json", *CTAGS_HAS_JSON_FEATURE),
+        Feature {
+            detail: Some(format!("{} languages", languages)),
+            ..Feature::new("languages", languages > 0)
+        },
+    ])
+}
+
+fn gtags_features(_version: &str) -> Result<Vec<Feature>> {
+    if *GTAGS_EXISTS {
+        Ok(Vec::new())
+    } else {
+        Err(anyhow!(
+            "not the gtags of GNU GLOBAL, dumb_jump won't use it"
+        ))
+    }
+}
+
+fn global_features(_version: &str) -> Result<Vec<Feature>> {
+    // `GTAGSLABEL=native-pygments` requires the pygments module of Python.
+    let pygments = Command::new("python3")
+        .args(["-c", "import pygments"])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    Ok(vec![Feature::new("pygments", pygments)])
+}
+
+/// Returns true if the version info of rg shows that PCRE2 is available.
+fn rg_has_pcre2(version: &str) -> bool {
+    // `features:+pcre2` since ripgrep 14, `PCRE2 10.39 is available` before.
+    version.contains("+pcre2")
+        || version
+            .lines()
+            .any(|line| line.starts_with("PCRE2") && line.contains("is available"))
+}
+
+fn rg_features(version: &str) -> Result<Vec<Feature>> {
+    Ok(vec![
+        Feature::new("pcre2", rg_has_pcre2(version)),
+        Feature::new("json", *RG_HAS_JSON_FEATURE),
+    ])
+}
+
+/// Probes the external tools and directories, `log_path` is the log file in use, if any.
+pub fn health_report(log_path: Option<PathBuf>) -> HealthReport {
+    let tools = vec![
+        ToolStatus::probe("ctags", "tags, proj_tags, dumb_jump", ctags_features),
+        ToolStatus::probe("gtags", "dumb_jump", gtags_features),
+        ToolStatus::probe("global", "dumb_jump", global_features),
+        ToolStatus::probe("rg", "grep, grep2, dumb_jump", rg_features),
+    ];
+
+    let directories = vec![
+        DirectoryStatus::probe("cache", utility::clap_cache_dir().ok()),
+        DirectoryStatus::probe("data", Some(PROJECT_DIRS.data_dir().to_path_buf())),
+    ];
+
+    HealthReport {
+        tools,
+        directories,
+        unwatched_roots: unwatched_roots(),
+        log_path,
+    }
+}
+
+impl Doctor {
+    pub fn run(&self, log_path: Option<PathBuf>) -> Result<()> {
+        let log_path = log_path.or_else(|| std::env::var_os("VIM_CLAP_LOG_PATH").map(Into::into));
+        let report = health_report(log_path);
+
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+
+        if self.json {
+            writeln!(lock, "{}", serde_json::to_string_pretty(&report)?)?;
+            return Ok(());
+        }
+
+        for tool in &report.tools {
+            let mark = if tool.version.is_some() && tool.error.is_none() {
+                "OK"
+            } else {
+                "!!"
+            };
+            writeln!(
+                lock,
+                "[{}] {}: {}",
+                mark,
+                tool.name,
+                tool.version.as_deref().unwrap_or("not found")
+            )?;
+            for feature in &tool.features {
+                writeln!(
+                    lock,
+                    "       {} {}{}",
+                    if feature.available { "+" } else { "-" },
+                    feature.name,
+                    feature
+                        .detail
+                        .as_ref()
+                        .map(|detail| format!(" ({})", detail))
+                        .unwrap_or_default()
+                )?;
+            }
+            if let Some(ref error) = tool.error {
+                writeln!(lock, "       error: {}", error)?;
+            }
+            writeln!(lock, "       used by: {}", tool.used_by)?;
+        }
+
+        for dir in &report.directories {
+            let mark = if dir.writable { "OK" } else { "!!" };
+            match dir.path {
+                Some(ref path) => writeln!(
+                    lock,
+                    "[{}] {} directory: {}, {} bytes{}",
+                    mark,
+                    dir.name,
+                    path.display(),
+                    dir.size.unwrap_or_default(),
+                    if dir.writable { "" } else { ", not writable" }
+                )?,
+                None => writeln!(lock, "[{}] {} directory: not found", mark, dir.name)?,
+            }
+        }
+
+        for unwatched in &report.unwatched_roots {
+            writeln!(
+                lock,
+                "[!!] watcher: {} is not watched since {}, {}",
+                unwatched.root.display(),
+                unwatched.time.format("%Y-%m-%d %H:%M:%S"),
+                unwatched.reason
+            )?;
+        }
+
+        match report.log_path {
+            Some(ref log_path) => writeln!(lock, "log: {}", log_path.display())?,
+            None => writeln!(lock, "log: disabled")?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rg_pcre2_feature() {
+        assert!(rg_has_pcre2(
+            "ripgrep 14.1.1\n\nfeatures:+pcre2\nsimd(compile):+SSE2\n"
+        ));
+        assert!(rg_has_pcre2(
+            "ripgrep 13.0.0\n-SIMD -AVX (compiled)\n\nPCRE2 10.39 is available (JIT is available)\n"
+        ));
+        assert!(!rg_has_pcre2(
+            "ripgrep 13.0.0\n-SIMD -AVX (compiled)\n\nPCRE2 is not available in this build of ripgrep.\n"
+        ));
+    }
+}
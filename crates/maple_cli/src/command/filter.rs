@@ -7,7 +7,7 @@ use clap::Parser;
 use filter::{
     matcher::{Bonus, FuzzyAlgorithm, MatchScope, Matcher},
     subprocess::Exec,
-    FilterContext, Source,
+    FilterContext, Source, WalkConfig, Walker,
 };
 use types::SourceItem;
 
@@ -28,7 +28,9 @@ pub struct Filter {
     #[clap(index = 1, long)]
     query: String,
 
-    /// Fuzzy matching algorithm
+    /// Fuzzy matching algorithm, one of `fzy`, `skim` and `typo`.
+    ///
+    /// `typo` is `fzy` tolerating a few typos, e.g., `recieve` can match `receive`.
     #[clap(long, parse(from_str), default_value = "fzy")]
     algo: FuzzyAlgorithm,
 
@@ -44,6 +46,22 @@ pub struct Filter {
     #[clap(long, parse(from_os_str))]
     recent_files: Option<PathBuf>,
 
+    /// Walk the directory natively and use the found files as the source, no shell command needed.
+    #[clap(long, parse(from_os_str))]
+    walk: Option<PathBuf>,
+
+    /// Include the hidden files when walking the directory.
+    #[clap(long)]
+    hidden: bool,
+
+    /// Follow the symbolic links when walking the directory.
+    #[clap(long)]
+    follow_links: bool,
+
+    /// Maximum depth to descend when walking the directory.
+    #[clap(long)]
+    max_depth: Option<usize>,
+
     /// Read input from a file instead of stdin, only absolute file path is supported.
     #[clap(long)]
     input: Option<AbsPathBuf>,
@@ -62,7 +80,8 @@ pub struct Filter {
 }
 
 impl Filter {
-    /// Firstly try building the Source from shell command, then the input file, finally reading the source from stdin.
+    /// Firstly try building the Source from shell command, then the directory walker, then the
+    /// input file, finally reading the source from stdin.
     fn generate_source<I: Iterator<Item = SourceItem>>(&self) -> Source<I> {
         if let Some(ref cmd_str) = self.cmd {
             if let Some(ref dir) = self.cmd_dir {
@@ -70,6 +89,14 @@ impl Filter {
             } else {
                 Exec::shell(cmd_str).into()
             }
+        } else if let Some(ref dir) = self.walk {
+            let config = WalkConfig {
+                hidden: self.hidden,
+                follow_links: self.follow_links,
+                max_depth: self.max_depth,
+                ..Default::default()
+            };
+            Walker::new(dir, config).into()
         } else {
             self.input
                 .as_ref()
@@ -79,16 +106,12 @@ impl Filter {
     }
 
     fn get_bonuses(&self) -> Vec<Bonus> {
-        use std::io::BufRead;
-
         let mut bonuses = vec![self.bonus.clone()];
         if let Some(ref recent_files) = self.recent_files {
             // Ignore the error cases.
             if let Ok(file) = std::fs::File::open(recent_files) {
-                let lines: Vec<String> = std::io::BufReader::new(file)
-                    .lines()
-                    .filter_map(|x| x.ok())
-                    .collect();
+                let lines: Vec<String> =
+                    utility::lossy_lines(std::io::BufReader::new(file)).collect();
                 bonuses.push(Bonus::RecentFiles(lines.into()));
             }
         }
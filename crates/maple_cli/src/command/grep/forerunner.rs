@@ -1,5 +1,5 @@
 use super::*;
-use crate::process::light::CommandEnv;
+use crate::process::light::{CommandEnv, ExecutedInfo};
 
 #[derive(Parser, Debug, Clone)]
 pub struct RipGrepForerunner {
@@ -10,6 +10,10 @@ pub struct RipGrepForerunner {
     /// Specify the threshold for writing the output of command to a tempfile.
     #[clap(long = "output-threshold", default_value = "30000")]
     output_threshold: usize,
+
+    /// Use `rg` instead of the built-in searcher.
+    #[clap(long)]
+    ripgrep: bool,
 }
 
 impl RipGrepForerunner {
@@ -29,6 +33,47 @@ impl RipGrepForerunner {
         false
     }
 
+    /// Same as [`LightCommand::execute`] but the output is produced by the built-in searcher.
+    fn execute_natively(
+        &self,
+        base_cmd: BaseCommand,
+        number: Option<usize>,
+        icon: Icon,
+    ) -> Result<ExecutedInfo> {
+        let fingerprint = base_cmd.fingerprint();
+        let lines = Searcher::new(&base_cmd.cwd).lines();
+        let total = lines.len();
+
+        let paint = |lines: &[String]| -> Vec<String> {
+            match icon.painter() {
+                Some(painter) => lines.iter().map(|line| painter.paint(line)).collect(),
+                None => lines.to_vec(),
+            }
+        };
+
+        let (lines, tempfile) = match number {
+            Some(number) => (paint(&lines[..number.min(total)]), None),
+            None if total > self.output_threshold => {
+                let tempfile = base_cmd.create_indexed_cache(
+                    fingerprint,
+                    total,
+                    lines.join("\n").as_bytes(),
+                    MatchScope::GrepLine,
+                )?;
+                (paint(&lines), Some(tempfile))
+            }
+            None => (paint(&lines), None),
+        };
+
+        Ok(ExecutedInfo {
+            total,
+            lines,
+            using_cache: false,
+            tempfile,
+            icon_added: icon.painter().is_some(),
+        })
+    }
+
     pub fn run(
         self,
         Params {
@@ -40,10 +85,10 @@ impl RipGrepForerunner {
     ) -> Result<()> {
         if !no_cache {
             if let Some(ref dir) = self.cmd_dir {
-                let base_cmd = BaseCommand::new(RG_EXEC_CMD.into(), dir.clone());
+                let base_cmd = GrepBackend::from_flag(self.ripgrep).base_command(dir.clone());
                 if let Some((total, cache)) = base_cmd.cache_info() {
                     if total > 100000 {
-                        send_response_from_cache(&cache, total as usize, SendResponse::Json, icon);
+                        send_response_from_cache(&cache, total, SendResponse::Json, icon);
                         return Ok(());
                     }
                 }
@@ -54,6 +99,17 @@ impl RipGrepForerunner {
             return Ok(());
         }
 
+        let cwd = match self.cmd_dir {
+            Some(ref d) => d.clone(),
+            None => std::env::current_dir()?,
+        };
+        let base_cmd = GrepBackend::from_flag(self.ripgrep).base_command(cwd);
+
+        if !self.ripgrep {
+            self.execute_natively(base_cmd, number, icon)?.print();
+            return Ok(());
+        }
+
         let mut std_cmd = StdCommand::new(RG_ARGS[0]);
         // Do not use --vimgrep here.
         std_cmd.args(&RG_ARGS[1..]);
@@ -74,12 +130,6 @@ impl RipGrepForerunner {
             ),
         );
 
-        let cwd = match self.cmd_dir {
-            Some(d) => d,
-            None => std::env::current_dir()?,
-        };
-        let base_cmd = BaseCommand::new(RG_EXEC_CMD.into(), cwd);
-
         light_cmd.execute(base_cmd)?.print();
 
         Ok(())
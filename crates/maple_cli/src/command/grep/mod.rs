@@ -2,16 +2,16 @@ mod forerunner;
 
 pub use self::forerunner::RipGrepForerunner;
 
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use clap::Parser;
 use itertools::Itertools;
 use rayon::prelude::*;
 
-use filter::{matcher::MatchScope, subprocess::Exec, Source};
+use filter::{matcher::MatchScope, subprocess::Exec, Source, SourceItem};
 use icon::Icon;
 use utility::is_git_repo;
 
@@ -19,9 +19,9 @@ use crate::app::Params;
 use crate::process::{
     light::{CommandEnv, LightCommand},
     rstd::StdCommand,
-    tokio::TokioCommand,
     BaseCommand,
 };
+use crate::searcher::Searcher;
 use crate::tools::ripgrep::Match;
 use crate::utils::{send_response_from_cache, SendResponse};
 
@@ -40,6 +40,92 @@ const RG_ARGS: &[&str] = &[
 // Now `.` is pushed to the end for all platforms due to https://github.com/liuchengxu/vim-clap/issues/711.
 const RG_EXEC_CMD: &str = "rg --column --line-number --no-heading --color=never --smart-case '' .";
 
+/// Key of the caches produced by the built-in searcher, which are in the same format as the
+/// output of `RG_EXEC_CMD` but not in the same order.
+const NATIVE_EXEC_CMD: &str = "native-searcher '' .";
+
+/// Program producing the grep sources and caches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GrepBackend {
+    /// The built-in searcher.
+    #[default]
+    Native,
+    /// `rg`.
+    Ripgrep,
+}
+
+impl From<&str> for GrepBackend {
+    fn from(backend: &str) -> Self {
+        match backend {
+            "rg" => Self::Ripgrep,
+            _ => Self::Native,
+        }
+    }
+}
+
+impl GrepBackend {
+    /// Returns the backend used by the commands with the `--ripgrep` flag.
+    pub fn from_flag(ripgrep: bool) -> Self {
+        if ripgrep {
+            Self::Ripgrep
+        } else {
+            Self::Native
+        }
+    }
+
+    /// Returns the command the caches of this backend are keyed by.
+    fn cache_command(&self) -> &'static str {
+        match self {
+            Self::Native => NATIVE_EXEC_CMD,
+            Self::Ripgrep => RG_EXEC_CMD,
+        }
+    }
+
+    /// Returns the [`BaseCommand`] of the caches produced by this backend in `dir`.
+    pub fn base_command(&self, dir: PathBuf) -> BaseCommand {
+        BaseCommand::new(self.cache_command().into(), dir)
+    }
+}
+
+/// Returns all the lines under `dir` in the format of `RG_EXEC_CMD`.
+fn search_all(dir: &Path, backend: GrepBackend) -> Result<Vec<String>> {
+    match backend {
+        GrepBackend::Ripgrep => {
+            let mut cmd = std::process::Command::new(RG_ARGS[0]);
+            cmd.args(&RG_ARGS[1..]).current_dir(dir);
+            rg_lines(cmd)
+        }
+        GrepBackend::Native => Ok(Searcher::new(dir).lines()),
+    }
+}
+
+/// Returns the lines of `files` relative to `dir` in the format of `RG_EXEC_CMD`.
+fn search_files(dir: &Path, files: &[String], backend: GrepBackend) -> Result<Vec<String>> {
+    if files.is_empty() {
+        return Ok(Vec::new());
+    }
+    if backend == GrepBackend::Ripgrep {
+        let mut cmd = std::process::Command::new(RG_ARGS[0]);
+        // Replace `.` with the files, which are printed as they are given.
+        cmd.args(&RG_ARGS[1..RG_ARGS.len() - 1])
+            .arg("--")
+            .args(files.iter().map(|file| format!("./{}", file)))
+            .current_dir(dir);
+        rg_lines(cmd)
+    } else {
+        Ok(Searcher::new(dir).lines_of(files))
+    }
+}
+
+fn rg_lines(mut cmd: std::process::Command) -> Result<Vec<String>> {
+    let output = cmd.output()?;
+    // rg exits with 1 if nothing is matched.
+    if output.status.code() == Some(1) && output.stderr.is_empty() {
+        return Ok(Vec::new());
+    }
+    crate::process::process_output(output)
+}
+
 #[derive(Parser, Debug, Clone)]
 pub struct Grep {
     /// Specify the query string for GREP_CMD.
@@ -48,13 +134,15 @@ pub struct Grep {
 
     /// Specify the grep command to run, normally rg will be used.
     ///
+    /// The built-in searcher is used in `--sync` mode if this is not specified.
+    ///
     /// Incase of clap can not reconginize such option: --cmd "rg --vimgrep ... "fn ul"".
     ///                                                       |-----------------|
     ///                                                   this can be seen as an option by mistake.
-    #[clap(long, required_if_eq("sync", "true"))]
+    #[clap(long)]
     grep_cmd: Option<String>,
 
-    /// Delegate to -g option of rg
+    /// Only search the files matching this glob, the same as the -g option of rg.
     #[clap(long)]
     glob: Option<String>,
 
@@ -73,6 +161,10 @@ pub struct Grep {
     /// Synchronous filtering, returns after the input stream is complete.
     #[clap(long)]
     sync: bool,
+
+    /// Use `rg` instead of the built-in searcher to produce the source.
+    #[clap(long)]
+    ripgrep: bool,
 }
 
 impl Grep {
@@ -97,11 +189,48 @@ impl Grep {
             ..
         }: Params,
     ) -> Result<()> {
-        let mut grep_cmd = self
-            .grep_cmd
-            .clone()
-            .context("--grep-cmd is required when --sync is on")?;
+        let matches = match self.grep_cmd {
+            Some(ref grep_cmd) => self.rg_matches(grep_cmd.clone(), number)?,
+            None => {
+                let dir = match self.cmd_dir {
+                    Some(ref dir) => dir.clone(),
+                    None => std::env::current_dir()?,
+                };
+                Searcher::with_pattern(dir, &self.grep_query)?
+                    .globs(self.glob.as_deref())?
+                    .search()
+                    .take(number.unwrap_or(usize::MAX))
+                    .collect()
+            }
+        };
+
+        let enable_icon = !matches!(icon, Icon::Null);
+
+        let (lines, indices): (Vec<String>, Vec<Vec<usize>>) = matches
+            .par_iter()
+            .map(|mat| mat.build_grep_line(enable_icon))
+            .unzip();
+
+        let total = lines.len();
+
+        let (lines, indices, truncated_map) = printer::truncate_grep_lines(
+            lines,
+            indices,
+            winwidth.unwrap_or(80),
+            if enable_icon { Some(2) } else { None },
+        );
+
+        if truncated_map.is_empty() {
+            utility::println_json!(total, lines, indices);
+        } else {
+            utility::println_json!(total, lines, indices, truncated_map);
+        }
 
+        Ok(())
+    }
+
+    /// Runs the rg command `grep_cmd` and parses its json output.
+    fn rg_matches(&self, mut grep_cmd: String, number: Option<usize>) -> Result<Vec<Match>> {
         if let Some(ref g) = self.glob {
             grep_cmd.push_str(" -g ");
             grep_cmd.push_str(g);
@@ -132,34 +261,11 @@ impl Grep {
         let base_cmd = BaseCommand::new(grep_cmd, std::env::current_dir()?);
         let execute_info = light_cmd.execute(base_cmd)?;
 
-        let enable_icon = !matches!(icon, Icon::Null);
-
-        let (lines, indices): (Vec<String>, Vec<Vec<usize>>) = execute_info
+        Ok(execute_info
             .lines
             .par_iter()
-            .filter_map(|s| {
-                Match::try_from(s.as_str())
-                    .ok()
-                    .map(|mat| mat.build_grep_line(enable_icon))
-            })
-            .unzip();
-
-        let total = lines.len();
-
-        let (lines, indices, truncated_map) = printer::truncate_grep_lines(
-            lines,
-            indices,
-            winwidth.unwrap_or(80),
-            if enable_icon { Some(2) } else { None },
-        );
-
-        if truncated_map.is_empty() {
-            utility::println_json!(total, lines, indices);
-        } else {
-            utility::println_json!(total, lines, indices, truncated_map);
-        }
-
-        Ok(())
+            .filter_map(|s| Match::try_from(s.as_str()).ok())
+            .collect())
     }
 
     /// Runs grep using the dyn filter.
@@ -168,7 +274,9 @@ impl Grep {
     fn dyn_run(&self, params: Params) -> Result<()> {
         let no_cache = params.no_cache;
 
-        let do_dyn_filter = |source: Source<std::iter::Empty<_>>| {
+        type GrepSource = Source<Box<dyn Iterator<Item = SourceItem>>>;
+
+        let do_dyn_filter = |source: GrepSource| {
             filter::dyn_run(
                 &self.grep_query,
                 source,
@@ -178,18 +286,28 @@ impl Grep {
             )
         };
 
-        let source: Source<std::iter::Empty<_>> = if let Some(ref tempfile) = self.input {
+        let source: GrepSource = if let Some(ref tempfile) = self.input {
             Source::File(tempfile.clone())
-        } else if let Some(ref dir) = self.cmd_dir {
-            if !no_cache {
-                let base_cmd = BaseCommand::new(RG_EXEC_CMD.into(), dir.clone());
-                if let Some(cache_file) = base_cmd.cache_file() {
-                    return do_dyn_filter(Source::File(cache_file));
+        } else {
+            let dir = match self.cmd_dir {
+                Some(ref dir) => {
+                    if !no_cache {
+                        let base_cmd =
+                            GrepBackend::from_flag(self.ripgrep).base_command(dir.clone());
+                        if let Some(cache_file) = base_cmd.cache_file() {
+                            return do_dyn_filter(Source::File(cache_file));
+                        }
+                    }
+                    dir.clone()
                 }
+                None => std::env::current_dir()?,
+            };
+
+            if self.ripgrep {
+                Exec::shell(RG_EXEC_CMD).cwd(dir).into()
+            } else {
+                Source::List(Box::new(native_source_items(dir)))
             }
-            Exec::shell(RG_EXEC_CMD).cwd(dir).into()
-        } else {
-            Exec::shell(RG_EXEC_CMD).into()
         };
 
         do_dyn_filter(source)
@@ -199,45 +317,123 @@ impl Grep {
 #[derive(Debug, Clone)]
 pub struct RgBaseCommand {
     pub inner: BaseCommand,
+    backend: GrepBackend,
 }
 
 impl RgBaseCommand {
-    pub fn new(dir: PathBuf) -> Self {
-        let inner = BaseCommand::new(RG_EXEC_CMD.into(), dir);
-        Self { inner }
+    /// Creates the command of the grep2 caches in `dir` produced by `backend`.
+    pub fn new(dir: PathBuf, backend: GrepBackend) -> Self {
+        let inner = backend.base_command(dir);
+        Self { inner, backend }
+    }
+
+    pub fn backend(&self) -> GrepBackend {
+        self.backend
     }
 
     pub fn cache_info(&self) -> Option<(usize, PathBuf)> {
         self.inner.cache_info()
     }
 
+    pub fn outdated_cache_info(&self) -> Option<(usize, PathBuf)> {
+        self.inner.outdated_cache_info()
+    }
+
+    /// Creates the cache using the backend of this command.
     pub async fn create_cache(self) -> Result<(usize, PathBuf)> {
-        let lines = TokioCommand::new(&self.inner.command)
-            .current_dir(&self.inner.cwd)
-            .lines()
-            .await?;
+        let fingerprint = self.inner.fingerprint();
+        let cwd = self.inner.cwd.clone();
+        let backend = self.backend;
+        let lines = tokio::task::spawn_blocking(move || search_all(&cwd, backend)).await??;
 
         let total = lines.len();
         let lines = lines.into_iter().join("\n");
 
-        let cache_path = self.inner.create_cache(total, lines.as_bytes())?;
+        let cache_path = self.inner.create_indexed_cache(
+            fingerprint,
+            total,
+            lines.as_bytes(),
+            MatchScope::GrepLine,
+        )?;
 
         Ok((total, cache_path))
     }
+
+    /// Updates the cache in place given the `changed` files relative to the cwd, only the files
+    /// in `existing` are searched again.
+    ///
+    /// Returns the new total, `None` if there is no cache.
+    pub fn update_cache(
+        &self,
+        changed: &HashSet<String>,
+        existing: &[String],
+    ) -> Result<Option<usize>> {
+        let fingerprint = self.inner.fingerprint();
+        let (old_total, cache_path) = match self.inner.outdated_cache_info() {
+            Some(cache) => cache,
+            None => return Ok(None),
+        };
+
+        let mut lines = utility::lz4::read_to_string(cache_path)?
+            .par_lines()
+            .filter(|line| {
+                // The paths are prefixed with `./` in the cache, but not in `changed`.
+                !matches!(
+                    pattern::extract_fpath_from_grep_line(line),
+                    Some(path) if changed.contains(path.strip_prefix("./").unwrap_or(path))
+                )
+            })
+            .map(Into::into)
+            .collect::<Vec<String>>();
+
+        // None of the changed files are in the cache or searchable, e.g., the editor swap files.
+        if lines.len() == old_total && existing.is_empty() {
+            return Ok(Some(old_total));
+        }
+
+        lines.extend(search_files(&self.inner.cwd, existing, self.backend)?);
+
+        // Keep the native cache sorted by path like the one created from scratch.
+        if self.backend == GrepBackend::Native {
+            lines.par_sort_by(|a, b| {
+                pattern::extract_fpath_from_grep_line(a)
+                    .cmp(&pattern::extract_fpath_from_grep_line(b))
+            });
+        }
+
+        let total = lines.len();
+        self.inner.clone().create_indexed_cache(
+            fingerprint,
+            total,
+            lines.join("\n").as_bytes(),
+            MatchScope::GrepLine,
+        )?;
+
+        Ok(Some(total))
+    }
 }
 
-pub fn refresh_cache(dir: impl AsRef<Path>) -> Result<usize> {
-    let mut cmd = Command::new(RG_ARGS[0]);
-    // Do not use --vimgrep here.
-    cmd.args(&RG_ARGS[1..]).current_dir(dir.as_ref());
+/// Returns the stream of all lines under `dir` in the same format as `RG_EXEC_CMD`.
+fn native_source_items(dir: impl AsRef<Path>) -> impl Iterator<Item = SourceItem> {
+    Searcher::new(dir)
+        .search()
+        .map(|mat| crate::searcher::vimgrep_line(&mat).into())
+}
 
-    let stdout = crate::process::rstd::collect_stdout(&mut cmd)?;
+pub fn refresh_cache(dir: impl AsRef<Path>, backend: GrepBackend) -> Result<usize> {
+    let base_cmd = backend.base_command(PathBuf::from(dir.as_ref()));
 
-    let total = bytecount::count(&stdout, b'\n');
+    let fingerprint = base_cmd.fingerprint();
+    let lines = search_all(dir.as_ref(), backend)?;
 
-    let base_cmd = BaseCommand::new(RG_EXEC_CMD.into(), PathBuf::from(dir.as_ref()));
+    let total = lines.len();
 
-    base_cmd.create_cache(total, &stdout)?;
+    base_cmd.create_indexed_cache(
+        fingerprint,
+        total,
+        lines.join("\n").as_bytes(),
+        MatchScope::GrepLine,
+    )?;
 
     Ok(total)
 }
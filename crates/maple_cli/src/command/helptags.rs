@@ -18,7 +18,7 @@ pub struct Helptags {
 }
 
 #[inline]
-fn strip_trailing_slash(x: &str) -> Cow<str> {
+fn strip_trailing_slash(x: &str) -> Cow<'_, str> {
     if x.ends_with('/') {
         let mut x: String = x.into();
         x.pop();
@@ -1,9 +1,12 @@
 pub mod blines;
 pub mod cache;
 pub mod ctags;
+pub mod doctor;
 pub mod dumb_jump;
 pub mod exec;
 pub mod filter;
 pub mod grep;
 pub mod gtags;
 pub mod helptags;
+pub mod query_history;
+pub mod stats;
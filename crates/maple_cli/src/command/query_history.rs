@@ -0,0 +1,40 @@
+use std::io::Write;
+
+use anyhow::Result;
+use clap::Parser;
+use matcher::Matcher;
+
+use crate::app::Params;
+use crate::datastore::QUERY_HISTORY_IN_MEMORY;
+
+/// List the queries recorded on exiting a provider, ranked by frecency.
+#[derive(Parser, Debug, Clone)]
+pub struct QueryHistory {
+    /// Id of the provider, e.g., grep2.
+    #[clap(index = 1)]
+    provider_id: String,
+
+    /// Only list the queries matching this query.
+    #[clap(index = 2, default_value = "")]
+    query: String,
+}
+
+impl QueryHistory {
+    pub fn run(&self, params: Params) -> Result<()> {
+        let matcher = Matcher::default().set_case_matching(params.case_matching);
+
+        let queries = QUERY_HISTORY_IN_MEMORY.lock().filter_on_query(
+            &self.provider_id,
+            &self.query,
+            &matcher,
+        );
+
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+        for item in queries.iter().take(params.number.unwrap_or(queries.len())) {
+            writeln!(lock, "{}", item.source_item.raw)?;
+        }
+
+        Ok(())
+    }
+}
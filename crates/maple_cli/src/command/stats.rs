@@ -0,0 +1,77 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::metrics::{Metrics, Sample};
+
+/// Aggregate the trace file written by `maple rpc --trace`.
+#[derive(Parser, Debug, Clone)]
+pub struct Stats {
+    /// Path of the JSON-lines trace file.
+    #[clap(index = 1, parse(from_os_str))]
+    trace: PathBuf,
+
+    /// Print the summary in JSON.
+    #[clap(long)]
+    json: bool,
+}
+
+impl Stats {
+    /// Only the latest samples of each provider and stage are aggregated, the same as the
+    /// `stats` RPC method.
+    pub fn run(&self) -> Result<()> {
+        let mut metrics = Metrics::default();
+
+        for line in BufReader::new(std::fs::File::open(&self.trace)?).lines() {
+            match serde_json::from_str::<Sample>(&line?) {
+                Ok(sample) => metrics.record(sample),
+                Err(error) => tracing::debug!(?error, "Skipped the malformed sample"),
+            }
+        }
+
+        let summary = metrics.summary();
+
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+
+        if self.json {
+            writeln!(lock, "{}", serde_json::to_string_pretty(&summary)?)?;
+            return Ok(());
+        }
+
+        writeln!(
+            lock,
+            "{:<16} {:<10} {:>7} {:>10} {:>10} {:>10} {:>10} {:>14} {:>7} {:>9}",
+            "provider",
+            "stage",
+            "count",
+            "p50(ms)",
+            "p90(ms)",
+            "p99(ms)",
+            "max(ms)",
+            "items/s",
+            "errors",
+            "timeouts"
+        )?;
+        for s in summary {
+            writeln!(
+                lock,
+                "{:<16} {:<10} {:>7} {:>10.3} {:>10.3} {:>10.3} {:>10.3} {:>14.0} {:>7} {:>9}",
+                s.provider_id,
+                serde_json::to_value(s.stage)?.as_str().unwrap_or_default(),
+                s.count,
+                s.p50_ms,
+                s.p90_ms,
+                s.p99_ms,
+                s.max_ms,
+                s.items_per_sec,
+                s.errors,
+                s.timeouts
+            )?;
+        }
+
+        Ok(())
+    }
+}
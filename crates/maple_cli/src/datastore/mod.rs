@@ -7,9 +7,12 @@ use anyhow::Result;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 
-use crate::cache::{CacheInfo, MAX_DIGESTS};
+use crate::cache::{CacheInfo, CacheSettings, MAX_DIGESTS};
+use crate::query_history::QueryHistory;
 use crate::recent_files::SortedRecentFiles;
+use crate::resume::SessionSnapshots;
 use crate::utils::{generate_data_file_path, load_json};
+use crate::watcher::UnwatchedRoot;
 
 // TODO: use mmap?
 
@@ -24,6 +27,11 @@ pub static CACHE_INFO_IN_MEMORY: Lazy<Arc<Mutex<CacheInfo>>> = Lazy::new(|| {
     Arc::new(Mutex::new(maybe_persistent))
 });
 
+const CACHE_SETTINGS_FILENAME: &str = "cache_settings.json";
+
+pub static CACHE_SETTINGS_JSON_PATH: Lazy<Option<PathBuf>> =
+    Lazy::new(|| generate_data_file_path(CACHE_SETTINGS_FILENAME).ok());
+
 const RECENT_FILES_FILENAME: &str = "recent_files.json";
 
 pub static RECENT_FILES_JSON_PATH: Lazy<Option<PathBuf>> =
@@ -36,10 +44,92 @@ pub static RECENT_FILES_IN_MEMORY: Lazy<Mutex<SortedRecentFiles>> = Lazy::new(||
     Mutex::new(maybe_persistent)
 });
 
+const QUERY_HISTORY_FILENAME: &str = "query_history.json";
+
+pub static QUERY_HISTORY_JSON_PATH: Lazy<Option<PathBuf>> =
+    Lazy::new(|| generate_data_file_path(QUERY_HISTORY_FILENAME).ok());
+
+pub static QUERY_HISTORY_IN_MEMORY: Lazy<Mutex<QueryHistory>> = Lazy::new(|| {
+    let maybe_persistent = load_json(QUERY_HISTORY_JSON_PATH.as_deref()).unwrap_or_default();
+    Mutex::new(maybe_persistent)
+});
+
+const SESSION_SNAPSHOTS_FILENAME: &str = "session_snapshots.json";
+
+pub static SESSION_SNAPSHOTS_JSON_PATH: Lazy<Option<PathBuf>> =
+    Lazy::new(|| generate_data_file_path(SESSION_SNAPSHOTS_FILENAME).ok());
+
+pub static SESSION_SNAPSHOTS_IN_MEMORY: Lazy<Mutex<SessionSnapshots>> = Lazy::new(|| {
+    let maybe_persistent = load_json(SESSION_SNAPSHOTS_JSON_PATH.as_deref()).unwrap_or_default();
+    Mutex::new(maybe_persistent)
+});
+
+const UNWATCHED_ROOTS_FILENAME: &str = "unwatched_roots.json";
+
+pub static UNWATCHED_ROOTS_JSON_PATH: Lazy<Option<PathBuf>> =
+    Lazy::new(|| generate_data_file_path(UNWATCHED_ROOTS_FILENAME).ok());
+
+/// Writes `cache_info` to the disk, merged with the digests written by the other maple
+/// processes, see [`CacheInfo::merge`].
+///
+/// The merged digests are also kept in [`CACHE_INFO_IN_MEMORY`], merged again with the in-memory
+/// ones in case they have been updated meanwhile, so that the caches refreshed by the other
+/// processes are visible to this process as well.
 pub fn store_cache_info(cache_info: &CacheInfo) -> Result<()> {
-    crate::utils::write_json(cache_info, CACHE_JSON_PATH.as_ref())
+    let merged = crate::utils::merge_json(CACHE_JSON_PATH.as_ref(), |on_disk| {
+        cache_info.clone().merge(on_disk.unwrap_or_default())
+    })?;
+    if let Some(merged) = merged {
+        let mut in_memory = CACHE_INFO_IN_MEMORY.lock();
+        *in_memory = std::mem::take(&mut *in_memory).merge(merged);
+    }
+    Ok(())
+}
+
+/// Reads the cache settings from the disk.
+///
+/// The settings are never kept in memory as they can be changed by `maple cache config` while
+/// the other maple processes are running.
+pub fn load_cache_settings() -> CacheSettings {
+    load_json(CACHE_SETTINGS_JSON_PATH.as_deref()).unwrap_or_default()
+}
+
+pub fn store_cache_settings(settings: &CacheSettings) -> Result<()> {
+    crate::utils::write_json(settings, CACHE_SETTINGS_JSON_PATH.as_ref())
+}
+
+/// Replaces the recent files on the disk with the result of `merge`, see [`merge_json`].
+///
+/// [`merge_json`]: crate::utils::merge_json
+pub fn merge_recent_files(
+    merge: impl FnOnce(Option<SortedRecentFiles>) -> SortedRecentFiles,
+) -> Result<Option<SortedRecentFiles>> {
+    crate::utils::merge_json(RECENT_FILES_JSON_PATH.as_ref(), merge)
+}
+
+/// Replaces the query history on the disk with the result of `merge`, see [`merge_json`].
+///
+/// [`merge_json`]: crate::utils::merge_json
+pub fn merge_query_history(
+    merge: impl FnOnce(Option<QueryHistory>) -> QueryHistory,
+) -> Result<Option<QueryHistory>> {
+    crate::utils::merge_json(QUERY_HISTORY_JSON_PATH.as_ref(), merge)
+}
+
+/// Replaces the session snapshots on the disk with the result of `merge`, see [`merge_json`].
+///
+/// [`merge_json`]: crate::utils::merge_json
+pub fn merge_session_snapshots(
+    merge: impl FnOnce(Option<SessionSnapshots>) -> SessionSnapshots,
+) -> Result<Option<SessionSnapshots>> {
+    crate::utils::merge_json(SESSION_SNAPSHOTS_JSON_PATH.as_ref(), merge)
 }
 
-pub fn store_recent_files(recent_files: &SortedRecentFiles) -> Result<()> {
-    crate::utils::write_json(&recent_files, RECENT_FILES_JSON_PATH.as_ref())
+/// Replaces the unwatched roots on the disk with the result of `merge`, see [`merge_json`].
+///
+/// [`merge_json`]: crate::utils::merge_json
+pub fn merge_unwatched_roots(
+    merge: impl FnOnce(Option<Vec<UnwatchedRoot>>) -> Vec<UnwatchedRoot>,
+) -> Result<Option<Vec<UnwatchedRoot>>> {
+    crate::utils::merge_json(UNWATCHED_ROOTS_JSON_PATH.as_ref(), merge)
 }
@@ -12,18 +12,48 @@ pub struct Usage {
     pub line: String,
     /// Highlights of matched elements.
     pub indices: Vec<usize>,
+    /// Empty if the usage is not addressable.
+    pub path: String,
+    pub line_number: usize,
+    /// 1-based.
+    pub column: usize,
+    /// Source line of the usage.
+    pub pattern: String,
 }
 
 impl From<AddressableUsage> for Usage {
     fn from(addressable_usage: AddressableUsage) -> Self {
-        let AddressableUsage { line, indices, .. } = addressable_usage;
-        Self { line, indices }
+        let AddressableUsage {
+            line,
+            indices,
+            path,
+            line_number,
+            column,
+            pattern,
+        } = addressable_usage;
+        Self {
+            line,
+            indices,
+            path,
+            line_number,
+            column,
+            pattern,
+        }
     }
 }
 
 impl Usage {
     pub fn new(line: String, indices: Vec<usize>) -> Self {
-        Self { line, indices }
+        Self {
+            line,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if the location of usage is known.
+    pub fn is_addressable(&self) -> bool {
+        !self.path.is_empty()
     }
 }
 
@@ -34,6 +64,9 @@ pub struct AddressableUsage {
     pub indices: Vec<usize>,
     pub path: String,
     pub line_number: usize,
+    /// 1-based.
+    pub column: usize,
+    pub pattern: String,
 }
 
 impl PartialEq for AddressableUsage {
@@ -95,6 +128,10 @@ impl Usages {
         self.0.par_iter()
     }
 
+    pub fn get(&self, index: usize) -> Option<&Usage> {
+        self.0.get(index)
+    }
+
     pub fn get_line(&self, index: usize) -> Option<&str> {
         self.0.get(index).map(|usage| usage.line.as_str())
     }
@@ -104,8 +104,6 @@ impl<'a, P: AsRef<Path> + Hash> CtagsSearcher<'a, P> {
 
         let cmd = self.build_exec(query, query_type);
 
-        Ok(crate::utils::lines(cmd)?
-            .flatten()
-            .filter_map(|s| Symbol::from_readtags(&s)))
+        Ok(crate::utils::lines(cmd)?.filter_map(|s| Symbol::from_readtags(&s)))
     }
 }
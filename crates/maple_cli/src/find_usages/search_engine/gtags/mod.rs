@@ -101,6 +101,30 @@ impl GtagsSearcher {
         }
     }
 
+    /// Updates the tags of `files` relative to the project root only, the removed files are
+    /// removed from the DB too.
+    pub fn update_files(&self, files: &[String]) -> Result<()> {
+        for file in files {
+            let exit_status = self
+                .global()
+                .env("GTAGSLABEL", "native-pygments")
+                .cwd(&self.project_root)
+                .arg("--single-update")
+                .arg(file)
+                .join()?;
+
+            if !exit_status.success() {
+                return Err(anyhow!(
+                    "Updating gtags of {} failed, exit_status: {:?}",
+                    file,
+                    exit_status
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Search definition tags exactly matching `keyword`.
     pub fn search_definitions(&self, keyword: &str) -> Result<impl Iterator<Item = Symbol>> {
         let cmd = self
@@ -134,6 +158,7 @@ impl GtagsSearcher {
                         kind_weight,
                         path: symbol.path, // TODO: perhaps path_weight? Lower the weight of path containing `test`.
                         line_number: symbol.line_number,
+                        pattern: symbol.pattern,
                     })
             })
             .collect::<Vec<_>>();
@@ -167,9 +192,7 @@ impl GtagsSearcher {
 
 // Returns a stream of tag parsed from the gtags output.
 fn execute(cmd: Exec) -> Result<impl Iterator<Item = Symbol>> {
-    Ok(crate::utils::lines(cmd)?
-        .flatten()
-        .filter_map(|s| Symbol::from_gtags(&s)))
+    Ok(crate::utils::lines(cmd)?.filter_map(|s| Symbol::from_gtags(&s)))
 }
 
 /// Used for sorting the usages from gtags properly.
@@ -179,6 +202,7 @@ struct GtagsUsage {
     indices: Vec<usize>,
     line_number: usize,
     path: String,
+    pattern: String,
     kind_weight: usize,
 }
 
@@ -189,22 +213,24 @@ impl GtagsUsage {
             indices: self.indices,
             path: self.path,
             line_number: self.line_number,
+            column: 1,
+            pattern: self.pattern,
         }
     }
 }
 
 impl PartialOrd for GtagsUsage {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some((self.kind_weight, &self.path, self.line_number).cmp(&(
-            other.kind_weight,
-            &other.path,
-            other.line_number,
-        )))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for GtagsUsage {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        (self.kind_weight, &self.path, self.line_number).cmp(&(
+            other.kind_weight,
+            &other.path,
+            other.line_number,
+        ))
     }
 }
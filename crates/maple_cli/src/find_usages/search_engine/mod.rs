@@ -14,31 +14,27 @@ pub use self::regex::RegexSearcher;
 use super::AddressableUsage;
 
 /// When spawning the ctags/gtags request, we can specify the searching strategy.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
 #[allow(unused)]
 pub enum QueryType {
     /// Prefix match.
     StartWith,
     /// Exact match.
+    #[default]
     Exact,
     /// Substring match.
     Contain,
-    ///
+    /// Reserved, not implemented yet.
     Inherit,
 }
 
-impl Default for QueryType {
-    fn default() -> Self {
-        Self::Exact
-    }
-}
-
 /// Unified tag info.
 ///
 /// Parsed from `ctags` and `gtags` output.
 #[derive(Default, Debug)]
 pub struct Symbol {
     /// None for `gtags`.
+    #[allow(unused)]
     pub name: Option<String>,
     pub path: String,
     pub pattern: String,
@@ -142,7 +138,7 @@ impl Symbol {
         let indices = if let Some(idx) = found {
             let start = formatted.len() + idx;
             let end = start + query.len();
-            Some((start..end).into_iter().collect())
+            Some((start..end).collect())
         } else {
             None
         };
@@ -172,6 +168,8 @@ impl Symbol {
             indices,
             path: self.path,
             line_number: self.line_number,
+            column: 1,
+            pattern: self.pattern,
         }
     }
 }
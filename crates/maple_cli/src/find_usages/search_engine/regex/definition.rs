@@ -30,7 +30,7 @@ static RG_LANGUAGE_EXT_TABLE: Lazy<HashMap<&str, &str>> = Lazy::new(|| {
             values
                 .par_iter()
                 .filter_map(|v| {
-                    v.split('.').last().and_then(|ext| {
+                    v.split('.').next_back().and_then(|ext| {
                         // Simply ignore the abnormal cases.
                         if ext.contains('[') || ext.contains('*') {
                             None
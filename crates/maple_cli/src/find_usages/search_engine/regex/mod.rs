@@ -34,6 +34,8 @@ pub struct RegexUsage {
     pub indices: Vec<usize>,
     pub path: String,
     pub line_number: usize,
+    pub column: usize,
+    pub pattern: String,
     pub pattern_priority: Priority,
 }
 
@@ -44,6 +46,8 @@ impl From<RegexUsage> for AddressableUsage {
             indices,
             path,
             line_number,
+            column,
+            pattern,
             ..
         } = regex_usage;
         Self {
@@ -51,6 +55,8 @@ impl From<RegexUsage> for AddressableUsage {
             indices,
             path,
             line_number,
+            column,
+            pattern,
         }
     }
 }
@@ -62,6 +68,8 @@ impl RegexUsage {
             indices,
             path: matched.path().into(),
             line_number: matched.line_number() as usize,
+            column: matched.column() + 1,
+            pattern: matched.pattern().trim_end().into(),
             pattern_priority: matched.pattern_priority(),
         }
     }
@@ -69,17 +77,17 @@ impl RegexUsage {
 
 impl PartialOrd for RegexUsage {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some((self.pattern_priority, &self.path, self.line_number).cmp(&(
-            other.pattern_priority,
-            &other.path,
-            other.line_number,
-        )))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for RegexUsage {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        (self.pattern_priority, &self.path, self.line_number).cmp(&(
+            other.pattern_priority,
+            &other.path,
+            other.line_number,
+        ))
     }
 }
 
@@ -3,13 +3,18 @@ mod cache;
 mod datastore;
 mod find_usages;
 mod logger;
+mod metrics;
 mod paths;
 mod previewer;
 mod process;
+mod query_history;
 mod recent_files;
+mod resume;
+mod searcher;
 mod stdio_server;
 mod tools;
 mod utils;
+mod watcher;
 
 pub mod command;
 /// Re-exports.
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use log4rs::{
@@ -6,6 +6,15 @@ use log4rs::{
     config::{Appender, Config, Root},
     encode::pattern::PatternEncoder,
 };
+use once_cell::sync::OnceCell;
+
+/// Path of the log file, set once the logging system is initialized.
+static LOG_PATH: OnceCell<PathBuf> = OnceCell::new();
+
+/// Returns the path of the log file in use, if any.
+pub fn log_path() -> Option<PathBuf> {
+    LOG_PATH.get().cloned()
+}
 
 pub fn init<P: AsRef<Path> + std::fmt::Debug>(log_path: P) -> Result<()> {
     let encoder = PatternEncoder::new(
@@ -34,6 +43,7 @@ pub fn init<P: AsRef<Path> + std::fmt::Debug>(log_path: P) -> Result<()> {
     // if you are trying to debug an issue and need more logs on then turn it off
     // once you are done.
     let _handle = log4rs::init_config(config)?;
+    let _ = LOG_PATH.set(log_path.as_ref().to_path_buf());
     tracing::debug!(?log_path, "Logging system initialized");
     Ok(())
 }
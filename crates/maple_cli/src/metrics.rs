@@ -0,0 +1,301 @@
+//! Timing of the stages of handling the session events.
+//!
+//! The samples are aggregated in memory for the `stats` RPC method and optionally appended to a
+//! JSON-lines trace file, which can be aggregated later by `maple stats`.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of the samples kept in memory per provider and stage.
+const MAX_SAMPLES: usize = 1_000;
+
+static METRICS: Lazy<Mutex<Metrics>> = Lazy::new(Default::default);
+
+static TRACE_WRITER: OnceCell<Mutex<LineWriter<File>>> = OnceCell::new();
+
+/// Stage of handling a session event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Collecting the source items, e.g., running the source command or rg.
+    Source,
+    /// Matching the source items against the query.
+    Match,
+    /// Sorting the matched items.
+    Sort,
+    /// Reading, matching and ranking the source not kept in memory in one pass.
+    Filter,
+    /// Searching the usages using rg or the tags.
+    Search,
+    /// Decorating the lines for displaying, e.g., truncating the long lines and adding icons.
+    Decorate,
+    /// Serializing the message to Vim, the items are the bytes of message.
+    Serialize,
+    /// Writing the message to Vim, the items are the bytes of message.
+    ///
+    /// Slow when Vim is busy with rendering the previous messages.
+    Write,
+}
+
+/// How a stage ended.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    #[default]
+    Ok,
+    Error,
+    /// The stage was not waited for anymore, it may still be running in the background.
+    Timeout,
+}
+
+/// Timing of a stage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub provider_id: String,
+    pub stage: Stage,
+    /// Elapsed time in microseconds.
+    pub elapsed_us: u64,
+    /// Number of the items produced by this stage.
+    pub items: usize,
+    /// Missing in the trace files written before the outcome was recorded.
+    #[serde(default)]
+    pub outcome: Outcome,
+}
+
+/// Aggregated timings of a stage of a provider.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageSummary {
+    pub provider_id: String,
+    pub stage: Stage,
+    pub count: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    pub items_per_sec: f64,
+    pub errors: usize,
+    pub timeouts: usize,
+}
+
+/// Recent samples of each provider and stage.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    samples: HashMap<(String, Stage), VecDeque<Sample>>,
+}
+
+impl Metrics {
+    pub fn record(&mut self, sample: Sample) {
+        let samples = self
+            .samples
+            .entry((sample.provider_id.clone(), sample.stage))
+            .or_default();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Returns the summary of each provider and stage, sorted by provider and stage.
+    pub fn summary(&self) -> Vec<StageSummary> {
+        let mut summary = self
+            .samples
+            .iter()
+            .map(|((provider_id, stage), samples)| {
+                let mut elapsed = samples.iter().map(|s| s.elapsed_us).collect::<Vec<_>>();
+                elapsed.sort_unstable();
+
+                let percentile = |p: usize| {
+                    let idx = (elapsed.len() * p / 100).min(elapsed.len() - 1);
+                    elapsed[idx] as f64 / 1000.0
+                };
+
+                let total_us: u64 = elapsed.iter().sum();
+                let total_items: usize = samples.iter().map(|s| s.items).sum();
+                let count_outcome =
+                    |outcome: Outcome| samples.iter().filter(|s| s.outcome == outcome).count();
+
+                StageSummary {
+                    provider_id: provider_id.clone(),
+                    stage: *stage,
+                    count: samples.len(),
+                    p50_ms: percentile(50),
+                    p90_ms: percentile(90),
+                    p99_ms: percentile(99),
+                    max_ms: percentile(100),
+                    items_per_sec: if total_us == 0 {
+                        0.0
+                    } else {
+                        total_items as f64 * 1_000_000.0 / total_us as f64
+                    },
+                    errors: count_outcome(Outcome::Error),
+                    timeouts: count_outcome(Outcome::Timeout),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        summary.sort_by(|a, b| (&a.provider_id, a.stage).cmp(&(&b.provider_id, b.stage)));
+
+        summary
+    }
+}
+
+/// Appends the samples to the JSON-lines file `path` from now on.
+pub fn enable_trace(path: &Path) -> Result<()> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if TRACE_WRITER.set(Mutex::new(LineWriter::new(file))).is_err() {
+        tracing::debug!(?path, "Trace file has already been enabled");
+    }
+    Ok(())
+}
+
+/// Records the timing of `stage`.
+pub fn record(provider_id: &str, stage: Stage, elapsed: Duration, items: usize, outcome: Outcome) {
+    let elapsed_us = elapsed.as_micros() as u64;
+
+    tracing::debug!(
+        provider_id,
+        ?stage,
+        elapsed_us,
+        items,
+        ?outcome,
+        "Stage finished"
+    );
+
+    let sample = Sample {
+        provider_id: provider_id.into(),
+        stage,
+        elapsed_us,
+        items,
+        outcome,
+    };
+
+    if let Some(writer) = TRACE_WRITER.get() {
+        let mut writer = writer.lock();
+        if let Err(error) = serde_json::to_writer(&mut *writer, &sample)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| writeln!(writer).map_err(Into::into))
+        {
+            tracing::error!(?error, "Failed to write the trace file");
+        }
+    }
+
+    METRICS.lock().record(sample);
+}
+
+/// Returns the summary of the samples recorded by this process.
+pub fn summary() -> Vec<StageSummary> {
+    METRICS.lock().summary()
+}
+
+/// Timer of a stage, the timing is recorded on [`StageTimer::finish`].
+#[derive(Debug)]
+pub struct StageTimer<'a> {
+    provider_id: &'a str,
+    stage: Stage,
+    start: Instant,
+}
+
+impl<'a> StageTimer<'a> {
+    pub fn start(provider_id: &'a str, stage: Stage) -> Self {
+        Self {
+            provider_id,
+            stage,
+            start: Instant::now(),
+        }
+    }
+
+    /// Records the elapsed time since the start with the number of `items` produced.
+    pub fn finish(self, items: usize) {
+        self.finish_with_outcome(items, Outcome::Ok);
+    }
+
+    /// Same as [`Self::finish`], but the stage may have failed or timed out.
+    pub fn finish_with_outcome(self, items: usize, outcome: Outcome) {
+        record(
+            self.provider_id,
+            self.stage,
+            self.start.elapsed(),
+            items,
+            outcome,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_summary() {
+        let mut metrics = Metrics::default();
+
+        for elapsed_us in 1..=100 {
+            metrics.record(Sample {
+                provider_id: "files".into(),
+                stage: Stage::Match,
+                elapsed_us: elapsed_us * 1000,
+                items: 100,
+                outcome: Outcome::Ok,
+            });
+        }
+        metrics.record(Sample {
+            provider_id: "blines".into(),
+            stage: Stage::Decorate,
+            elapsed_us: 500,
+            items: 50,
+            outcome: Outcome::Ok,
+        });
+
+        let summary = metrics.summary();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].provider_id, "blines");
+        assert_eq!(summary[0].items_per_sec, 100_000.0);
+
+        let files = &summary[1];
+        assert_eq!(files.count, 100);
+        assert_eq!(files.p50_ms, 51.0);
+        assert_eq!(files.p90_ms, 91.0);
+        assert_eq!(files.max_ms, 100.0);
+        assert_eq!(files.items_per_sec, 10_000.0 * 1_000_000.0 / 5_050_000.0);
+
+        for _ in 0..MAX_SAMPLES {
+            metrics.record(Sample {
+                provider_id: "files".into(),
+                stage: Stage::Match,
+                elapsed_us: 10,
+                items: 1,
+                outcome: Outcome::Ok,
+            });
+        }
+        assert_eq!(metrics.summary()[1].max_ms, 0.01);
+
+        metrics.record(Sample {
+            provider_id: "grep".into(),
+            stage: Stage::Source,
+            elapsed_us: 300_000,
+            items: 0,
+            outcome: Outcome::Timeout,
+        });
+        let grep = &metrics.summary()[2];
+        assert_eq!(grep.count, 1);
+        assert_eq!(grep.timeouts, 1);
+        assert_eq!(grep.errors, 0);
+
+        let sample: Sample = serde_json::from_str(
+            r#"{"provider_id":"files","stage":"match","elapsed_us":10,"items":1}"#,
+        )
+        .unwrap();
+        assert_eq!(sample.outcome, Outcome::Ok);
+    }
+}
@@ -200,7 +200,7 @@ impl<'a> LightCommand<'a> {
 
         Ok(ExecutedInfo {
             using_cache: true,
-            total: *total as usize,
+            total: *total,
             tempfile: Some(cached_path.clone()),
             lines,
             icon_added: self.env.icon.painter().is_some(),
@@ -235,6 +235,7 @@ impl<'a> LightCommand<'a> {
     pub fn execute(&mut self, base_cmd: BaseCommand) -> Result<ExecutedInfo> {
         self.env.dir = Some(base_cmd.cwd.clone());
 
+        let fingerprint = base_cmd.fingerprint();
         let cmd_stdout = self.collect_stdout()?;
 
         self.env.total = bytecount::count(&cmd_stdout, b'\n');
@@ -245,7 +246,7 @@ impl<'a> LightCommand<'a> {
 
         // Cache the output if there are too many lines.
         let cached_path = if self.env.should_create_cache() {
-            let p = base_cmd.create_cache(self.env.total, &cmd_stdout)?;
+            let p = base_cmd.create_cache(fingerprint, self.env.total, &cmd_stdout)?;
             Some(p)
         } else {
             None
@@ -5,18 +5,31 @@ pub mod tokio;
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
+use filter::matcher::MatchScope;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use self::rstd::StdCommand;
 
-use crate::cache::{push_cache_digest, Digest};
+use crate::cache::{push_cache_digest, Compression, Digest, Fingerprint};
 use crate::datastore::CACHE_INFO_IN_MEMORY;
 
+/// The cached outputs with fewer lines are filtered fast enough without an index.
+const MIN_INDEXED_LINES: usize = 10_000;
+
 /// Converts [`std::process::Output`] to a Vec of String.
 ///
 /// Remove the last line if it's empty.
 pub fn process_output(output: std::process::Output) -> Result<Vec<String>> {
+    // The shell exits with 127 if the command is not found.
+    if output.status.code() == Some(127) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+        .into());
+    }
+
     if !output.status.success() && !output.stderr.is_empty() {
         return Err(anyhow!("Error in output: {:?}", output.stderr));
     }
@@ -77,20 +90,27 @@ impl BaseCommand {
         Self { command, cwd }
     }
 
-    /// Returns the cache digest if the cache exists.
+    /// Returns the cache digest if the cache exists and the cwd has not changed since.
     pub fn cache_digest(&self) -> Option<Digest> {
+        // Computed before taking the lock as it may walk the cwd.
+        let fingerprint = self.fingerprint();
         let mut info = CACHE_INFO_IN_MEMORY.lock();
-        info.find_digest_usable(self)
+        info.find_digest_usable(self, fingerprint.as_ref())
     }
 
     pub fn cache_file(&self) -> Option<PathBuf> {
-        let mut info = CACHE_INFO_IN_MEMORY.lock();
-        info.find_digest_usable(self).map(|d| d.cached_path)
+        self.cache_digest().map(|d| d.cached_path)
     }
 
     pub fn cache_info(&self) -> Option<(usize, PathBuf)> {
+        self.cache_digest().map(|d| (d.total, d.cached_path))
+    }
+
+    /// Returns the cache info even if the cwd has changed since, which can be served while the
+    /// cache is being refreshed.
+    pub fn outdated_cache_info(&self) -> Option<(usize, PathBuf)> {
         let mut info = CACHE_INFO_IN_MEMORY.lock();
-        info.find_digest_usable(self)
+        info.find_digest_outdated(self)
             .map(|d| (d.total, d.cached_path))
     }
 
@@ -103,23 +123,80 @@ impl BaseCommand {
         Ok(stdout_stream)
     }
 
-    /// Writes the whole stdout `cmd_stdout` to a cache file.
-    fn write_stdout_to_disk(&self, cmd_stdout: &[u8]) -> Result<PathBuf> {
+    /// Writes the whole stdout `cmd_stdout` to a cache file, compressed if configured.
+    fn write_stdout_to_disk(
+        &self,
+        total: usize,
+        cmd_stdout: &[u8],
+        match_scope: MatchScope,
+    ) -> Result<PathBuf> {
         use std::io::Write;
 
         let cached_filename = utility::calculate_hash(self);
         let cached_path = crate::utils::generate_cache_file_path(cached_filename.to_string())?;
 
-        std::fs::File::create(&cached_path)?.write_all(cmd_stdout)?;
+        // Written to a unique temporary file first, the outdated cache being read meanwhile is
+        // kept intact until it's replaced and the concurrent writers of the same cache, e.g.,
+        // another maple process or the watcher, do not write into each other's file.
+        let cache_dir = cached_path
+            .parent()
+            .ok_or_else(|| anyhow!("Cache file {} has no parent", cached_path.display()))?;
+        let mut tmp_file = tempfile::NamedTempFile::new_in(cache_dir)?;
+        let settings = crate::datastore::load_cache_settings();
+        match settings.compression {
+            Compression::None => tmp_file.write_all(cmd_stdout)?,
+            Compression::Lz4 => tmp_file.write_all(&utility::lz4::compress(cmd_stdout)?)?,
+        }
+        tmp_file.persist(&cached_path)?;
+
+        // The compressed cache can not be memory mapped.
+        if settings.index && settings.compression == Compression::None && total >= MIN_INDEXED_LINES
+        {
+            if let Err(err) = filter::build_index(&cached_path, match_scope) {
+                tracing::error!(
+                    ?err,
+                    ?cached_path,
+                    "Failed to build the index of cached file"
+                );
+            }
+        } else {
+            // The index of the replaced cache is outdated anyway.
+            let _ = std::fs::remove_file(filter::index_path(&cached_path));
+        }
 
         Ok(cached_path)
     }
 
+    /// Returns the fingerprint of the cwd.
+    ///
+    /// It must be captured before executing the command for creating the cache, otherwise the
+    /// changes made during the execution are never detected.
+    pub fn fingerprint(&self) -> Option<Fingerprint> {
+        Fingerprint::recent(&self.cwd)
+    }
+
     /// Caches the output into a tempfile and also writes the cache digest to the disk.
-    pub fn create_cache(self, total: usize, cmd_stdout: &[u8]) -> Result<PathBuf> {
-        let cache_file = self.write_stdout_to_disk(cmd_stdout)?;
+    pub fn create_cache(
+        self,
+        fingerprint: Option<Fingerprint>,
+        total: usize,
+        cmd_stdout: &[u8],
+    ) -> Result<PathBuf> {
+        self.create_indexed_cache(fingerprint, total, cmd_stdout, MatchScope::Full)
+    }
 
-        let digest = Digest::new(self, total, cache_file.clone());
+    /// Same as [`Self::create_cache`], but the index of the cache pre-extracts the text of each
+    /// line in `match_scope` if enabled by `maple cache config --index true`.
+    pub fn create_indexed_cache(
+        self,
+        fingerprint: Option<Fingerprint>,
+        total: usize,
+        cmd_stdout: &[u8],
+        match_scope: MatchScope,
+    ) -> Result<PathBuf> {
+        let cache_file = self.write_stdout_to_disk(total, cmd_stdout, match_scope)?;
+
+        let digest = Digest::new(self, total, cache_file.clone(), fingerprint);
 
         push_cache_digest(digest)?;
 
@@ -22,7 +22,7 @@ pub fn collect_stdout(cmd: &mut Command) -> Result<Vec<u8>> {
 fn build_command(shell_cmd: &str) -> Command {
     if cfg!(target_os = "windows") {
         let mut cmd = Command::new("cmd");
-        cmd.args(&["/C", shell_cmd]);
+        cmd.args(["/C", shell_cmd]);
         cmd
     } else {
         let mut cmd = Command::new("bash");
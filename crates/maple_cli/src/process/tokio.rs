@@ -11,7 +11,7 @@ use tokio::process::Command;
 pub fn build_command(shell_cmd: impl AsRef<str>) -> Command {
     if cfg!(target_os = "windows") {
         let mut cmd = Command::new("cmd");
-        cmd.args(&["/C", shell_cmd.as_ref()]);
+        cmd.args(["/C", shell_cmd.as_ref()]);
         cmd
     } else {
         let mut cmd = Command::new("bash");
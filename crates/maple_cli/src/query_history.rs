@@ -0,0 +1,165 @@
+//! Frecency-ranked history of the queries typed in each provider.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use filter::{FilteredItem, SourceItem};
+use matcher::Matcher;
+use serde::{Deserialize, Serialize};
+
+use crate::datastore::QUERY_HISTORY_IN_MEMORY;
+use crate::recent_files::{FrecentEntry, SortPreference};
+
+/// Maximum number of queries per provider.
+const MAX_ENTRIES: u64 = 1_000;
+
+/// In memory version of the query history, the `fpath` of [`FrecentEntry`] is the query.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueryHistory {
+    /// Maximum number of entries per provider.
+    pub max_entries: u64,
+    /// Sort preference of entries.
+    pub sort_preference: SortPreference,
+    /// Queries of each provider, ordered by the frecent score.
+    pub entries: HashMap<String, Vec<FrecentEntry>>,
+}
+
+impl Default for QueryHistory {
+    fn default() -> Self {
+        Self {
+            max_entries: MAX_ENTRIES,
+            sort_preference: Default::default(),
+            entries: Default::default(),
+        }
+    }
+}
+
+impl QueryHistory {
+    /// Returns the queries of `provider_id` sorted according to the sort preference.
+    pub fn sorted_queries(&self, provider_id: &str) -> Vec<&FrecentEntry> {
+        let mut queries = self
+            .entries
+            .get(provider_id)
+            .map(|entries| entries.iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let cmp = |a: &&FrecentEntry, b: &&FrecentEntry| -> Ordering {
+            match self.sort_preference {
+                SortPreference::Frequency => b.visits.cmp(&a.visits),
+                SortPreference::Recency => b.last_visit.cmp(&a.last_visit),
+                SortPreference::Frecency => b.cmp(a),
+            }
+        };
+        queries.sort_by(cmp);
+
+        queries
+    }
+
+    /// Returns the queries of `provider_id` matching `query`, all of them if `query` is empty.
+    pub fn filter_on_query(
+        &self,
+        provider_id: &str,
+        query: &str,
+        matcher: &Matcher,
+    ) -> Vec<FilteredItem> {
+        let queries = self.sorted_queries(provider_id);
+
+        if query.is_empty() {
+            return queries
+                .into_iter()
+                .map(|entry| {
+                    FilteredItem::new(
+                        entry.fpath.as_str(),
+                        entry.frecent_score as i64,
+                        Default::default(),
+                    )
+                })
+                .collect();
+        }
+
+        let source_items: Vec<SourceItem> = queries
+            .into_iter()
+            .map(|entry| entry.fpath.as_str().into())
+            .collect();
+
+        filter::par_filter(query, source_items, matcher)
+    }
+
+    /// Updates or inserts the query without writing to the disk.
+    fn upsert_entry(&mut self, provider_id: &str, query: &str) {
+        let entries = self.entries.entry(provider_id.into()).or_default();
+
+        match entries.iter().position(|entry| entry.fpath == query) {
+            Some(pos) => entries[pos].refresh_now(),
+            None => entries.push(FrecentEntry::new(query.into())),
+        }
+
+        entries.sort_unstable_by(|a, b| b.cmp(a));
+
+        if entries.len() > self.max_entries as usize {
+            entries.truncate(self.max_entries as usize);
+        }
+    }
+}
+
+/// Records `query` typed in provider `provider_id`.
+///
+/// The query is upserted again into the query history on the disk, which may have been updated
+/// by the other maple processes since loaded. The in-memory mutex is not held while writing.
+pub fn upsert(provider_id: &str, query: &str) {
+    QUERY_HISTORY_IN_MEMORY
+        .lock()
+        .upsert_entry(provider_id, query);
+
+    let merged = crate::datastore::merge_query_history(|on_disk| match on_disk {
+        Some(mut on_disk) => {
+            on_disk.upsert_entry(provider_id, query);
+            on_disk
+        }
+        None => QUERY_HISTORY_IN_MEMORY.lock().clone(),
+    });
+
+    match merged {
+        Ok(Some(merged)) => *QUERY_HISTORY_IN_MEMORY.lock() = merged,
+        Ok(None) => {}
+        Err(e) => tracing::error!(?e, "Failed to write the query history to the disk"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_history() {
+        let mut history = QueryHistory::default();
+
+        for query in ["foo !bar", "'exact baz", "foo !bar", "hello"] {
+            history.upsert_entry("grep2", query);
+        }
+        history.upsert_entry("files", "main.rs");
+
+        let queries = |history: &QueryHistory| {
+            history
+                .sorted_queries("grep2")
+                .into_iter()
+                .map(|entry| entry.fpath.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(queries(&history)[0], "foo !bar");
+        assert_eq!(queries(&history).len(), 3);
+        assert!(history.sorted_queries("tags").is_empty());
+
+        history.sort_preference = SortPreference::Recency;
+        history.upsert_entry("grep2", "'exact baz");
+        assert_eq!(queries(&history)[0], "'exact baz");
+
+        let matched = history.filter_on_query("grep2", "baz", &Matcher::default());
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].source_item.raw, "'exact baz");
+
+        let all = history.filter_on_query("files", "", &Matcher::default());
+        assert_eq!(all.len(), 1);
+    }
+}
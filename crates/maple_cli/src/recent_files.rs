@@ -1,9 +1,10 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::path::Path;
 
 use chrono::prelude::*;
 use filter::SourceItem;
-use matcher::{Bonus, FuzzyAlgorithm, MatchScope};
+use matcher::Matcher;
 use serde::{Deserialize, Serialize};
 
 use crate::utils::UtcTime;
@@ -18,25 +19,20 @@ const MONTH: i64 = DAY * 30;
 const MAX_ENTRIES: u64 = 10_000;
 
 /// Preference for sorting the recent files.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub enum SortPreference {
-    /// Sort by the visit time.
-    Frequency,
     /// Sort by the number of visits.
+    Frequency,
+    /// Sort by the visit time.
     Recency,
     /// Sort by both `Frecency` and `Recency`.
+    #[default]
     Frecency,
 }
 
-impl Default for SortPreference {
-    fn default() -> Self {
-        Self::Frecency
-    }
-}
-
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FrecentEntry {
-    /// Absolute file path.
+    /// Absolute file path, or the query in [`QueryHistory`](crate::query_history::QueryHistory).
     pub fpath: String,
     /// Time of last visit.
     pub last_visit: UtcTime,
@@ -56,17 +52,17 @@ impl Eq for FrecentEntry {}
 
 impl PartialOrd for FrecentEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some((self.frecent_score, self.visits, self.last_visit).cmp(&(
-            other.frecent_score,
-            other.visits,
-            other.last_visit,
-        )))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for FrecentEntry {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        (self.frecent_score, self.visits, self.last_visit).cmp(&(
+            other.frecent_score,
+            other.visits,
+            other.last_visit,
+        ))
     }
 }
 
@@ -168,7 +164,12 @@ impl SortedRecentFiles {
         });
     }
 
-    pub fn filter_on_query(&self, query: &str, cwd: String) -> Vec<filter::FilteredItem> {
+    pub fn filter_on_query(
+        &self,
+        query: &str,
+        cwd: String,
+        matcher: &Matcher,
+    ) -> Vec<filter::FilteredItem> {
         let mut cwd = cwd;
         cwd.push(std::path::MAIN_SEPARATOR);
 
@@ -178,19 +179,25 @@ impl SortedRecentFiles {
             .map(|entry| entry.fpath.replacen(&cwd, "", 1).into())
             .collect();
 
-        cwd.pop();
-
-        let matcher = matcher::Matcher::with_bonuses(
-            vec![Bonus::cwd(cwd), Bonus::FileName],
-            FuzzyAlgorithm::Fzy,
-            MatchScope::Full,
-        );
+        filter::par_filter(query, source_items, matcher)
+    }
 
-        filter::par_filter(query, source_items, &matcher)
+    /// Returns the frecent scores of the entries under `cwd`, keyed by the path relative to `cwd`.
+    pub fn frecent_scores_in(&self, cwd: &Path) -> HashMap<String, u64> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let relative_path = Path::new(&entry.fpath).strip_prefix(cwd).ok()?;
+                Some((
+                    relative_path.to_string_lossy().into_owned(),
+                    entry.frecent_score,
+                ))
+            })
+            .collect()
     }
 
-    /// Updates or inserts a new entry in a sorted way.
-    pub fn upsert(&mut self, file: String) {
+    /// Updates or inserts a new entry in a sorted way without writing to the disk.
+    fn upsert_entry(&mut self, file: String) {
         match self
             .entries
             .iter()
@@ -209,10 +216,26 @@ impl SortedRecentFiles {
         if self.entries.len() > self.max_entries as usize {
             self.entries.truncate(self.max_entries as usize);
         }
+    }
 
-        // Write back to the disk.
-        if let Err(e) = crate::datastore::store_recent_files(self) {
-            tracing::error!(?e, "Failed to write the recent files to the disk");
+    /// Updates or inserts a new entry in a sorted way.
+    pub fn upsert(&mut self, file: String) {
+        self.upsert_entry(file.clone());
+
+        // Write back to the disk, the entry is upserted again into the recent files on the disk,
+        // which may have been updated by the other maple processes since loaded.
+        let merged = crate::datastore::merge_recent_files(|on_disk| match on_disk {
+            Some(mut on_disk) => {
+                on_disk.upsert_entry(file);
+                on_disk
+            }
+            None => self.clone(),
+        });
+
+        match merged {
+            Ok(Some(merged)) => *self = merged,
+            Ok(None) => {}
+            Err(e) => tracing::error!(?e, "Failed to write the recent files to the disk"),
         }
     }
 }
@@ -225,14 +248,14 @@ mod tests {
     fn test_sort_by_cwd() {
         let mut sorted_recent_files = SortedRecentFiles::default();
 
-        let entries = vec![
+        let entries = [
             "/usr/local/share/test1.txt",
             "/home/xlc/.vimrc",
             "/home/xlc/test.txt",
         ];
 
         for entry in entries.iter() {
-            sorted_recent_files.upsert(entry.to_string());
+            sorted_recent_files.upsert_entry(entry.to_string());
         }
 
         sorted_recent_files.sort_by_cwd("/usr/local/share");
@@ -249,5 +272,10 @@ mod tests {
                 "/home/xlc/.vimrc",
             ]
         );
+
+        let scores = sorted_recent_files.frecent_scores_in(Path::new("/home/xlc"));
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores.get("test.txt"), Some(&1));
+        assert_eq!(scores.get(".vimrc"), Some(&1));
     }
 }
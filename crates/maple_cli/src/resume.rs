@@ -0,0 +1,173 @@
+//! Snapshots of the terminated sessions, used for resuming the last query and results.
+
+use std::path::{Path, PathBuf};
+
+use chrono::prelude::*;
+use filter::FilteredItem;
+use serde::{Deserialize, Serialize};
+
+use crate::datastore::SESSION_SNAPSHOTS_IN_MEMORY;
+use crate::utils::UtcTime;
+
+/// Maximum number of snapshots, the least recently saved one is dropped first.
+const MAX_SNAPSHOTS: usize = 100;
+
+/// Maximum number of the top results kept in a snapshot.
+pub const MAX_SNAPSHOT_ITEMS: usize = 200;
+
+/// A result line with the matched indices.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotItem {
+    pub line: String,
+    pub indices: Vec<usize>,
+}
+
+impl From<&FilteredItem> for SnapshotItem {
+    fn from(item: &FilteredItem) -> Self {
+        Self {
+            line: item.source_item.raw.clone(),
+            indices: item.match_indices.clone(),
+        }
+    }
+}
+
+impl From<SnapshotItem> for FilteredItem {
+    fn from(item: SnapshotItem) -> Self {
+        FilteredItem::new(item.line, 0, item.indices)
+    }
+}
+
+/// State of a session at the time it was terminated.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub provider_id: String,
+    pub cwd: PathBuf,
+    pub query: String,
+    /// Line number of the cursor in the display window.
+    pub lnum: usize,
+    /// Number of all the matched results when the session was terminated, only the top ones of
+    /// which are kept in `items`.
+    pub total: usize,
+    /// Top results of `query`.
+    pub items: Vec<SnapshotItem>,
+    pub saved_at: UtcTime,
+}
+
+impl SessionSnapshot {
+    pub fn new(
+        provider_id: String,
+        cwd: PathBuf,
+        query: String,
+        lnum: usize,
+        total: usize,
+        results: &[FilteredItem],
+    ) -> Self {
+        Self {
+            provider_id,
+            cwd,
+            query,
+            lnum,
+            total,
+            items: results
+                .iter()
+                .take(MAX_SNAPSHOT_ITEMS)
+                .map(Into::into)
+                .collect(),
+            saved_at: Utc::now(),
+        }
+    }
+}
+
+/// Last snapshot of each provider and cwd, the most recent one first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SessionSnapshots {
+    pub entries: Vec<SessionSnapshot>,
+}
+
+impl SessionSnapshots {
+    /// Returns the snapshot of provider `provider_id` saved in `cwd`.
+    pub fn get(&self, provider_id: &str, cwd: &Path) -> Option<&SessionSnapshot> {
+        self.entries
+            .iter()
+            .find(|s| s.provider_id == provider_id && s.cwd == cwd)
+    }
+
+    /// Inserts a new snapshot, replacing the previous one of the same provider and cwd.
+    fn insert_entry(&mut self, snapshot: SessionSnapshot) {
+        self.entries
+            .retain(|s| s.provider_id != snapshot.provider_id || s.cwd != snapshot.cwd);
+        self.entries.insert(0, snapshot);
+        self.entries.truncate(MAX_SNAPSHOTS);
+    }
+}
+
+/// Saves the snapshot of a terminated session.
+///
+/// The snapshot is inserted again into the snapshots on the disk, which may have been updated by
+/// the other maple processes since loaded. The in-memory mutex is not held while writing.
+pub fn insert(snapshot: SessionSnapshot) {
+    SESSION_SNAPSHOTS_IN_MEMORY
+        .lock()
+        .insert_entry(snapshot.clone());
+
+    let merged = crate::datastore::merge_session_snapshots(|on_disk| match on_disk {
+        Some(mut on_disk) => {
+            on_disk.insert_entry(snapshot);
+            on_disk
+        }
+        None => SESSION_SNAPSHOTS_IN_MEMORY.lock().clone(),
+    });
+
+    match merged {
+        Ok(Some(merged)) => *SESSION_SNAPSHOTS_IN_MEMORY.lock() = merged,
+        Ok(None) => {}
+        Err(e) => tracing::error!(?e, "Failed to write the session snapshots to the disk"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(provider_id: &str, cwd: &str, query: &str) -> SessionSnapshot {
+        let results = vec![FilteredItem::new("src/main.rs", 100, vec![4, 5])];
+        SessionSnapshot::new(
+            provider_id.into(),
+            cwd.into(),
+            query.into(),
+            1,
+            10,
+            &results,
+        )
+    }
+
+    #[test]
+    fn test_insert_snapshot() {
+        let mut snapshots = SessionSnapshots::default();
+        snapshots.insert_entry(snapshot("files", "/a", "ma"));
+        snapshots.insert_entry(snapshot("files", "/b", "lib"));
+        snapshots.insert_entry(snapshot("files", "/a", "main"));
+
+        assert_eq!(snapshots.entries.len(), 2);
+        assert_eq!(snapshots.entries[0].query, "main");
+        assert_eq!(
+            snapshots
+                .get("files", Path::new("/b"))
+                .map(|s| s.query.as_str()),
+            Some("lib")
+        );
+        assert!(snapshots.get("grep2", Path::new("/a")).is_none());
+
+        assert_eq!(snapshots.entries[0].total, 10);
+        let item = snapshots.entries[0].items[0].clone();
+        assert_eq!(item.indices, vec![4, 5]);
+        let restored: FilteredItem = item.into();
+        assert_eq!(restored.source_item.raw, "src/main.rs");
+
+        for i in 0..MAX_SNAPSHOTS {
+            snapshots.insert_entry(snapshot("grep2", &format!("/{}", i), "fn"));
+        }
+        assert_eq!(snapshots.entries.len(), MAX_SNAPSHOTS);
+        assert!(snapshots.get("files", Path::new("/a")).is_none());
+    }
+}
@@ -0,0 +1,231 @@
+//! Native in-process searcher, the built-in replacement of `rg` for the grep providers.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use rayon::iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
+use regex::bytes::{Regex, RegexBuilder};
+
+use filter::{WalkConfig, Walker};
+
+use crate::tools::ripgrep::{jsont::Data, Match, SubMatch};
+
+/// Files containing a NUL byte in the first few KiB are treated as binary and skipped, like rg.
+const BINARY_DETECTION_LEN: usize = 8 * 1024;
+
+/// Files larger than this are skipped, they are hardly the text files worth searching.
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Searcher {
+    dir: PathBuf,
+    regex: Regex,
+    walker: Walker,
+}
+
+impl Searcher {
+    /// Creates a searcher matching every line, equivalent to `RG_EXEC_CMD`.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            regex: Regex::new("").expect("Empty regex is always valid"),
+            walker: Walker::new(dir, WalkConfig::default()),
+        }
+    }
+
+    /// Creates a searcher for the regex `pattern` using the smart case, like `rg --smart-case`.
+    pub fn with_pattern(dir: impl AsRef<Path>, pattern: &str) -> Result<Self> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(!pattern.chars().any(char::is_uppercase))
+            .build()?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            regex,
+            walker: Walker::new(dir, WalkConfig::default()),
+        })
+    }
+
+    /// Only searches the files matching `globs`, the same as `rg --glob`.
+    pub fn globs<'a>(mut self, globs: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        self.walker = self.walker.globs(globs)?;
+        Ok(self)
+    }
+
+    /// Searches all the files under `dir` in parallel, the matches are sent back as soon as
+    /// they are found.
+    pub fn search(self) -> impl Iterator<Item = Match> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+
+        std::thread::spawn(move || {
+            let Self { dir, regex, walker } = self;
+            let _ = walker
+                .source_items()
+                .par_bridge()
+                .try_for_each_with(sender, |sender, item| {
+                    search_file(&dir, &item.raw, &regex)
+                        .into_iter()
+                        .try_for_each(|mat| sender.send(mat))
+                });
+        });
+
+        receiver.into_iter()
+    }
+
+    /// Returns the matched lines in the same format as the output of `RG_EXEC_CMD`.
+    ///
+    /// The lines are sorted by path as the files are searched in parallel, the lines of each file
+    /// are kept in order.
+    pub fn lines(self) -> Vec<String> {
+        let mut matches = self.search().collect::<Vec<_>>();
+        matches.sort_by(|a, b| a.path().cmp(&b.path()));
+        matches.iter().map(vimgrep_line).collect()
+    }
+
+    /// Returns the matched lines of `files` only, the paths are relative to `dir`.
+    pub fn lines_of(&self, files: &[String]) -> Vec<String> {
+        files
+            .par_iter()
+            .flat_map_iter(|path| {
+                search_file(&self.dir, path, &self.regex)
+                    .into_iter()
+                    .map(|mat| vimgrep_line(&mat))
+            })
+            .collect()
+    }
+}
+
+/// Formats `mat` as `path:line_number:column:text`, the column is 1-based.
+pub fn vimgrep_line(mat: &Match) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        mat.path(),
+        mat.line_number(),
+        mat.column() + 1,
+        mat.pattern().trim_end_matches(&['\r', '\n'][..])
+    )
+}
+
+fn into_data(bytes: &[u8]) -> Data {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Data::Text { text: text.into() },
+        Err(_) => Data::Bytes {
+            bytes: bytes.to_vec(),
+        },
+    }
+}
+
+/// Returns the matches of `regex` in the file `dir/path`, the unreadable, binary and too large
+/// files are ignored.
+fn search_file(dir: &Path, path: &str, regex: &Regex) -> Vec<Match> {
+    let file = match File::open(dir.join(path)) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+
+    if file.metadata().map_or(true, |m| m.len() > MAX_FILE_SIZE) {
+        return Vec::new();
+    }
+
+    // The first buffer is filled with the leading bytes used for the binary detection.
+    let mut reader = BufReader::with_capacity(BINARY_DETECTION_LEN, file);
+    match reader.fill_buf() {
+        Ok(head) if !head.contains(&0) => {}
+        _ => return Vec::new(),
+    }
+
+    // `find_iter` yields an empty match at each position for the empty pattern.
+    let max_submatches = if regex.as_str().is_empty() {
+        1
+    } else {
+        usize::MAX
+    };
+
+    // The same as the paths printed by `rg` searching `.`, e.g., `./src/lib.rs`.
+    let rg_path = Path::new(".")
+        .join(path.strip_prefix("./").unwrap_or(path))
+        .to_string_lossy()
+        .into_owned();
+
+    let mut matches = Vec::new();
+    let mut absolute_offset = 0;
+    let mut line = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => line_number += 1,
+        }
+
+        let submatches = regex
+            .find_iter(&line)
+            .map(|m| SubMatch {
+                m: into_data(m.as_bytes()),
+                start: m.start(),
+                end: m.end(),
+            })
+            .take(max_submatches)
+            .collect::<Vec<_>>();
+
+        if !submatches.is_empty() {
+            matches.push(Match {
+                path: Data::Text {
+                    text: rg_path.clone(),
+                },
+                lines: into_data(&line),
+                line_number: Some(line_number),
+                absolute_offset,
+                submatches,
+            });
+        }
+
+        absolute_offset += line.len() as u64;
+    }
+
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_file() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let path = Path::new("src").join("searcher.rs");
+        let path = path.to_str().unwrap();
+
+        let regex = Searcher::with_pattern(dir, "BINARY_DETECTION_LEN")
+            .unwrap()
+            .regex;
+        let matches = search_file(dir, path, &regex);
+        assert!(matches.len() >= 2);
+        assert!(matches[0]
+            .pattern()
+            .starts_with("const BINARY_DETECTION_LEN"));
+        assert_eq!(matches[0].column(), "const ".len());
+
+        let all = search_file(dir, path, &Searcher::new(dir).regex);
+        let total_lines = std::fs::read_to_string(dir.join(path))
+            .unwrap()
+            .lines()
+            .count();
+        assert_eq!(all.len(), total_lines);
+        assert_eq!(
+            vimgrep_line(&all[0]),
+            format!("./{}:1:1:{}", path, all[0].pattern().trim_end())
+        );
+
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("binary"), b"foo\nbar\0\n").unwrap();
+        std::fs::write(tmp.path().join("text"), b"foo\nbar").unwrap();
+        let regex = Searcher::with_pattern(tmp.path(), "bar").unwrap().regex;
+        assert!(search_file(tmp.path(), "binary", &regex).is_empty());
+        let matches = search_file(tmp.path(), "text", &regex);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(vimgrep_line(&matches[0]), "./text:2:1:bar");
+    }
+}
@@ -1,6 +1,5 @@
 #![allow(unused)]
 
-mod deprecated_runner;
 mod providers;
 mod rpc;
 mod session;
@@ -11,50 +10,282 @@ mod vim;
 
 use std::io::prelude::*;
 use std::io::{BufReader, BufWriter};
-use std::ops::Deref;
 use std::sync::Arc;
 
 use anyhow::Result;
 use crossbeam_channel::{Receiver, Sender};
-use once_cell::sync::OnceCell;
-use serde::Serialize;
 use serde_json::json;
 
-use self::rpc::{Call, RpcClient};
+use self::rpc::{Call, RpcClient, RpcError};
 use self::session::{SessionEvent, SessionManager};
 use self::session_client::SessionClient;
 use self::state::State;
 use self::types::GlobalEnv;
 
-pub use self::deprecated_runner::{run_forever, write_response};
 pub use self::rpc::{MethodCall, Notification};
+pub use self::session::{note_job_is_finished, register_job_successfully};
 
-static GLOBAL_ENV: OnceCell<GlobalEnv> = OnceCell::new();
+/// Starts and keep running the server on top of stdio.
+pub fn start() -> Result<()> {
+    serve(
+        BufReader::new(std::io::stdin()),
+        BufWriter::new(std::io::stdout()),
+    );
+
+    Ok(())
+}
 
-/// Ensure GLOBAL_ENV has been instalized before using it.
-pub fn global() -> impl Deref<Target = GlobalEnv> {
-    if let Some(x) = GLOBAL_ENV.get() {
-        x
-    } else if cfg!(debug_assertions) {
-        panic!("Uninitalized static: GLOBAL_ENV")
-    } else {
-        unreachable!("Never forget to intialize before using it!")
+/// Starts the server on top of a unix socket or TCP address, each connection is served as an
+/// individual client with its own sessions and [`GlobalEnv`].
+pub async fn listen(addr: &str) -> Result<()> {
+    match addr.parse::<std::net::SocketAddr>() {
+        Ok(socket_addr) => {
+            // The clients are not authenticated and can run any source command.
+            if !socket_addr.ip().is_loopback() {
+                return Err(anyhow::anyhow!(
+                    "Refusing to listen on the non-loopback address {}",
+                    socket_addr
+                ));
+            }
+            let listener = tokio::net::TcpListener::bind(socket_addr).await?;
+            tracing::debug!(?socket_addr, "Listening on the TCP address");
+            loop {
+                match listener.accept().await.and_then(|(stream, _)| {
+                    let stream = stream.into_std()?;
+                    stream.set_nonblocking(false)?;
+                    Ok((stream.try_clone()?, stream))
+                }) {
+                    Ok((reader, writer)) => spawn_connection(BufReader::new(reader), writer),
+                    Err(error) => tracing::error!(?error, "Failed to accept the TCP connection"),
+                }
+            }
+        }
+        Err(_) => listen_unix(addr).await,
     }
 }
 
-/// Starts and keep running the server on top of stdio.
-pub fn start() -> Result<()> {
+#[cfg(unix)]
+async fn listen_unix(path: &str) -> Result<()> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    // Remove the socket left by the last run, the other kinds of file are never touched.
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)?;
+    // Only the current user can connect to it as the clients can run any source command.
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    tracing::debug!(?path, "Listening on the unix socket");
+    loop {
+        match listener.accept().await.and_then(|(stream, _)| {
+            let stream = stream.into_std()?;
+            stream.set_nonblocking(false)?;
+            Ok((stream.try_clone()?, stream))
+        }) {
+            Ok((reader, writer)) => spawn_connection(BufReader::new(reader), writer),
+            Err(error) => tracing::error!(?error, "Failed to accept the unix socket connection"),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn listen_unix(path: &str) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "Invalid TCP address and the unix socket is unsupported on this platform: {}",
+        path
+    ))
+}
+
+/// Serves the connection on a blocking thread, the client is served by the same blocking
+/// [`serve`] as the stdio one.
+fn spawn_connection(reader: impl BufRead + Send + 'static, writer: impl Write + Send + 'static) {
+    tokio::task::spawn_blocking(move || {
+        tracing::debug!("New client connected");
+        serve(reader, writer);
+        tracing::debug!("Client disconnected");
+    });
+}
+
+/// Serves a client until its input is closed.
+fn serve(reader: impl BufRead + Send + 'static, writer: impl Write + Send + 'static) {
     let (call_tx, call_rx) = crossbeam_channel::unbounded();
 
-    let rpc_client = Arc::new(RpcClient::new(
-        BufReader::new(std::io::stdin()),
-        BufWriter::new(std::io::stdout()),
-        call_tx.clone(),
-    ));
+    let rpc_client = Arc::new(RpcClient::new(reader, writer, call_tx));
 
-    let state = State::new(call_tx, rpc_client);
-    let session_client = SessionClient::new(state);
+    let session_client = SessionClient::new(State::new(rpc_client));
     session_client.loop_call(&call_rx);
+    session_client.session_manager_mutex.lock().terminate_all();
+}
 
-    Ok(())
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use super::*;
+
+    fn send(mut stream: &UnixStream, msg: serde_json::Value) {
+        writeln!(stream, "{}", msg).unwrap();
+    }
+
+    /// Returns the first message containing `pattern`.
+    fn read_message(stream: &UnixStream, pattern: &str) -> String {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            assert!(
+                reader.read_line(&mut line).unwrap() > 0,
+                "Connection closed"
+            );
+            if line.contains(pattern) {
+                return line;
+            }
+        }
+    }
+
+    /// Returns the first message of the filtering results.
+    fn read_filter_message(stream: &UnixStream) -> String {
+        read_message(stream, "s:process_filter_message")
+    }
+
+    /// Returns the path of the socket in `dir`, which is removed along with `dir`.
+    fn socket_path(dir: &tempfile::TempDir) -> PathBuf {
+        dir.path().join("maple.sock")
+    }
+
+    fn connect(path: &Path) -> UnixStream {
+        for _ in 0..100 {
+            if let Ok(stream) = UnixStream::connect(path) {
+                stream
+                    .set_read_timeout(Some(Duration::from_secs(10)))
+                    .unwrap();
+                return stream;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Failed to connect to {}", path.display());
+    }
+
+    fn on_init(stream: &UnixStream, source_cmd: String, debounce: bool) {
+        send(
+            stream,
+            json!({
+                "id": 1,
+                "method": "on_init",
+                "session_id": 1,
+                "params": {
+                    "provider_id": "test",
+                    "cwd": std::env::temp_dir(),
+                    "no_cache": false,
+                    "debounce": debounce,
+                    "source_fpath": "",
+                    "source_cmd": source_cmd,
+                },
+            }),
+        );
+    }
+
+    fn on_typed(stream: &UnixStream, query: &str) {
+        send(
+            stream,
+            json!({
+                "id": 2,
+                "method": "on_typed",
+                "session_id": 1,
+                "params": { "query": query },
+            }),
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_clients_have_separate_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = socket_path(&dir);
+        let addr = path.to_string_lossy().into_owned();
+        let server = tokio::spawn(async move { listen(&addr).await });
+
+        let result = tokio::task::spawn_blocking(move || {
+            let clients = [(connect(&path), "alpha"), (connect(&path), "beta")];
+
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+
+            // Both clients use the same session id.
+            for (stream, source) in &clients {
+                on_init(stream, format!("printf '{}\\n'", source), false);
+            }
+            for (stream, _) in &clients {
+                on_typed(stream, "");
+            }
+
+            clients
+                .iter()
+                .map(|(stream, _)| read_filter_message(stream))
+                .collect::<Vec<_>>()
+        })
+        .await;
+
+        server.abort();
+
+        let messages = result.unwrap();
+        assert!(messages[0].contains("alpha") && !messages[0].contains("beta"));
+        assert!(messages[1].contains("beta") && !messages[1].contains("alpha"));
+    }
+
+    #[tokio::test]
+    async fn test_refuse_non_loopback_address() {
+        assert!(listen("0.0.0.0:0").await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_page_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = socket_path(&dir);
+        let addr = path.to_string_lossy().into_owned();
+        let server = tokio::spawn(async move { listen(&addr).await });
+
+        let result = tokio::task::spawn_blocking(move || {
+            let stream = connect(&path);
+
+            // The page is handled by the debounced event loop while no OnTyped is pending.
+            on_init(&stream, "seq 1 1000".into(), true);
+            on_typed(&stream, "1");
+            let filtered: serde_json::Value =
+                serde_json::from_str(&read_filter_message(&stream)).unwrap();
+
+            send(
+                &stream,
+                json!({
+                    "id": 3,
+                    "method": "page",
+                    "session_id": 1,
+                    "params": { "offset": 200, "count": 10 },
+                }),
+            );
+            let page: serde_json::Value =
+                serde_json::from_str(&read_message(&stream, "\"id\":3")).unwrap();
+
+            (filtered, page)
+        })
+        .await;
+
+        server.abort();
+
+        let (filtered, page) = result.unwrap();
+        let total = filtered["total"].as_u64().unwrap();
+        // 1, 10-19, 21, 31, ..., 91, 100-199, ..., 1000.
+        assert_eq!(total, 272);
+        assert_eq!(page["result"]["total"].as_u64(), Some(total));
+        assert_eq!(page["result"]["retained"].as_u64(), Some(total));
+        assert_eq!(page["result"]["offset"].as_u64(), Some(200));
+        assert_eq!(page["result"]["lines"].as_array().unwrap().len(), 10);
+    }
 }
@@ -1,49 +1,276 @@
 pub mod on_move;
+pub mod selection;
 
 use std::ops::Deref;
 use std::sync::Arc;
 
 use anyhow::Result;
-use filter::{matcher::Matcher, FilterContext, FilteredItem};
+use filter::{
+    CancellationToken, FilterContext, FilteredItem, ProgressSink, Query, Source, SourceItem,
+    WalkConfig, Walker,
+};
+use matcher::FuzzyAlgorithm;
 use parking_lot::Mutex;
 use serde_json::json;
 
 use crate::command::ctags::recursive_tags::build_recursive_ctags_cmd;
-use crate::command::grep::RgBaseCommand;
+use crate::command::grep::{refresh_cache, RgBaseCommand};
+use crate::datastore::SESSION_SNAPSHOTS_IN_MEMORY;
+use crate::metrics::{Outcome, Stage, StageTimer};
 use crate::process::tokio::TokioCommand;
+use crate::resume::SessionSnapshot;
 use crate::stdio_server::{
-    session::{EventHandle, SessionContext, SourceScale},
-    write_response, MethodCall,
+    rpc::RpcError,
+    session::{
+        process_source_scale, spawn_singleton_blocking_job, EventHandle, ExitState, SessionContext,
+        SourceScale,
+    },
+    vim::Vim,
+    MethodCall,
 };
 
 pub use on_move::{OnMove, OnMoveHandler};
+pub use selection::{send_locations, send_selection, Location};
+
+/// Last query and the indices of all the source lines it matched.
+#[derive(Debug, Clone)]
+struct LastFiltered {
+    query: Query,
+    matched: Vec<usize>,
+}
+
+/// Results of the last query.
+#[derive(Debug, Default)]
+pub struct CurrentResults {
+    /// Number of all the matched items, the same as the total sent to Vim.
+    pub total: usize,
+    /// Top matched items, at most [`MAX_RETAINED_RESULTS`] if filtered on the fly.
+    pub items: Vec<FilteredItem>,
+}
+
+impl CurrentResults {
+    fn new(total: usize, items: Vec<FilteredItem>) -> Self {
+        Self { total, items }
+    }
+}
 
 #[derive(Clone)]
 pub struct BuiltinHandle {
-    pub current_results: Arc<Mutex<Vec<FilteredItem>>>,
+    pub current_results: Arc<Mutex<CurrentResults>>,
+    /// Used for narrowing the candidates when the new query is a refinement of the last one.
+    last_filtered: Arc<Mutex<Option<LastFiltered>>>,
+    /// Token of the filtering job spawned by the last on_typed, if any.
+    running_job: Option<CancellationToken>,
+    /// Last query and cursor position, saved on terminate.
+    last_query: String,
+    last_lnum: usize,
 }
 
 impl BuiltinHandle {
     pub fn new() -> Self {
         Self {
-            current_results: Arc::new(Mutex::new(Vec::new())),
+            current_results: Arc::new(Mutex::new(CurrentResults::default())),
+            last_filtered: Arc::new(Mutex::new(None)),
+            running_job: None,
+            last_query: String::new(),
+            last_lnum: 1,
         }
     }
+
+    /// Matches the in-memory `lines` in the background so that a newer on_typed can cancel this
+    /// one, only the lines matched by the last query are matched if it's a refinement.
+    fn spawn_match_job(
+        &mut self,
+        msg_id: u64,
+        query: String,
+        lines: Arc<Vec<String>>,
+        context: Arc<SessionContext>,
+    ) {
+        let token = CancellationToken::new();
+        self.running_job.replace(token.clone());
+
+        let current_results = self.current_results.clone();
+        let last_filtered = self.last_filtered.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let query: Query = query.into();
+
+            // Only the lines matched last time are possible to match a refined query, except
+            // for the typo algorithm as a longer query tolerates more typos.
+            let candidates = last_filtered
+                .lock()
+                .as_ref()
+                .filter(|last| {
+                    !matches!(context.fuzzy_algo, FuzzyAlgorithm::Typo)
+                        && last.query.is_superset(&query)
+                })
+                .map(|last| last.matched.clone());
+
+            let provider_id = context.provider_id.as_str();
+
+            let timer = StageTimer::start(provider_id, Stage::Match);
+            let (results, matched) = filter::par_match_subset(
+                &query,
+                &lines,
+                candidates.as_deref(),
+                &context.fuzzy_matcher(),
+            );
+            timer.finish(results.len());
+
+            if token.is_cancelled() {
+                context.vim.write_failure(msg_id, RpcError::Cancelled);
+                return;
+            }
+
+            let timer = StageTimer::start(provider_id, Stage::Sort);
+            let results = filter::sort_initial_filtered(results);
+            timer.finish(results.len());
+
+            // Take the first 200 entries and add an icon to each of them.
+            let timer = StageTimer::start(provider_id, Stage::Decorate);
+            let printer::DecoratedLines {
+                lines,
+                indices,
+                truncated_map,
+                icon_added,
+            } = printer::decorate_lines(
+                results.iter().take(200).cloned().collect(),
+                context.display_winwidth as usize,
+                context.icon,
+            );
+            timer.finish(lines.len());
+
+            // Checked again under the lock so that the stale results never overwrite the newer
+            // ones, the same as the filtering job.
+            let mut current_results = current_results.lock();
+            if token.is_cancelled() {
+                context.vim.write_failure(msg_id, RpcError::Cancelled);
+                return;
+            }
+
+            context.vim.write_response(json!({
+                "total": results.len(),
+                "lines": lines,
+                "indices": indices,
+                "truncated_map": truncated_map,
+                "icon_added": icon_added,
+                "method": "s:process_filter_message",
+            }));
+
+            last_filtered
+                .lock()
+                .replace(LastFiltered { query, matched });
+            *current_results = CurrentResults::new(results.len(), results);
+        });
+    }
+
+    /// Filters `source` in the background so that a newer on_typed can cancel this one.
+    ///
+    /// The progress is streamed to Vim if `stream_progress` is true, otherwise only the final
+    /// results are sent.
+    fn spawn_filter_job<I: Iterator<Item = SourceItem>>(
+        &mut self,
+        msg_id: u64,
+        query: String,
+        source: impl FnOnce() -> Result<Source<I>> + Send + 'static,
+        context: &SessionContext,
+        stream_progress: bool,
+    ) {
+        let token = CancellationToken::new();
+        self.running_job.replace(token.clone());
+
+        let (winwidth, icon) = (context.display_winwidth as usize, context.icon);
+        let vim = context.vim.clone();
+
+        let mut filter_context = FilterContext::new(
+            icon,
+            Some(MAX_RETAINED_RESULTS),
+            Some(winwidth),
+            context.fuzzy_matcher(),
+        )
+        .cancellation_token(token.clone());
+
+        if stream_progress {
+            filter_context = filter_context.progress_sink(progress_sink(
+                vim.clone(),
+                query.clone(),
+                winwidth,
+                icon,
+            ));
+        }
+
+        let current_results = self.current_results.clone();
+        let provider_id = context.provider_id.as_str().to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let timer = StageTimer::start(&provider_id, Stage::Filter);
+            match source().and_then(|source| filter::dyn_collect(&query, source, filter_context)) {
+                Ok(Some((total, ranked))) => {
+                    timer.finish(total);
+
+                    // The token is checked again under the lock, a newer job is only spawned after
+                    // cancelling this one, hence the stale results never overwrite the newer ones.
+                    let mut current_results = current_results.lock();
+                    if token.is_cancelled() {
+                        return;
+                    }
+
+                    // The final results have been sent as the last progress.
+                    if !stream_progress {
+                        let timer = StageTimer::start(&provider_id, Stage::Decorate);
+                        let printer::DecoratedLines {
+                            lines,
+                            indices,
+                            truncated_map,
+                            icon_added,
+                        } = printer::decorate_lines(
+                            ranked.iter().take(40).cloned().collect(),
+                            winwidth,
+                            icon,
+                        );
+                        timer.finish(lines.len());
+
+                        vim.write_response(json!({
+                            "total": total,
+                            "lines": lines,
+                            "indices": indices,
+                            "truncated_map": truncated_map,
+                            "icon_added": icon_added,
+                            "method": "s:process_filter_message",
+                        }));
+                    }
+
+                    *current_results = CurrentResults::new(total, ranked);
+                }
+                // Vim drops the responses to the superseded queries, but each request is answered.
+                Ok(None) => {
+                    tracing::debug!(%query, "The filtering job has been cancelled");
+                    vim.write_failure(msg_id, RpcError::Cancelled);
+                }
+                Err(e) => {
+                    tracing::error!(error = ?e, "Error occured when filtering the source")
+                }
+            }
+        });
+    }
 }
 
 #[async_trait::async_trait]
 impl EventHandle for BuiltinHandle {
     async fn on_move(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
         let msg_id = msg.id;
+        let lnum = msg.get_u64("lnum").ok();
+
+        if let Some(lnum) = lnum {
+            self.last_lnum = lnum as usize;
+        }
 
-        let curline = match (
-            context.state.source_scale.lock().deref(),
-            msg.get_u64("lnum").ok(),
-        ) {
+        let curline = match (context.state.source_scale.lock().deref(), lnum) {
             (SourceScale::Small { ref lines, .. }, Some(lnum)) => {
                 if let Some(curline) = self
                     .current_results
                     .lock()
+                    .items
                     .get((lnum - 1) as usize)
                     .map(|r| r.source_item.raw.clone())
                 {
@@ -58,140 +285,470 @@ impl EventHandle for BuiltinHandle {
         let on_move_handler = on_move::OnMoveHandler::create(&msg, &context, curline)?;
         if let Err(error) = on_move_handler.handle().await {
             tracing::error!(?error, "Failed to handle OnMove event");
-            write_response(json!({"error": error.to_string(), "id": msg_id }));
+            context.vim.write_failure(msg_id, error);
         }
         Ok(())
     }
 
     async fn on_typed(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
-        let query = msg.get_query();
-
-        let source_scale = context.state.source_scale.lock();
-
-        match source_scale.deref() {
-            SourceScale::Small { ref lines, .. } => {
-                let results = filter::par_filter(
-                    query,
-                    lines.iter().map(|s| s.as_str().into()).collect(),
-                    &context.fuzzy_matcher(),
-                );
-
-                // Take the first 200 entries and add an icon to each of them.
-                let printer::DecoratedLines {
-                    lines,
-                    indices,
-                    truncated_map,
-                    icon_added,
-                } = printer::decorate_lines(
-                    results.iter().take(200).cloned().collect(),
-                    context.display_winwidth as usize,
-                    context.icon,
-                );
-
-                let total = results.len();
-
-                let method = "s:process_filter_message";
-                utility::println_json_with_length!(
-                    total,
-                    lines,
-                    indices,
-                    truncated_map,
-                    icon_added,
-                    method
-                );
-
-                let mut current_results = self.current_results.lock();
-                *current_results = results;
+        let query = msg.get_query()?;
+        self.last_query = query.clone();
+
+        // The results of the previous query are stale now.
+        if let Some(token) = self.running_job.take() {
+            token.cancel();
+        }
+
+        // Cloned to not hold the lock while matching, the source lines are shared.
+        let source_scale = context.state.source_scale.lock().clone();
+
+        match source_scale {
+            SourceScale::Small { lines, .. } => {
+                self.spawn_match_job(msg.id, query, lines, context);
             }
-            SourceScale::Cache { ref path, .. } => {
-                if let Err(e) = filter::dyn_run::<std::iter::Empty<_>>(
-                    &query,
-                    path.clone().into(),
-                    FilterContext::new(
-                        context.icon,
-                        Some(40),
-                        Some(context.display_winwidth as usize),
-                        Matcher::default()
-                            .set_match_scope(context.match_scope)
-                            .set_bonuses(context.match_bonuses.clone()),
-                    ),
-                ) {
-                    tracing::error!(error = ?e, "Error occured when filtering the cache source");
+            SourceScale::Cache { path, .. } => {
+                let is_blines = context.provider_id.as_str() == "blines";
+                let source = move || {
+                    if is_blines {
+                        blines_source(&path)
+                    } else {
+                        Ok(path.into())
+                    }
+                };
+                self.spawn_filter_job(msg.id, query, source, &context, false);
+            }
+            SourceScale::Large { lines, .. } => {
+                // Stream the progress as the filtering on the large source could take a while.
+                let source = move || {
+                    Ok(Source::List(
+                        (0..lines.len()).map(move |i| lines[i].as_str().into()),
+                    ))
+                };
+                self.spawn_filter_job(msg.id, query, source, &context, true);
+            }
+            SourceScale::Indefinite => {
+                // The source is not collected yet, run it again and stream the progress as the
+                // filtering on it could take a while.
+                if let Some(source) = streaming_source(&context) {
+                    self.spawn_filter_job(msg.id, query, source, &context, true);
                 }
             }
-            _ => {}
         }
 
         Ok(())
     }
+
+    async fn on_page(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
+        let offset = msg.get_u64("offset")? as usize;
+        let count = msg.get_u64("count")? as usize;
+
+        let (total, retained, page) = {
+            let current_results = self.current_results.lock();
+            let page = current_results
+                .items
+                .iter()
+                .skip(offset)
+                .take(count)
+                .cloned()
+                .collect();
+            (current_results.total, current_results.items.len(), page)
+        };
+
+        let printer::DecoratedLines {
+            lines,
+            indices,
+            truncated_map,
+            icon_added,
+        } = printer::decorate_lines(page, context.display_winwidth as usize, context.icon);
+
+        // Make the line numbers of truncated map relative to the whole results.
+        let truncated_map = truncated_map
+            .into_iter()
+            .map(|(lnum, line)| (lnum + offset, line))
+            .collect::<printer::LinesTruncatedMap>();
+
+        context.vim.write_response(json!({
+            "id": msg.id,
+            "provider_id": context.provider_id,
+            "result": {
+                "total": total,
+                // Only the retained results can be paged.
+                "retained": retained,
+                "offset": offset,
+                "lines": lines,
+                "indices": indices,
+                "truncated_map": truncated_map,
+                "icon_added": icon_added,
+            },
+        }));
+
+        Ok(())
+    }
+
+    async fn on_selection(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
+        send_selection(msg, &context)
+    }
+
+    async fn on_resume(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
+        let maybe_snapshot = SESSION_SNAPSHOTS_IN_MEMORY
+            .lock()
+            .get(context.provider_id.as_str(), &context.cwd)
+            .cloned();
+
+        let snapshot = match maybe_snapshot {
+            Some(snapshot) => snapshot,
+            None => {
+                context.vim.write_response(json!({
+                    "id": msg.id,
+                    "provider_id": context.provider_id,
+                    "result": null,
+                }));
+                return Ok(());
+            }
+        };
+
+        let results = snapshot
+            .items
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<FilteredItem>>();
+
+        // Only the top results are saved, the remaining ones can only be filtered again.
+        let truncated = snapshot.total > results.len();
+
+        let printer::DecoratedLines {
+            lines,
+            indices,
+            truncated_map,
+            icon_added,
+        } = printer::decorate_lines(
+            results.clone(),
+            context.display_winwidth as usize,
+            context.icon,
+        );
+
+        context.vim.write_response(json!({
+            "id": msg.id,
+            "provider_id": context.provider_id,
+            "result": {
+                "query": snapshot.query,
+                "lnum": snapshot.lnum,
+                "total": snapshot.total,
+                "truncated": truncated,
+                "lines": lines,
+                "indices": indices,
+                "truncated_map": truncated_map,
+                "icon_added": icon_added,
+            },
+        }));
+
+        // The matched source lines are not saved, the next query has to be filtered from scratch.
+        self.last_filtered.lock().take();
+        self.last_query = snapshot.query;
+        self.last_lnum = snapshot.lnum;
+        *self.current_results.lock() = CurrentResults::new(snapshot.total, results);
+
+        Ok(())
+    }
+
+    async fn on_terminate(&mut self, context: Arc<SessionContext>, exit_state: ExitState) {
+        if let Some(token) = self.running_job.take() {
+            token.cancel();
+        }
+
+        // The query sent by Vim is the final one even if the session does not own the filtering.
+        let query = exit_state.query.unwrap_or_else(|| self.last_query.clone());
+        let lnum = exit_state.lnum.unwrap_or(self.last_lnum);
+
+        if query.is_empty() {
+            return;
+        }
+
+        // The results are only known if the final query was filtered by the session, otherwise
+        // the query is filtered again on resume.
+        let snapshot = context.enable_resume.then(|| {
+            let current_results = self.current_results.lock();
+            let (total, items) = if query == self.last_query {
+                (current_results.total, current_results.items.as_slice())
+            } else {
+                (0, [].as_slice())
+            };
+
+            SessionSnapshot::new(
+                context.provider_id.as_str().into(),
+                context.cwd.clone(),
+                query.clone(),
+                lnum,
+                total,
+                items,
+            )
+        });
+
+        // Both of them write the data files, which must not block the async runtime.
+        let provider_id = context.provider_id.clone();
+        let write_data_files = tokio::task::spawn_blocking(move || {
+            crate::query_history::upsert(provider_id.as_str(), &query);
+            if let Some(snapshot) = snapshot {
+                crate::resume::insert(snapshot);
+            }
+        });
+
+        if let Err(error) = write_data_files.await {
+            tracing::error!(
+                ?error,
+                "Failed to write the query history and session snapshot"
+            );
+        }
+    }
 }
 
 /// Threshold for large scale.
 const LARGE_SCALE: usize = 200_000;
 
-/// Performs the initialization like collecting the source and total number of source items.
-pub async fn on_session_create(context: Arc<SessionContext>) -> Result<SourceScale> {
-    let to_scale = |lines: Vec<String>| {
-        let total = lines.len();
+/// Source of the filtering job spawned by on_typed.
+type FilterSource = Source<std::vec::IntoIter<SourceItem>>;
+
+/// Maximum number of the results retained for paging when filtering the source on the fly.
+const MAX_RETAINED_RESULTS: usize = 10_000;
 
-        if total > LARGE_SCALE {
-            SourceScale::Large(total)
-        } else {
-            SourceScale::Small { total, lines }
+/// Returns the sink sending the filtering progress of `query` to Vim.
+fn progress_sink(vim: Vim, query: String, winwidth: usize, icon: icon::Icon) -> ProgressSink {
+    ProgressSink::new(move |progress| {
+        let printer::DecoratedLines {
+            lines,
+            indices,
+            truncated_map,
+            icon_added,
+        } = printer::decorate_lines(progress.top_items, winwidth, icon);
+
+        let params = json!({
+            "query": query,
+            "total": progress.matched,
+            "processed": progress.processed,
+            "done": progress.done,
+            "lines": lines,
+            "indices": indices,
+            "truncated_map": truncated_map,
+            "icon_added": icon_added,
+        });
+        if let Err(error) = vim.rpc_client.notify("s:process_progress", params) {
+            tracing::error!(?error, "Failed to send the filtering progress");
         }
+    })
+}
+
+/// Returns the equivalent native walker if `source_cmd` is `fd` or `rg --files`.
+///
+/// The walker respects the ignore files just like `fd` and `rg`, whereas `git ls-tree` lists the
+/// committed files only and `find` lists the ignored files too, so they and the custom finder
+/// commands are still executed as is.
+fn default_files_walker(source_cmd: Option<&str>, cwd: &std::path::Path) -> Option<Walker> {
+    let hidden = match source_cmd.map(str::trim) {
+        None | Some("fd --type f") | Some("rg --files") => false,
+        Some("fd --type f --hidden") | Some("rg --files --hidden") => true,
+        _ => return None,
     };
 
+    Some(Walker::new(
+        cwd,
+        WalkConfig {
+            hidden,
+            ..Default::default()
+        },
+    ))
+}
+
+/// Returns the lines of buffer `path` prefixed with the line number, the same as `maple blines`.
+fn blines_source(path: &std::path::Path) -> Result<FilterSource> {
+    let lines = std::fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| format!("{} {}", idx + 1, line).into())
+        .collect::<Vec<_>>();
+    Ok(Source::List(lines.into_iter()))
+}
+
+/// Returns the constructor of the source to be filtered on the fly if the source items are not
+/// kept in memory.
+fn streaming_source(
+    context: &SessionContext,
+) -> Option<Box<dyn FnOnce() -> Result<FilterSource> + Send>> {
+    if context.provider_id.as_str() == "files" {
+        if let Some(walker) = default_files_walker(context.source_cmd.as_deref(), &context.cwd) {
+            return Some(Box::new(move || Ok(walker.into())));
+        }
+    }
+
+    let source_cmd = context.source_cmd.clone()?;
+    let cwd = context.cwd.clone();
+    Some(Box::new(move || {
+        Ok(filter::subprocess::Exec::shell(source_cmd).cwd(cwd).into())
+    }))
+}
+
+/// Returns the scale of the collected source `lines`.
+fn to_scale(lines: Vec<String>) -> SourceScale {
+    let total = lines.len();
+
+    if total > LARGE_SCALE {
+        SourceScale::Large {
+            total,
+            lines: Arc::new(lines),
+        }
+    } else {
+        SourceScale::Small {
+            total,
+            lines: Arc::new(lines),
+        }
+    }
+}
+
+/// Walks the files in the background, the walked files are streamed to Vim as the progress of an
+/// empty query and set as the source of session once the walk is done.
+///
+/// The walk is stopped once the session is terminated.
+fn spawn_files_walk(walker: Walker, context: Arc<SessionContext>) {
+    tokio::task::spawn_blocking(move || {
+        let winwidth = context.display_winwidth as usize;
+
+        let filter_context = FilterContext::new(
+            context.icon,
+            Some(100),
+            Some(winwidth),
+            context.fuzzy_matcher(),
+        )
+        .cancellation_token(context.state.cancellation_token.clone())
+        .progress_sink(progress_sink(
+            context.vim.clone(),
+            String::new(),
+            winwidth,
+            context.icon,
+        ));
+
+        let timer = StageTimer::start(context.provider_id.as_str(), Stage::Source);
+        let mut lines = Vec::new();
+        let source = Source::List(
+            walker
+                .source_items()
+                .inspect(|item| lines.push(item.raw.clone())),
+        );
+        match filter::dyn_collect("", source, filter_context) {
+            Ok(Some(_)) => {
+                timer.finish(lines.len());
+                process_source_scale(to_scale(lines), context);
+            }
+            Ok(None) => tracing::debug!("Walking the files has been cancelled"),
+            Err(e) => {
+                timer.finish_with_outcome(lines.len(), Outcome::Error);
+                tracing::error!(error = ?e, "Failed to walk the files");
+            }
+        }
+    });
+}
+
+/// Performs the initialization like collecting the source and total number of source items.
+///
+/// Returns `None` if the source is collected by a background job, which sets the source scale
+/// on its own.
+pub async fn on_session_create(context: Arc<SessionContext>) -> Result<Option<SourceScale>> {
     match context.provider_id.as_str() {
         "blines" => {
             let total =
                 crate::utils::count_lines(std::fs::File::open(&context.start_buffer_path)?)?;
-            return Ok(SourceScale::Cache {
+            return Ok(Some(SourceScale::Cache {
                 total,
                 path: context.start_buffer_path.to_path_buf(),
-            });
+            }));
         }
         "tags" => {
             let lines =
                 crate::command::ctags::buffer_tags::buffer_tags_lines(&context.start_buffer_path)?;
 
-            return Ok(SourceScale::Small {
+            return Ok(Some(SourceScale::Small {
                 total: lines.len(),
-                lines,
-            });
+                lines: Arc::new(lines),
+            }));
         }
         "proj_tags" => {
+            crate::watcher::watch(&context.cwd);
             let ctags_cmd = build_recursive_ctags_cmd(context.cwd.to_path_buf());
             let scale = if context.no_cache {
+                let fingerprint = ctags_cmd.fingerprint();
                 let lines = ctags_cmd.par_formatted_lines()?;
-                ctags_cmd.create_cache_async(lines.clone()).await?;
+                ctags_cmd
+                    .create_cache_async(fingerprint, lines.clone())
+                    .await?;
                 to_scale(lines)
             } else {
                 match ctags_cmd.ctags_cache() {
                     Some((total, path)) => SourceScale::Cache { total, path },
-                    None => {
-                        let lines = ctags_cmd.par_formatted_lines()?;
-                        ctags_cmd.create_cache_async(lines.clone()).await?;
-                        to_scale(lines)
-                    }
+                    None => match ctags_cmd.outdated_ctags_cache() {
+                        Some((total, path)) => {
+                            // Serve the outdated cache while refreshing it.
+                            let job_id = utility::calculate_hash(&ctags_cmd);
+                            spawn_singleton_blocking_job(
+                                move || {
+                                    if let Err(e) = ctags_cmd.par_create_cache() {
+                                        tracing::error!(?e, "Failed to refresh the tags cache");
+                                    }
+                                },
+                                job_id,
+                            );
+                            SourceScale::Cache { total, path }
+                        }
+                        None => {
+                            let fingerprint = ctags_cmd.fingerprint();
+                            let lines = ctags_cmd.par_formatted_lines()?;
+                            ctags_cmd
+                                .create_cache_async(fingerprint, lines.clone())
+                                .await?;
+                            to_scale(lines)
+                        }
+                    },
                 }
             };
-            return Ok(scale);
+            return Ok(Some(scale));
         }
         "grep2" => {
-            let rg_cmd = RgBaseCommand::new(context.cwd.to_path_buf());
+            crate::watcher::watch(&context.cwd);
+            let backend = context.env.grep_backend;
+            let rg_cmd = RgBaseCommand::new(context.cwd.to_path_buf(), backend);
             let (total, path) = if context.no_cache {
                 rg_cmd.create_cache().await?
             } else {
                 match rg_cmd.cache_info() {
                     Some(cache) => cache,
-                    None => rg_cmd.create_cache().await?,
+                    None => match rg_cmd.outdated_cache_info() {
+                        Some(cache) => {
+                            // Serve the outdated cache while refreshing it.
+                            let job_id = utility::calculate_hash(&rg_cmd.inner);
+                            spawn_singleton_blocking_job(
+                                move || {
+                                    if let Err(e) = refresh_cache(&rg_cmd.inner.cwd, backend) {
+                                        tracing::error!(?e, "Failed to refresh the grep cache");
+                                    }
+                                },
+                                job_id,
+                            );
+                            cache
+                        }
+                        None => rg_cmd.create_cache().await?,
+                    },
                 }
             };
-            let method = "clap#state#set_variable_string";
-            let name = "g:__clap_forerunner_tempfile";
-            let value = &path;
-            utility::println_json_with_length!(method, name, value);
-            return Ok(SourceScale::Cache { total, path });
+            context.vim.write_response(json!({
+                "method": "clap#state#set_variable_string",
+                "name": "g:__clap_forerunner_tempfile",
+                "value": &path,
+            }));
+            return Ok(Some(SourceScale::Cache { total, path }));
+        }
+        "files" => {
+            if let Some(walker) = default_files_walker(context.source_cmd.as_deref(), &context.cwd)
+            {
+                spawn_files_walk(walker, context);
+                return Ok(None);
+            }
         }
         _ => {}
     }
@@ -205,10 +762,26 @@ pub async fn on_session_create(context: Arc<SessionContext>) -> Result<SourceSca
         let lines = TokioCommand::new(source_cmd)
             .current_dir(&context.cwd)
             .lines()
-            .await?;
+            .await
+            .map_err(|e| RpcError::from_command_error(source_cmd, e))?;
 
-        return Ok(to_scale(lines));
+        return Ok(Some(to_scale(lines)));
     }
 
-    Ok(SourceScale::Indefinite)
+    Ok(Some(SourceScale::Indefinite))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_files_walker() {
+        let cwd = std::path::Path::new(".");
+        assert!(default_files_walker(Some("fd --type f"), cwd).is_some());
+        assert!(default_files_walker(Some("rg --files --hidden"), cwd).is_some());
+        assert!(default_files_walker(Some("git ls-tree -r --name-only HEAD"), cwd).is_none());
+        assert!(default_files_walker(Some("find . -type f"), cwd).is_none());
+        assert!(default_files_walker(Some("fd --type f --exclude target"), cwd).is_none());
+    }
 }
@@ -14,9 +14,7 @@ use crate::command::ctags::buffer_tags::{
     current_context_tag, current_context_tag_async, BufferTagInfo,
 };
 use crate::previewer::{self, vim_help::HelpTagPreview};
-use crate::stdio_server::{
-    global, providers::filer, session::SessionContext, write_response, MethodCall,
-};
+use crate::stdio_server::{providers::filer, session::SessionContext, MethodCall};
 use crate::utils::build_abs_path;
 
 static IS_FERESHING_CACHE: Lazy<AtomicBool> = Lazy::new(|| AtomicBool::new(false));
@@ -72,7 +70,7 @@ impl OnMove {
                 let (lnum, p) =
                     extract_proj_tags(&curline).context("Couldn't extract proj tags")?;
                 let mut path: PathBuf = context.cwd.clone();
-                path.push(&p);
+                path.push(p);
                 Self::ProjTags(Position::new(path, lnum))
             }
             "coc_location" | "grep" | "grep2" => {
@@ -170,10 +168,10 @@ impl<'a> OnMoveHandler<'a> {
         let msg_id = msg.id;
         let curline = match curline {
             Some(line) => line,
-            None => msg.get_curline(&context.provider_id)?,
+            None => msg.get_curline(&context.provider_id, &context.env)?,
         };
         if context.provider_id.as_str() == "filer" {
-            let path = build_abs_path(&msg.get_cwd(), curline);
+            let path = build_abs_path(&msg.get_cwd()?, curline);
             return Ok(Self {
                 msg_id,
                 size: context.sensible_preview_size(),
@@ -198,8 +196,8 @@ impl<'a> OnMoveHandler<'a> {
             BLines(position) | Grep(position) | ProjTags(position) | BufferTags(position) => {
                 self.preview_file_at(position).await
             }
-            Filer(path) if path.is_dir() => self.preview_directory(&path)?,
-            Files(path) | Filer(path) | History(path) => self.preview_file(&path)?,
+            Filer(path) if path.is_dir() => self.preview_directory(path)?,
+            Files(path) | Filer(path) | History(path) => self.preview_file(path)?,
             Commit(rev) => self.show_commit(rev)?,
             HelpTags {
                 subject,
@@ -213,7 +211,9 @@ impl<'a> OnMoveHandler<'a> {
 
     fn send_response(&self, result: serde_json::value::Value) {
         let provider_id = &self.context.provider_id;
-        write_response(json!({ "id": self.msg_id, "provider_id": provider_id, "result": result }));
+        self.context.vim.write_response(
+            json!({ "id": self.msg_id, "provider_id": provider_id, "result": result }),
+        );
     }
 
     fn show_commit(&self, rev: &str) -> Result<()> {
@@ -231,7 +231,7 @@ impl<'a> OnMoveHandler<'a> {
         let preview_tag = HelpTagPreview::new(subject, doc_filename, runtimepath);
         if let Some((fname, lines)) = preview_tag.get_help_lines(self.size * 2) {
             let lines = std::iter::once(fname.clone())
-                .chain(lines.into_iter())
+                .chain(lines)
                 .collect::<Vec<_>>();
             self.send_response(
                 json!({ "syntax": "help", "lines": lines, "hi_lnum": 1, "fname": fname }),
@@ -253,11 +253,12 @@ impl<'a> OnMoveHandler<'a> {
                 if !cache_line.eq(latest_line) {
                     tracing::debug!(?latest_line, ?cache_line, "The cache might be oudated");
                     let dir = self.context.cwd.clone();
+                    let backend = self.context.env.grep_backend;
                     IS_FERESHING_CACHE.store(true, Ordering::SeqCst);
                     // Spawn a future in the background
-                    tokio::task::spawn_blocking(|| {
+                    tokio::task::spawn_blocking(move || {
                         tracing::debug!(?dir, "Attempting to refresh grep2 cache");
-                        match crate::command::grep::refresh_cache(dir) {
+                        match crate::command::grep::refresh_cache(dir, backend) {
                             Ok(total) => {
                                 tracing::debug!(total, "Refresh the grep2 cache successfully");
                             }
@@ -315,15 +316,14 @@ impl<'a> OnMoveHandler<'a> {
                                 Some(tag) if tag.line < start => {
                                     context_lines.reserve_exact(3);
 
-                                    let border_line = if crate::stdio_server::global().is_nvim {
+                                    let border_line = if self.context.env.is_nvim {
                                         "─".repeat(container_width)
                                     } else {
                                         // Vim has a different border width.
                                         let mut border_line =
                                             String::with_capacity(container_width - 2);
-                                        border_line.extend(
-                                            std::iter::repeat('─').take(container_width - 2),
-                                        );
+                                        border_line
+                                            .extend(std::iter::repeat_n('─', container_width - 2));
                                         border_line
                                     };
 
@@ -357,7 +357,7 @@ impl<'a> OnMoveHandler<'a> {
                 let highlight_lnum = highlight_lnum + context_lines.len();
 
                 let lines = std::iter::once(format!("{}:{}", fname, lnum))
-                    .chain(context_lines.into_iter())
+                    .chain(context_lines)
                     .chain(self.truncate_preview_lines(lines.into_iter()))
                     .collect::<Vec<_>>();
 
@@ -418,7 +418,7 @@ impl<'a> OnMoveHandler<'a> {
     }
 
     fn preview_directory<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let enable_icon = global().enable_icon;
+        let enable_icon = self.context.env.enable_icon;
         let lines = filer::read_dir_entries(&path, enable_icon, Some(2 * self.size))?;
         self.send_response(json!({ "lines": lines, "is_dir": true }));
         Ok(())
@@ -0,0 +1,230 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use pattern::*;
+
+use crate::find_usages::Usage;
+use crate::stdio_server::{session::SessionContext, MethodCall};
+use crate::utils::build_abs_path;
+
+/// Structured location of a selected line, can be used as a quickfix entry directly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub filename: String,
+    pub lnum: usize,
+    pub col: usize,
+    pub text: String,
+}
+
+impl Location {
+    /// Parses the raw `line` of provider `provider_id`.
+    ///
+    /// Returns `None` if the provider is unsupported or the line is malformed.
+    pub fn parse(
+        provider_id: &str,
+        line: &str,
+        cwd: &Path,
+        start_buffer_path: &Path,
+    ) -> Option<Self> {
+        let in_cwd = |fpath: &Path| {
+            let fpath = fpath.strip_prefix("./").unwrap_or(fpath);
+            cwd.join(fpath).to_string_lossy().into_owned()
+        };
+
+        let location = match provider_id {
+            "files" | "git_files" => Self {
+                filename: build_abs_path(cwd, line).to_string_lossy().into_owned(),
+                lnum: 1,
+                col: 1,
+                text: line.into(),
+            },
+            "grep" | "grep2" => {
+                let (fpath, lnum, col, text) = extract_grep_position(line)?;
+                Self {
+                    filename: in_cwd(&fpath),
+                    lnum,
+                    col,
+                    text: text.into(),
+                }
+            }
+            "proj_tags" => {
+                let (lnum, fpath) = extract_proj_tags(line)?;
+                Self {
+                    filename: in_cwd(Path::new(fpath)),
+                    lnum,
+                    col: 1,
+                    text: line.trim().into(),
+                }
+            }
+            "blines" => {
+                let lnum = extract_blines_lnum(line)?;
+                let text = line
+                    .trim_start()
+                    .split_once(char::is_whitespace)
+                    .map(|(_, text)| text.trim_start())
+                    .unwrap_or_default();
+                Self {
+                    filename: start_buffer_path.to_string_lossy().into_owned(),
+                    lnum,
+                    col: 1,
+                    text: text.into(),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(location)
+    }
+
+    /// Converts the structured `usage` of dumb_jump, `None` if the usage is not addressable.
+    pub fn from_usage(usage: &Usage, cwd: &Path) -> Option<Self> {
+        if !usage.is_addressable() {
+            return None;
+        }
+        let path = Path::new(&usage.path);
+        Some(Self {
+            filename: cwd
+                .join(path.strip_prefix("./").unwrap_or(path))
+                .to_string_lossy()
+                .into_owned(),
+            lnum: usage.line_number,
+            col: usage.column,
+            text: usage.pattern.clone(),
+        })
+    }
+}
+
+/// Sends the locations of the lines displayed at `lnums` to Vim.
+///
+/// The displayed lines are sent by Vim as the session may not own the filtering, e.g., the
+/// results of the files provider are filtered by the external maple jobs by default, the lines
+/// that can not be parsed are skipped.
+pub fn send_selection(msg: MethodCall, context: &SessionContext) -> Result<()> {
+    send_locations(msg, context, |_lnum, line| {
+        Location::parse(
+            context.provider_id.as_str(),
+            line,
+            &context.cwd,
+            &context.start_buffer_path,
+        )
+    })
+}
+
+/// Same as [`send_selection`], but the location is resolved by `get_location` given the 1-based
+/// line number of display window and the displayed line without the icon.
+pub fn send_locations(
+    msg: MethodCall,
+    context: &SessionContext,
+    get_location: impl Fn(usize, &str) -> Option<Location>,
+) -> Result<()> {
+    #[derive(Deserialize)]
+    struct Params {
+        lnums: Vec<usize>,
+        lines: Vec<String>,
+        #[serde(default)]
+        icon_added: bool,
+    }
+
+    let msg_id = msg.id;
+    let Params {
+        lnums,
+        lines,
+        icon_added,
+    } = msg.parse()?;
+
+    let locations = lnums
+        .into_iter()
+        .zip(lines.iter())
+        .filter_map(|(lnum, line)| get_location(lnum, strip_icon(line, icon_added)))
+        .collect::<Vec<_>>();
+
+    context.vim.write_response(json!({
+        "id": msg_id,
+        "provider_id": context.provider_id,
+        "result": { "locations": locations },
+    }));
+
+    Ok(())
+}
+
+/// Returns `line` without the leading icon if it's been added.
+fn strip_icon(line: &str, icon_added: bool) -> &str {
+    if icon_added {
+        line.get(icon::ICON_LEN..).unwrap_or(line)
+    } else {
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_location() {
+        let cwd = Path::new("/repo");
+        let buffer = Path::new("/repo/src/lib.rs");
+
+        assert_eq!(
+            Location::parse("grep2", "./src/main.rs:10:5:fn main() {", cwd, buffer),
+            Some(Location {
+                filename: "/repo/src/main.rs".into(),
+                lnum: 10,
+                col: 5,
+                text: "fn main() {".into(),
+            })
+        );
+
+        let usage = Usage {
+            line: "[rrefs]./src/context.rs:36:8:        let cwd = msg.get_cwd();".into(),
+            indices: vec![],
+            path: "./src/context.rs".into(),
+            line_number: 36,
+            column: 9,
+            pattern: "        let cwd = msg.get_cwd();".into(),
+        };
+        assert_eq!(
+            Location::from_usage(&usage, cwd),
+            Some(Location {
+                filename: "/repo/src/context.rs".into(),
+                lnum: 36,
+                col: 9,
+                text: "        let cwd = msg.get_cwd();".into(),
+            })
+        );
+        assert!(Location::from_usage(&Usage::new("foo".into(), vec![]), cwd).is_none());
+        assert!(Location::parse("dumb_jump", &usage.line, cwd, buffer).is_none());
+
+        assert_eq!(
+            Location::parse("blines", " 103       call foo()", cwd, buffer),
+            Some(Location {
+                filename: "/repo/src/lib.rs".into(),
+                lnum: 103,
+                col: 1,
+                text: "call foo()".into(),
+            })
+        );
+
+        assert_eq!(
+            Location::parse("files", "src/main.rs", cwd, buffer).map(|l| l.filename),
+            Some("/repo/src/main.rs".into())
+        );
+
+        assert_eq!(
+            Location::parse(
+                "files",
+                strip_icon("\u{e7a8} src/main.rs", true),
+                cwd,
+                buffer
+            )
+            .map(|l| l.filename),
+            Some("/repo/src/main.rs".into())
+        );
+
+        assert!(Location::parse("grep", "not a grep line", cwd, buffer).is_none());
+        assert!(Location::parse("filer", "src/", cwd, buffer).is_none());
+    }
+}
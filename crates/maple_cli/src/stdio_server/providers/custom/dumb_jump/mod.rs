@@ -1,6 +1,7 @@
 mod searcher;
 
 use std::ops::Deref;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
@@ -14,11 +15,13 @@ use filter::Query;
 
 use self::searcher::{SearchEngine, SearchingWorker};
 use crate::find_usages::{CtagsSearcher, GtagsSearcher, QueryType, Usage, Usages};
+use crate::metrics::{Stage, StageTimer};
 use crate::stdio_server::{
-    providers::builtin::OnMoveHandler,
-    rpc::Call,
+    providers::builtin::{send_locations, Location, OnMoveHandler},
+    rpc::{Call, RpcError},
     session::{note_job_is_finished, register_job_successfully, EventHandle, SessionContext},
-    write_response, MethodCall,
+    vim::Vim,
+    MethodCall,
 };
 use crate::tools::ctags::{get_language, TagsConfig};
 use crate::utils::ExactOrInverseTerms;
@@ -62,6 +65,7 @@ fn parse_query_info(query: &str) -> QueryInfo {
         exact_terms,
         inverse_terms,
         fuzzy_terms,
+        ..
     } = Query::from(query);
 
     // If there is no fuzzy term, use the full query as the keyword,
@@ -137,11 +141,12 @@ struct Params {
 }
 
 #[inline]
-fn parse_msg(msg: MethodCall) -> (u64, Params) {
-    (msg.id, msg.parse_unsafe())
+fn parse_msg(msg: MethodCall) -> (u64, Result<Params>) {
+    (msg.id, msg.parse())
 }
 
 async fn search_for_usages(
+    vim: &Vim,
     msg_id: u64,
     params: Params,
     maybe_search_info: Option<QueryInfo>,
@@ -160,6 +165,8 @@ async fn search_for_usages(
 
     let query_info = maybe_search_info.unwrap_or_else(|| parse_query_info(query.as_ref()));
 
+    let timer = StageTimer::start("dumb_jump", Stage::Search);
+
     let (response, usages) = match search_engine
         .run(SearchingWorker {
             cwd,
@@ -169,6 +176,7 @@ async fn search_for_usages(
         .await
     {
         Ok(usages) => {
+            timer.finish(usages.len());
             let response = {
                 let total = usages.len();
                 // Only show the top 200 items.
@@ -189,16 +197,12 @@ async fn search_for_usages(
         }
         Err(e) => {
             tracing::error!(error = ?e, "Error at running dumb_jump");
-            let response = json!({
-                "id": msg_id,
-                "provider_id": "dumb_jump",
-                "error": { "message": e.to_string() }
-            });
+            let response = json!(RpcError::from(e).into_failure(msg_id));
             (response, Default::default())
         }
     };
 
-    write_response(response);
+    vim.write_response(response);
 
     SearchResults {
         usages,
@@ -226,6 +230,7 @@ impl DumbJumpHandle {
     /// Starts a new searching task.
     async fn start_search(
         &self,
+        vim: &Vim,
         msg_id: u64,
         params: Params,
         query_info: QueryInfo,
@@ -239,15 +244,26 @@ impl DumbJumpHandle {
             _ => SearchEngine::Regex,
         };
 
-        search_for_usages(msg_id, params, Some(query_info), search_engine, false).await
+        search_for_usages(vim, msg_id, params, Some(query_info), search_engine, false).await
     }
 }
 
 #[async_trait::async_trait]
 impl EventHandle for DumbJumpHandle {
-    async fn on_create(&mut self, call: Call, _context: Arc<SessionContext>) {
+    async fn on_create(&mut self, call: Call, context: Arc<SessionContext>) {
         let (msg_id, params) = parse_msg(call.unwrap_method_call());
 
+        let params = match params {
+            Ok(params) => params,
+            Err(e) => {
+                context.vim.write_failure(msg_id, e);
+                return;
+            }
+        };
+
+        // The gtags DB is kept fresh by the watcher if enabled.
+        crate::watcher::watch(Path::new(&params.cwd));
+
         let job_id = utility::calculate_hash(&(&params.cwd, "dumb_jump"));
 
         if register_job_successfully(job_id) {
@@ -350,14 +366,14 @@ impl EventHandle for DumbJumpHandle {
             let on_move_handler = OnMoveHandler::create(&msg, &context, Some(curline.into()))?;
             if let Err(error) = on_move_handler.handle().await {
                 tracing::error!(?error, "Failed to handle OnMove event");
-                write_response(json!({"error": error.to_string(), "id": msg_id }));
+                context.vim.write_failure(msg_id, error);
             }
         }
 
         Ok(())
     }
 
-    async fn on_typed(&mut self, msg: MethodCall, _context: Arc<SessionContext>) -> Result<()> {
+    async fn on_typed(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
         /*
         // TODO: early initialization
         if !self.first_on_typed_event_received.load(Ordering::Relaxed) {
@@ -370,6 +386,7 @@ impl EventHandle for DumbJumpHandle {
         */
 
         let (msg_id, params) = parse_msg(msg);
+        let params = params?;
 
         let query_info = parse_query_info(&params.query);
 
@@ -379,18 +396,22 @@ impl EventHandle for DumbJumpHandle {
                 .cached_results
                 .usages
                 .par_iter()
-                .filter_map(|Usage { line, indices }| {
+                .filter_map(|usage| {
                     query_info
                         .filtering_terms
-                        .check_jump_line((line.clone(), indices.clone()))
-                        .map(|(line, indices)| Usage::new(line, indices))
+                        .check_jump_line((usage.line.clone(), usage.indices.clone()))
+                        .map(|(line, indices)| Usage {
+                            line,
+                            indices,
+                            ..usage.clone()
+                        })
                 })
                 .collect::<Vec<_>>();
             let total = refiltered.len();
             let (lines, indices): (Vec<&str>, Vec<&[usize]>) = refiltered
                 .iter()
                 .take(200)
-                .map(|Usage { line, indices }| (line.as_str(), indices.as_slice()))
+                .map(|Usage { line, indices, .. }| (line.as_str(), indices.as_slice()))
                 .unzip();
             let response = json!({
                 "id": msg_id,
@@ -398,16 +419,33 @@ impl EventHandle for DumbJumpHandle {
                 "force_execute": true,
                 "result": { "lines": lines, "indices": indices, "total": total },
             });
-            write_response(response);
+            context.vim.write_response(response);
             self.current_usages.replace(refiltered.into());
             return Ok(());
         }
 
-        self.cached_results = self.start_search(msg_id, params, query_info).await;
+        self.cached_results = self
+            .start_search(&context.vim, msg_id, params, query_info)
+            .await;
         self.current_usages.take();
 
         Ok(())
     }
+
+    async fn on_selection(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
+        let usages = self
+            .current_usages
+            .as_ref()
+            .unwrap_or(&self.cached_results.usages);
+        send_locations(msg, &context, |lnum, line| {
+            let usage = usages.get(lnum.checked_sub(1)?)?;
+            // The usages could have been replaced since the line was displayed.
+            if usage.line != line {
+                return None;
+            }
+            Location::from_usage(usage, &context.cwd)
+        })
+    }
 }
 
 #[cfg(test)]
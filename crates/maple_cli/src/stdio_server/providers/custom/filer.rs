@@ -4,15 +4,16 @@ use std::{fs, io};
 
 use anyhow::Result;
 use jsonrpc_core::Value;
+use serde::Serialize;
 use serde_json::json;
 
 use icon::prepend_filer_icon;
 
 use crate::stdio_server::providers::builtin::{OnMove, OnMoveHandler};
 use crate::stdio_server::{
-    rpc::Call,
+    rpc::{Call, RpcError},
     session::{EventHandle, SessionContext},
-    write_response, MethodCall,
+    MethodCall,
 };
 use crate::utils::build_abs_path;
 
@@ -128,11 +129,11 @@ pub struct FilerHandle;
 
 #[async_trait::async_trait]
 impl EventHandle for FilerHandle {
-    async fn on_create(&mut self, call: Call, _context: Arc<SessionContext>) {
-        write_response(
-            handle_filer_message(call.unwrap_method_call())
-                .expect("Both Success and Error are returned"),
-        );
+    async fn on_create(&mut self, call: Call, context: Arc<SessionContext>) {
+        context.vim.write_response(handle_filer_message(
+            call.unwrap_method_call(),
+            context.env.enable_icon,
+        ));
     }
 
     async fn on_move(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
@@ -143,8 +144,8 @@ impl EventHandle for FilerHandle {
         }
         let msg_id = msg.id;
         // Do not use curline directly.
-        let curline = msg.get_curline(&context.provider_id)?;
-        let Params { cwd } = msg.parse_unsafe();
+        let curline = msg.get_curline(&context.provider_id, &context.env)?;
+        let Params { cwd } = msg.parse()?;
         let path = build_abs_path(&cwd, curline);
         let on_move_handler = OnMoveHandler {
             msg_id,
@@ -155,35 +156,45 @@ impl EventHandle for FilerHandle {
         };
         if let Err(err) = on_move_handler.handle().await {
             tracing::error!(?err, ?path, "Failed to handle filer OnMove");
-            let res = json!({
-              "id": msg_id,
-              "provider_id": "filer",
-              "error": { "message": err.to_string(), "dir": path }
-            });
-            write_response(res);
+            context
+                .vim
+                .write_response(filer_failure(msg_id, err, &path));
         }
         Ok(())
     }
 
-    async fn on_typed(&mut self, msg: MethodCall, _context: Arc<SessionContext>) -> Result<()> {
-        write_response(handle_filer_message(msg).expect("Both Success and Error are returned"));
+    async fn on_typed(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
+        context
+            .vim
+            .write_response(handle_filer_message(msg, context.env.enable_icon));
         Ok(())
     }
 }
 
-fn handle_filer_message(msg: MethodCall) -> std::result::Result<Value, Value> {
-    let cwd = msg.get_cwd();
+/// Returns the failure response, `dir` is attached to the error data so that the Vim side can
+/// remember the error of that directory.
+fn filer_failure(msg_id: u64, error: anyhow::Error, dir: impl Serialize) -> Value {
+    let mut failure = RpcError::from(error).into_failure(msg_id);
+    failure.error.data = Some(json!({ "dir": dir }));
+    json!(failure)
+}
+
+fn handle_filer_message(msg: MethodCall, enable_icon: bool) -> Value {
+    let cwd = match msg.get_cwd() {
+        Ok(cwd) => cwd,
+        Err(err) => return json!(RpcError::from(err).into_failure(msg.id)),
+    };
 
-    read_dir_entries(&cwd, crate::stdio_server::global().enable_icon, None)
-        .map(|entries| {
+    match read_dir_entries(&cwd, enable_icon, None) {
+        Ok(entries) => {
             let result = json!({ "entries": entries, "dir": cwd, "total": entries.len() });
             json!({ "id": msg.id, "provider_id": "filer", "result": result })
-        })
-        .map_err(|err| {
+        }
+        Err(err) => {
             tracing::error!(?cwd, "Failed to read directory entries");
-            let error = json!({"message": err.to_string(), "dir": cwd});
-            json!({ "id": msg.id, "provider_id": "filer", "message": error })
-        })
+            filer_failure(msg.id, err, &cwd)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -194,7 +205,7 @@ mod tests {
     fn test_dir() {
         // /home/xlc/.vim/plugged/vim-clap/crates/stdio_server
         let entries = read_dir_entries(
-            &std::env::current_dir()
+            std::env::current_dir()
                 .unwrap()
                 .into_os_string()
                 .into_string()
@@ -13,14 +13,14 @@ use crate::stdio_server::{
     providers::builtin::OnMoveHandler,
     rpc::Call,
     session::{EventHandle, Session, SessionContext, SessionEvent},
-    write_response, MethodCall,
+    MethodCall,
 };
 
 async fn handle_recent_files_message(
     msg: MethodCall,
     context: Arc<SessionContext>,
     force_execute: bool,
-) -> Vec<FilteredItem> {
+) -> Result<Vec<FilteredItem>> {
     let msg_id = msg.id;
 
     let cwd = context.cwd.to_string_lossy().to_string();
@@ -36,7 +36,7 @@ async fn handle_recent_files_message(
         query,
         enable_icon,
         lnum,
-    } = msg.parse_unsafe();
+    } = msg.parse()?;
 
     let mut recent_files = RECENT_FILES_IN_MEMORY.lock();
 
@@ -61,7 +61,7 @@ async fn handle_recent_files_message(
             })
             .collect::<Vec<_>>()
     } else {
-        recent_files.filter_on_query(&query, cwd.clone())
+        recent_files.filter_on_query(&query, cwd.clone(), &context.fuzzy_matcher())
     };
     let initial_size = recent_files.len();
 
@@ -137,9 +137,9 @@ async fn handle_recent_files_message(
         "result": result,
     });
 
-    write_response(result);
+    context.vim.write_response(result);
 
-    ranked
+    Ok(ranked)
 }
 
 #[derive(Debug, Clone, Default)]
@@ -150,17 +150,21 @@ pub struct RecentFilesHandle {
 #[async_trait::async_trait]
 impl EventHandle for RecentFilesHandle {
     async fn on_create(&mut self, call: Call, context: Arc<SessionContext>) {
-        let initial_lines =
-            handle_recent_files_message(call.unwrap_method_call(), context, true).await;
-
-        let mut lines = self.lines.lock();
-        *lines = initial_lines;
+        let msg = call.unwrap_method_call();
+        let msg_id = msg.id;
+        match handle_recent_files_message(msg, context.clone(), true).await {
+            Ok(initial_lines) => {
+                let mut lines = self.lines.lock();
+                *lines = initial_lines;
+            }
+            Err(e) => context.vim.write_failure(msg_id, e),
+        }
     }
 
     async fn on_move(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()> {
         let msg_id = msg.id;
 
-        let lnum = msg.get_u64("lnum").expect("lnum is required");
+        let lnum = msg.get_u64("lnum")?;
 
         let maybe_curline = self
             .lines
@@ -172,7 +176,7 @@ impl EventHandle for RecentFilesHandle {
             let on_move_handler = OnMoveHandler::create(&msg, &context, Some(curline))?;
             if let Err(e) = on_move_handler.handle().await {
                 tracing::error!(error = ?e, "Failed to handle OnMove event");
-                write_response(json!({"error": e.to_string(), "id": msg_id }));
+                context.vim.write_failure(msg_id, e);
             }
         }
         Ok(())
@@ -183,8 +187,8 @@ impl EventHandle for RecentFilesHandle {
             .await
             .unwrap_or_else(|e| {
                 tracing::error!(error = ?e, "Failed to spawn task handle_recent_files_message");
-                Default::default()
-            });
+                Ok(Default::default())
+            })?;
 
         let mut lines = self.lines.lock();
         *lines = new_lines;
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use jsonrpc_core::ErrorCode;
+
+use super::types::{Error, Failure};
+
+/// Error of handling the message from Vim.
+///
+/// Each variant is mapped onto a stable error code so that the Vim side can react accordingly.
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    #[error("invalid params: {0}")]
+    InvalidParams(String),
+    #[error("unknown method: {0}")]
+    UnknownMethod(String),
+    #[error("{0}")]
+    ProviderFailure(String),
+    #[error("executable not found: {0}")]
+    MissingExecutable(String),
+    /// The request has been superseded by a newer one, e.g., a newer query.
+    #[error("cancelled")]
+    Cancelled,
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+impl RpcError {
+    /// Returns the error code of JSON-RPC 2.0, the codes of the non-standard errors are in the
+    /// range reserved for the server errors.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidParams(_) => ErrorCode::InvalidParams,
+            Self::UnknownMethod(_) => ErrorCode::MethodNotFound,
+            Self::ProviderFailure(_) => ErrorCode::ServerError(-32000),
+            Self::MissingExecutable(_) => ErrorCode::ServerError(-32001),
+            Self::Cancelled => ErrorCode::ServerError(-32002),
+            Self::Timeout(_) => ErrorCode::ServerError(-32003),
+        }
+    }
+
+    /// Returns the failure response to the method call `id`.
+    pub fn into_failure(self, id: u64) -> Failure {
+        Failure {
+            id,
+            error: self.into(),
+        }
+    }
+
+    /// Converts the error of running the external command `cmd`.
+    ///
+    /// The command is considered missing if it can not be found by the OS or the shell.
+    pub fn from_command_error(cmd: &str, error: anyhow::Error) -> Self {
+        let not_found = error.chain().any(|e| {
+            e.downcast_ref::<std::io::Error>()
+                .map(|e| e.kind() == std::io::ErrorKind::NotFound)
+                .unwrap_or(false)
+        });
+
+        if not_found {
+            let program = cmd.split_whitespace().next().unwrap_or(cmd);
+            Self::MissingExecutable(program.into())
+        } else {
+            error.into()
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<Self>() {
+            Ok(rpc_error) => rpc_error,
+            Err(error) => Self::ProviderFailure(format!("{:#}", error)),
+        }
+    }
+}
+
+impl From<RpcError> for Error {
+    fn from(rpc_error: RpcError) -> Self {
+        Self {
+            code: rpc_error.code(),
+            message: rpc_error.to_string(),
+            data: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_error_into_failure() {
+        let error: RpcError = anyhow::Error::new(RpcError::UnknownMethod("foo".into())).into();
+        assert_eq!(
+            serde_json::to_value(error.into_failure(1)).unwrap(),
+            serde_json::json!({
+                "id": 1,
+                "error": { "code": -32601, "message": "unknown method: foo" }
+            })
+        );
+
+        let error: RpcError = anyhow::anyhow!("Directory not found").into();
+        assert_eq!(error.code(), ErrorCode::ServerError(-32000));
+
+        assert_eq!(RpcError::Cancelled.code(), ErrorCode::ServerError(-32002));
+
+        let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "command not found");
+        assert!(matches!(
+            RpcError::from_command_error("fd --type f", io_error.into()),
+            RpcError::MissingExecutable(program) if program == "fd"
+        ));
+    }
+}
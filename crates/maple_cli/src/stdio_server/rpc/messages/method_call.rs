@@ -6,7 +6,8 @@ use rayon::prelude::*;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
 
-use crate::stdio_server::types::ProviderId;
+use crate::stdio_server::rpc::RpcError;
+use crate::stdio_server::types::{GlobalEnv, ProviderId};
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -19,24 +20,21 @@ pub struct MethodCall {
 
 impl MethodCall {
     pub fn parse<T: DeserializeOwned>(self) -> Result<T> {
-        self.params.parse().map_err(Into::into)
+        self.params
+            .parse()
+            .map_err(|e| RpcError::InvalidParams(e.message).into())
     }
 
-    pub fn parse_unsafe<T: DeserializeOwned>(self) -> T {
-        self.parse()
-            .unwrap_or_else(|e| panic!("Couldn't deserialize params: {:?}", e))
+    pub fn get_query(&self) -> Result<String> {
+        self.get_string("query")
     }
 
-    pub fn get_query(&self) -> String {
-        self.get_string_unsafe("query")
-    }
-
-    pub fn get_cwd(&self) -> String {
-        self.get_string_unsafe("cwd")
+    pub fn get_cwd(&self) -> Result<String> {
+        self.get_string("cwd")
     }
 
     /// Get the current line of display window without the leading icon.
-    pub fn get_curline(&self, provider_id: &ProviderId) -> Result<String> {
+    pub fn get_curline(&self, provider_id: &ProviderId, env: &GlobalEnv) -> Result<String> {
         let display_curline = self.get_string("curline")?;
 
         let curline = if let Ok(enable_icon) = self.get_bool("enable_icon") {
@@ -45,7 +43,7 @@ impl MethodCall {
             } else {
                 display_curline
             }
-        } else if provider_id.should_skip_leading_icon() {
+        } else if env.should_skip_leading_icon(provider_id) {
             display_curline.chars().skip(2).collect()
         } else {
             display_curline
@@ -56,8 +54,10 @@ impl MethodCall {
 
     fn map_params(&self) -> Result<&serde_json::Map<String, Value>> {
         match &self.params {
-            Params::None => Err(anyhow!("None params unsupported")),
-            Params::Array(_) => Err(anyhow!("Array params unsupported")),
+            Params::None => Err(RpcError::InvalidParams("None params unsupported".into()).into()),
+            Params::Array(_) => {
+                Err(RpcError::InvalidParams("Array params unsupported".into()).into())
+            }
             Params::Map(map) => Ok(map),
         }
     }
@@ -67,80 +67,37 @@ impl MethodCall {
         self.map_params()?
             .get(key)
             .and_then(|x| x.as_u64())
-            .ok_or_else(|| anyhow!("Missing {} in msg.params", key))
+            .ok_or_else(|| missing_param(key))
     }
 
     pub fn get_str(&self, key: &str) -> Result<&str> {
         self.map_params()?
             .get(key)
             .and_then(|x| x.as_str())
-            .ok_or_else(|| anyhow!("Missing {} in msg.params", key))
+            .ok_or_else(|| missing_param(key))
     }
 
     pub fn get_string(&self, key: &str) -> Result<String> {
         self.get_str(key).map(Into::into)
     }
 
-    pub fn get_string_unsafe(&self, key: &str) -> String {
-        self.get_string(key)
-            .unwrap_or_else(|e| panic!("Get String error: {:?}", e))
-    }
-
     pub fn get_bool(&self, key: &str) -> Result<bool> {
         self.map_params()?
             .get(key)
             .and_then(|x| x.as_bool())
-            .ok_or_else(|| anyhow!("Missing {} in msg.params", key))
+            .ok_or_else(|| missing_param(key))
     }
 }
 
 impl MethodCall {
-    pub async fn handle(self) -> anyhow::Result<Value> {
-        use crate::stdio_server::session::SessionEvent::*;
-
-        if self.method != "init_ext_map" {
-            tracing::debug!(message = ?self, "==> stdio message(in)");
-        }
-
-        let value = match self.method.as_str() {
-            "init_ext_map" => self.parse_filetypedetect(),
-            "preview/file" => self.preview_file().await?,
-            // "quickfix" => super::quickfix::preview_quickfix_entry(msg),
-
-            /*
-            "dumb_jump/on_init" => manager.new_session::<DumbJumpSession>(msg),
-            "dumb_jump/on_typed" => manager.send(msg.session_id, OnTyped(msg)),
-            "dumb_jump/on_move" => manager.send(msg.session_id, OnMove(msg)),
-
-            "recent_files/on_init" => manager.new_session::<RecentFilesSession>(msg),
-            "recent_files/on_typed" => manager.send(msg.session_id, OnTyped(msg)),
-            "recent_files/on_move" => manager.send(msg.session_id, OnMove(msg)),
-
-            "filer" => filer::handle_filer_message(msg),
-            "filer/on_init" => manager.new_session::<FilerSession>(msg),
-            "filer/on_move" => manager.send(msg.session_id, OnMove(msg)),
-
-            "on_init" => manager.new_session::<BuiltinSession>(msg),
-            "on_typed" => manager.send(msg.session_id, OnTyped(msg)),
-            "on_move" => manager.send(msg.session_id, OnMove(msg)),
-            "exit" => manager.terminate(msg.session_id),
-            */
-            _ => json!({ "error": format!("unknown method: {}", self.method), "id": self.id }),
-        };
-
-        Ok(value)
-    }
-
-    pub fn parse_filetypedetect(self) -> Value {
+    pub fn parse_filetypedetect(self) -> Result<Value> {
         let msg = self;
-        let output = msg.get_string_unsafe("autocmd_filetypedetect");
+        let output = msg.get_string("autocmd_filetypedetect")?;
         let ext_map = crate::stdio_server::vim::initialize_syntax_map(&output);
-        json!({ "method": "clap#ext#set", "ext_map": ext_map })
+        Ok(json!({ "ext_map": ext_map }))
     }
 
     pub async fn preview_file(self) -> Result<Value> {
-        let msg_id = self.id;
-
         #[derive(Deserialize)]
         struct InnerParams {
             fpath: String,
@@ -158,7 +115,7 @@ impl MethodCall {
             preview_width,
             preview_height,
             preview_direction,
-        } = self.params.parse()?;
+        } = self.parse()?;
 
         let fpath = crate::utils::expand_tilde(fpath)?;
 
@@ -170,17 +127,46 @@ impl MethodCall {
 
         let (lines, fname) = crate::previewer::preview_file(fpath, preview_height, preview_width)?;
 
-        let value = json!({"id": msg_id, "result": json!({"lines": lines, "fname": fname})});
+        Ok(json!({"lines": lines, "fname": fname}))
+    }
 
-        Ok(value)
+    /// Returns the recorded queries of a provider, filtered by `query` if it's not empty.
+    pub fn query_history(self) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct InnerParams {
+            provider_id: String,
+            query: Option<String>,
+            number: Option<usize>,
+        }
+
+        let InnerParams {
+            provider_id,
+            query,
+            number,
+        } = self.parse()?;
+
+        let filtered = crate::datastore::QUERY_HISTORY_IN_MEMORY
+            .lock()
+            .filter_on_query(
+                &provider_id,
+                query.as_deref().unwrap_or_default(),
+                &matcher::Matcher::default(),
+            );
+
+        let total = filtered.len();
+        let (queries, indices): (Vec<_>, Vec<_>) = filtered
+            .into_iter()
+            .take(number.unwrap_or(total))
+            .map(|item| (item.source_item.raw, item.match_indices))
+            .unzip();
+
+        Ok(json!({ "total": total, "queries": queries, "indices": indices }))
     }
 
     pub async fn preview_quickfix(self) -> Result<Value> {
         use crate::previewer::{preview_file, preview_file_at};
         use std::path::PathBuf;
 
-        let msg_id = self.id;
-
         #[derive(Deserialize)]
         struct InnerParams {
             cwd: String,
@@ -194,14 +180,14 @@ impl MethodCall {
             curline,
             winwidth,
             winheight,
-        } = self.params.parse()?;
+        } = self.parse()?;
 
         let (p, lnum) = parse_quickfix_entry(curline.as_str())?;
 
         let mut fpath: PathBuf = cwd.into();
         fpath.push(p);
 
-        let result = if lnum == 0 {
+        let value = if lnum == 0 {
             let size = (winheight + 5) as usize;
             let (lines, _) = preview_file(fpath.as_path(), size, winwidth as usize)?;
             json!({ "event": "on_move", "lines": lines, "fname": fpath })
@@ -211,12 +197,14 @@ impl MethodCall {
             json!({ "event": "on_move", "lines": lines, "fname": fpath, "hi_lnum": hi_lnum })
         };
 
-        let value = json!({ "id": msg_id, "provider_id": "quickfix", "result": result });
-
         Ok(value)
     }
 }
 
+fn missing_param(key: &str) -> anyhow::Error {
+    RpcError::InvalidParams(format!("Missing {} in msg.params", key)).into()
+}
+
 fn parse_quickfix_entry(line: &str) -> Result<(&str, usize)> {
     let mut splitted = line.split('|');
     let fpath = splitted
@@ -246,7 +234,7 @@ mod tests {
         let line = "test/bench/python/test_fuzzy_filter.vim|0 col 0| Modified 2月,13 2021 10:58:27 rw-rw-r--";
         assert_eq!(
             parse_quickfix_entry(line).unwrap(),
-            ("test/bench/python/test_fuzzy_filter.vim".into(), 0usize)
+            ("test/bench/python/test_fuzzy_filter.vim", 0usize)
         );
     }
 }
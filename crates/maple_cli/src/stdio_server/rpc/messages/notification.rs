@@ -1,10 +1,10 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use jsonrpc_core::Params;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::datastore::RECENT_FILES_IN_MEMORY;
+use crate::stdio_server::rpc::RpcError;
 use crate::stdio_server::types::GlobalEnv;
-use crate::stdio_server::GLOBAL_ENV;
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -18,46 +18,42 @@ impl Notification {
     /// Process the notification message from Vim.
     pub async fn process(self) -> Result<()> {
         match self.method.as_str() {
-            "initialize_global_env" => self.initialize_global_env(), // should be called only once.
             "note_recent_files" => self.note_recent_file().await,
-            _ => Err(anyhow!("Unknown notification: {:?}", self)),
+            _ => Err(RpcError::UnknownMethod(self.method).into()),
         }
     }
 
     pub fn parse<T: DeserializeOwned>(self) -> Result<T> {
-        self.params.parse().map_err(Into::into)
+        self.params
+            .parse()
+            .map_err(|e| RpcError::InvalidParams(e.message).into())
     }
 
-    pub fn parse_unsafe<T: DeserializeOwned>(self) -> T {
-        self.parse()
-            .unwrap_or_else(|e| panic!("Couldn't deserialize params: {:?}", e))
-    }
-
-    fn initialize_global_env(self) -> Result<()> {
+    /// Parses the params of `initialize_global_env`.
+    pub fn parse_global_env(self) -> Result<GlobalEnv> {
         #[derive(Deserialize)]
         struct InnerParams {
             is_nvim: Option<bool>,
             enable_icon: Option<bool>,
             clap_preview_size: serde_json::Value,
+            grep_backend: Option<String>,
         }
         let InnerParams {
             is_nvim,
             enable_icon,
             clap_preview_size,
-        } = self.params.parse()?;
+            grep_backend,
+        } = self.parse()?;
 
         let is_nvim = is_nvim.unwrap_or(false);
         let enable_icon = enable_icon.unwrap_or(false);
 
-        let global_env = GlobalEnv::new(is_nvim, enable_icon, clap_preview_size.into());
-
-        if let Err(e) = GLOBAL_ENV.set(global_env) {
-            tracing::debug!(error = ?e, "Failed to initialized GLOBAL_ENV");
-        } else {
-            tracing::debug!("GLOBAL_ENV initialized successfully");
-        }
-
-        Ok(())
+        Ok(GlobalEnv::new(
+            is_nvim,
+            enable_icon,
+            clap_preview_size.try_into()?,
+            grep_backend.as_deref().map(Into::into).unwrap_or_default(),
+        ))
     }
 
     async fn note_recent_file(self) -> Result<()> {
@@ -66,7 +62,7 @@ impl Notification {
             file: String,
         }
 
-        let InnerParams { file } = self.params.parse()?;
+        let InnerParams { file } = self.parse()?;
 
         tracing::debug!(?file, "Receive a recent file");
         if file.is_empty() || !std::path::Path::new(&file).exists() {
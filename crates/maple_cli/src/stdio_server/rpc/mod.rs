@@ -1,3 +1,4 @@
+mod error;
 mod messages;
 mod types;
 
@@ -11,18 +12,21 @@ use jsonrpc_core::Params;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 
+use crate::metrics::{Stage, StageTimer};
+
+pub use self::error::RpcError;
 pub use self::messages::method_call::MethodCall;
 pub use self::messages::notification::Notification;
 pub use self::types::{Call, Error, Failure, Output, RawMessage, Success};
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct RpcClient {
     /// Id of request to Vim created from the Rust side.
     #[serde(skip_serializing)]
     id: AtomicU64,
     /// Sender for sending message from Rust to Vim.
     #[serde(skip_serializing)]
-    output_writer_tx: Sender<RawMessage>,
+    output_writer_tx: Sender<Value>,
     /// Sender for passing the Vim response of request initiated from Rust.
     #[serde(skip_serializing)]
     output_reader_tx: Sender<(u64, Sender<Output>)>,
@@ -33,23 +37,27 @@ impl RpcClient {
     ///
     /// # Arguments
     ///
-    /// * `reader`: a buffer reader on top of [`std::io::Stdin`].
-    /// * `writer`: a buffer writer on top of [`std::io::Stdout`].
+    /// * `reader`: a buffer reader on top of [`std::io::Stdin`] or the socket stream.
+    /// * `writer`: a buffer writer on top of [`std::io::Stdout`] or the socket stream.
     pub fn new(
         reader: impl BufRead + Send + 'static,
         writer: impl Write + Send + 'static,
         sink: Sender<Call>,
     ) -> Self {
+        let (output_writer_tx, output_writer_rx) = unbounded();
+
         // Channel for passing through the response from Vim.
         let (output_reader_tx, output_reader_rx): (Sender<(u64, Sender<Output>)>, _) = unbounded();
-        tokio::spawn(async move {
-            if let Err(error) = loop_read(reader, output_reader_rx, &sink) {
+        let invalid_message_tx = output_writer_tx.clone();
+        // Both loops block on the IO, keep them off the async workers as each connection has
+        // its own pair.
+        tokio::task::spawn_blocking(move || {
+            if let Err(error) = loop_read(reader, output_reader_rx, &sink, &invalid_message_tx) {
                 tracing::error!(?error, "Thread stdio-reader exited");
             }
         });
 
-        let (output_writer_tx, output_writer_rx) = unbounded();
-        tokio::spawn(async move {
+        tokio::task::spawn_blocking(move || {
             if let Err(error) = loop_write(writer, &output_writer_rx) {
                 tracing::error!(?error, "Thread stdio-writer exited");
             }
@@ -79,9 +87,12 @@ impl RpcClient {
         };
         let (tx, rx) = bounded(1);
         self.output_reader_tx.send((id, tx))?;
-        self.output_writer_tx
-            .send(RawMessage::MethodCall(method_call))?;
-        match rx.recv_timeout(std::time::Duration::from_secs(60))? {
+        self.write(RawMessage::MethodCall(method_call))?;
+        let timeout = std::time::Duration::from_secs(60);
+        match rx
+            .recv_timeout(timeout)
+            .map_err(|_| RpcError::Timeout(timeout))?
+        {
             Output::Success(ok) => Ok(serde_json::from_value(ok.result)?),
             Output::Failure(err) => Err(anyhow!("Error: {:?}", err)),
         }
@@ -95,10 +106,7 @@ impl RpcClient {
             session_id: 888u64, // FIXME
         };
 
-        self.output_writer_tx
-            .send(RawMessage::Notification(notification))?;
-
-        Ok(())
+        self.write(RawMessage::Notification(notification))
     }
 
     /// Sends the response from Rust to Vim.
@@ -108,18 +116,17 @@ impl RpcClient {
                 id,
                 result: serde_json::to_value(ok)?,
             }),
-            Err(err) => Output::Failure(Failure {
-                id,
-                error: Error {
-                    code: jsonrpc_core::ErrorCode::InternalError,
-                    message: err.to_string(),
-                    data: None,
-                },
-            }),
+            Err(err) => Output::Failure(RpcError::from(err).into_failure(id)),
         };
 
-        self.output_writer_tx.send(RawMessage::Output(output))?;
+        self.write(RawMessage::Output(output))
+    }
 
+    /// Writes an arbitrary message to Vim.
+    ///
+    /// Used by the session handlers whose responses carry extra fields, e.g., `provider_id`.
+    pub fn write(&self, msg: impl Serialize) -> Result<()> {
+        self.output_writer_tx.send(serde_json::to_value(msg)?)?;
         Ok(())
     }
 }
@@ -129,6 +136,7 @@ fn loop_read(
     reader: impl BufRead,
     output_reader_rx: Receiver<(u64, Sender<Output>)>,
     sink: &Sender<Call>,
+    output_writer_tx: &Sender<Value>,
 ) -> Result<()> {
     let mut pending_outputs = HashMap::new();
 
@@ -158,32 +166,59 @@ fn loop_read(
                                 }
                             }
                         },
-                        Err(e) => {
-                            tracing::error!(?line, "Invalid raw message");
+                        Err(error) => {
+                            tracing::error!(?error, ?line, "Invalid raw message");
+                            // Reply to the malformed method call so that the caller is not left
+                            // waiting.
+                            if let Some(id) = serde_json::from_str::<Value>(line.trim())
+                                .ok()
+                                .and_then(|value| value.get("id").and_then(Value::as_u64))
+                            {
+                                let failure =
+                                    RpcError::InvalidParams(error.to_string()).into_failure(id);
+                                output_writer_tx.send(serde_json::to_value(failure)?)?;
+                            }
                         }
                     }
                 } else {
-                    println!("EOF reached");
+                    tracing::debug!("EOF reached");
+                    return Ok(());
                 }
             }
-            Err(error) => println!("Failed to read_line, error: {}", error),
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => {
+                tracing::error!(?error, "Failed to read_line");
+                return Err(error.into());
+            }
         }
     }
 }
 
 /// Keep writing the response from Rust backend to Vim via stdout.
-fn loop_write(writer: impl Write, rx: &Receiver<RawMessage>) -> Result<()> {
+fn loop_write(writer: impl Write, rx: &Receiver<Value>) -> Result<()> {
     let mut writer = writer;
 
     for msg in rx.iter() {
         tracing::debug!(?msg, "Sending back to the Vim side");
+
+        // The messages not specific to a provider are recorded as `rpc`.
+        let provider_id = msg
+            .get("provider_id")
+            .and_then(Value::as_str)
+            .unwrap_or("rpc");
+
+        let timer = StageTimer::start(provider_id, Stage::Serialize);
         let s = serde_json::to_string(&msg)?;
+        timer.finish(s.len());
+
+        let timer = StageTimer::start(provider_id, Stage::Write);
         // Use different convention for two reasons,
         // 1. If using '\r\ncontent', nvim will receive output as `\r` + `content`, while vim
         // receives `content`.
         // 2. Without last line ending, vim output handler won't be triggered.
         write!(writer, "Content-length: {}\n\n{}\n", s.len(), s)?;
         writer.flush()?;
+        timer.finish(s.len());
     }
 
     Ok(())
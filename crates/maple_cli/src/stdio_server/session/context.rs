@@ -1,19 +1,21 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{atomic::AtomicBool, Arc};
 
 use anyhow::Result;
-use filter::FilteredItem;
+use filter::{CancellationToken, CaseMatching, FilteredItem};
 use icon::{Icon, IconKind};
 use jsonrpc_core::Params;
-use matcher::MatchScope;
+use matcher::{Bonus, FuzzyAlgorithm, MatchScope};
 use parking_lot::Mutex;
 use serde::Deserialize;
 
 use crate::command::ctags::buffer_tags::BufferTagInfo;
+use crate::datastore::RECENT_FILES_IN_MEMORY;
 use crate::stdio_server::{
-    rpc::{Call, MethodCall, Notification},
-    types::ProviderId,
+    rpc::{Call, RpcError},
+    types::{GlobalEnv, ProviderId},
+    vim::Vim,
 };
 
 const DEFAULT_DISPLAY_WINWIDTH: u64 = 100;
@@ -21,34 +23,35 @@ const DEFAULT_DISPLAY_WINWIDTH: u64 = 100;
 const DEFAULT_PREVIEW_WINHEIGHT: u64 = 30;
 
 /// This type represents the scale of filtering source.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum SourceScale {
     /// We do not know the exact total number of source items.
+    #[default]
     Indefinite,
 
     /// Large scale.
     ///
     /// The number of total source items is already known, but that's
     /// too many for the synchorous filtering.
-    Large(usize),
+    Large {
+        total: usize,
+        lines: Arc<Vec<String>>,
+    },
 
     /// Small scale, in which case we do not have to use the dynamic filtering.
-    Small { total: usize, lines: Vec<String> },
+    Small {
+        total: usize,
+        lines: Arc<Vec<String>>,
+    },
 
     /// Unknown scale, but the cache exists.
     Cache { total: usize, path: PathBuf },
 }
 
-impl Default for SourceScale {
-    fn default() -> Self {
-        Self::Indefinite
-    }
-}
-
 impl SourceScale {
     pub fn total(&self) -> Option<usize> {
         match self {
-            Self::Large(total) | Self::Small { total, .. } | Self::Cache { total, .. } => {
+            Self::Large { total, .. } | Self::Small { total, .. } | Self::Cache { total, .. } => {
                 Some(*total)
             }
             _ => None,
@@ -60,6 +63,9 @@ impl SourceScale {
             Self::Small { ref lines, .. } => {
                 Some(lines.iter().take(n).map(|s| s.as_str().into()).collect())
             }
+            Self::Large { ref lines, .. } => {
+                Some(lines.iter().take(n).map(|s| s.as_str().into()).collect())
+            }
             Self::Cache { ref path, .. } => {
                 if let Ok(lines_iter) = utility::read_first_lines(path, n) {
                     Some(lines_iter.map(Into::into).collect::<Vec<_>>())
@@ -82,6 +88,8 @@ pub struct CachedBufTags {
 #[derive(Debug, Clone)]
 pub struct SessionState {
     pub is_running: Arc<AtomicBool>,
+    /// Cancelled on terminate to stop the background jobs of this session, e.g., walking the files.
+    pub cancellation_token: CancellationToken,
     pub source_scale: Arc<Mutex<SourceScale>>,
     pub buf_tags_cache: Arc<Mutex<HashMap<PathBuf, CachedBufTags>>>,
 }
@@ -96,14 +104,32 @@ pub struct SessionContext {
     pub display_winwidth: u64,
     pub preview_winheight: u64,
     pub icon: Icon,
+    pub fuzzy_algo: FuzzyAlgorithm,
+    pub case_matching: CaseMatching,
     pub match_scope: MatchScope,
-    pub match_bonuses: Vec<matcher::Bonus>,
+    pub match_bonuses: Vec<Bonus>,
     pub source_cmd: Option<String>,
     pub runtimepath: Option<String>,
+    /// Whether to save the snapshot of this session on terminate for resuming it later.
+    pub enable_resume: bool,
     pub state: SessionState,
+    /// Client which created this session.
+    pub vim: Vim,
+    /// Environment of the client when this session was created.
+    pub env: Arc<GlobalEnv>,
 }
 
 impl SessionContext {
+    /// Creates a new instance of [`SessionContext`] from the call of client `vim`.
+    pub fn new(call: Call, vim: Vim, env: Arc<GlobalEnv>) -> Result<Self, RpcError> {
+        tracing::debug!(?call, "Creating a new SessionContext from given call");
+        let params = match call {
+            Call::MethodCall(method_call) => method_call.params,
+            Call::Notification(notification) => notification.params,
+        };
+        Self::from_params(params, vim, env)
+    }
+
     /// Executes the command `cmd` and returns the raw bytes of stdout.
     pub fn execute(&self, cmd: &str) -> Result<Vec<u8>> {
         let out = utility::execute_at(cmd, Some(&self.cwd))?;
@@ -113,17 +139,19 @@ impl SessionContext {
     /// Size for fulfilling the preview window.
     pub fn sensible_preview_size(&self) -> usize {
         std::cmp::max(
-            self.provider_id.get_preview_size(),
+            self.env.preview_size_of(self.provider_id.as_str()),
             (self.preview_winheight / 2) as usize,
         )
     }
 
+    /// Returns the matcher configured for this session, shared by all the on_typed paths.
     pub fn fuzzy_matcher(&self) -> matcher::Matcher {
         matcher::Matcher::with_bonuses(
-            Vec::new(), // TODO: bonuses
-            matcher::FuzzyAlgorithm::Fzy,
+            self.match_bonuses.clone(),
+            self.fuzzy_algo,
             self.match_scope,
         )
+        .set_case_matching(self.case_matching)
     }
 
     pub fn set_source_scale(&self, new: SourceScale) {
@@ -131,7 +159,7 @@ impl SessionContext {
         *source_scale = new;
     }
 
-    fn from_params(params: Params) -> Self {
+    fn from_params(params: Params, vim: Vim, env: Arc<GlobalEnv>) -> Result<Self, RpcError> {
         #[derive(Deserialize)]
         struct InnerParams {
             provider_id: ProviderId,
@@ -144,6 +172,11 @@ impl SessionContext {
             source_cmd: Option<String>,
             runtimepath: Option<String>,
             enable_icon: Option<bool>,
+            fuzzy_algo: Option<String>,
+            case_matching: Option<String>,
+            match_scope: Option<String>,
+            match_bonuses: Option<Vec<String>>,
+            enable_resume: Option<bool>,
         }
 
         let InnerParams {
@@ -157,15 +190,23 @@ impl SessionContext {
             source_cmd,
             runtimepath,
             enable_icon,
+            fuzzy_algo,
+            case_matching,
+            match_scope,
+            match_bonuses,
+            enable_resume,
         } = params
             .parse()
-            .expect("Failed to deserialize SessionContext");
+            .map_err(|e| RpcError::InvalidParams(e.message))?;
 
-        let match_scope = match provider_id.as_str() {
-            "tags" | "proj_tags" => MatchScope::TagName,
-            "grep" | "grep2" => MatchScope::GrepLine,
-            _ => MatchScope::Full,
-        };
+        let match_scope =
+            match_scope
+                .map(Into::into)
+                .unwrap_or_else(|| match provider_id.as_str() {
+                    "tags" | "proj_tags" => MatchScope::TagName,
+                    "grep" | "grep2" => MatchScope::GrepLine,
+                    _ => MatchScope::Full,
+                });
 
         let icon = if enable_icon.unwrap_or(false) {
             match provider_id.as_str() {
@@ -179,12 +220,22 @@ impl SessionContext {
             Icon::Null
         };
 
-        let match_bonuses = match provider_id.as_str() {
-            "files" | "git_files" | "filer" => vec![matcher::Bonus::FileName],
-            _ => vec![],
+        let match_bonuses = match match_bonuses {
+            Some(bonuses) => bonuses
+                .iter()
+                .map(Into::into)
+                .filter(|bonus| !matches!(bonus, Bonus::None))
+                .collect(),
+            None => match provider_id.as_str() {
+                "files" | "git_files" => vec![Bonus::FileName, frecency_bonus(&cwd)],
+                "grep" | "grep2" => vec![frecency_bonus(&cwd)],
+                "filer" => vec![Bonus::FileName],
+                "recent_files" => vec![Bonus::cwd(cwd.to_string_lossy().into()), Bonus::FileName],
+                _ => vec![],
+            },
         };
 
-        Self {
+        Ok(Self {
             provider_id,
             cwd,
             no_cache,
@@ -194,36 +245,26 @@ impl SessionContext {
             preview_winheight: preview_winheight.unwrap_or(DEFAULT_PREVIEW_WINHEIGHT),
             source_cmd,
             runtimepath,
+            enable_resume: enable_resume.unwrap_or(false),
+            fuzzy_algo: fuzzy_algo.map(Into::into).unwrap_or_default(),
+            case_matching: case_matching.map(Into::into).unwrap_or_default(),
             match_scope,
             match_bonuses,
             icon,
             state: SessionState {
                 is_running: Arc::new(true.into()),
+                cancellation_token: CancellationToken::new(),
                 source_scale: Arc::new(Mutex::new(SourceScale::Indefinite)),
                 buf_tags_cache: Arc::new(Mutex::new(HashMap::new())),
             },
-        }
-    }
-}
-
-impl From<MethodCall> for SessionContext {
-    fn from(method_call: MethodCall) -> Self {
-        Self::from_params(method_call.params)
+            vim,
+            env,
+        })
     }
 }
 
-impl From<Notification> for SessionContext {
-    fn from(notification: Notification) -> Self {
-        Self::from_params(notification.params)
-    }
-}
-
-impl From<Call> for SessionContext {
-    fn from(call: Call) -> Self {
-        tracing::debug!(?call, "Creating a new SessionContext from given call");
-        match call {
-            Call::MethodCall(method_call) => method_call.into(),
-            Call::Notification(notification) => notification.into(),
-        }
-    }
+/// Returns the bonus based on the frecent scores of the recent files under `cwd`.
+fn frecency_bonus(cwd: &Path) -> Bonus {
+    let scores = RECENT_FILES_IN_MEMORY.lock().frecent_scores_in(cwd);
+    Bonus::Frecency(scores.into())
 }
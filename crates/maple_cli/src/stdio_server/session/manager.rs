@@ -1,10 +1,13 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use crossbeam_channel::Sender;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::stdio_server::{
-    rpc::Call,
-    session::{Session, SessionId},
+    rpc::{Call, RpcError},
+    session::{ExitState, Session, SessionId},
+    types::GlobalEnv,
+    vim::Vim,
     SessionEvent,
 };
 
@@ -13,12 +16,12 @@ use super::EventHandle;
 /// A small wrapper of Sender<SessionEvent> for logging on sending error.
 #[derive(Debug)]
 pub struct SessionEventSender {
-    pub sender: Sender<SessionEvent>,
+    pub sender: UnboundedSender<SessionEvent>,
     pub id: SessionId,
 }
 
 impl SessionEventSender {
-    pub fn new(sender: Sender<SessionEvent>, id: SessionId) -> Self {
+    pub fn new(sender: UnboundedSender<SessionEvent>, id: SessionId) -> Self {
         Self { sender, id }
     }
 }
@@ -38,19 +41,35 @@ impl SessionEventSender {
 }
 
 /// This structs manages all the created sessions tracked by the session id.
-#[derive(Debug, Default)]
+///
+/// Each connected client has its own manager, the session ids are only unique per client.
+#[derive(Debug)]
 pub struct SessionManager {
     sessions: HashMap<SessionId, SessionEventSender>,
+    vim: Vim,
 }
 
 impl SessionManager {
+    pub fn new(vim: Vim) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            vim,
+        }
+    }
+
     /// Starts a session in a background task.
-    pub fn new_session(&mut self, call: Call, session_event_handle: impl EventHandle) {
+    pub fn new_session(
+        &mut self,
+        call: Call,
+        env: Arc<GlobalEnv>,
+        session_event_handle: impl EventHandle,
+    ) -> Result<(), RpcError> {
         let session_id = call.session_id();
         if self.exists(session_id) {
             tracing::error!(session_id, "Skipped as given session already exists");
         } else {
-            let (session, session_sender) = Session::new(call.clone(), session_event_handle);
+            let (session, session_sender) =
+                Session::new(call.clone(), session_event_handle, self.vim.clone(), env)?;
             session.start_event_loop();
 
             session_sender
@@ -62,6 +81,7 @@ impl SessionManager {
                 SessionEventSender::new(session_sender, session_id),
             );
         }
+        Ok(())
     }
 
     /// Returns true if the session exists given `session_id`.
@@ -70,9 +90,16 @@ impl SessionManager {
     }
 
     /// Stop the session task by sending [`SessionEvent::Terminate`].
-    pub fn terminate(&mut self, session_id: SessionId) {
+    pub fn terminate(&mut self, session_id: SessionId, exit_state: ExitState) {
         if let Some(sender) = self.sessions.remove(&session_id) {
-            sender.send(SessionEvent::Terminate);
+            sender.send(SessionEvent::Terminate(exit_state));
+        }
+    }
+
+    /// Stops all the sessions, used when the client is disconnected.
+    pub fn terminate_all(&mut self) {
+        for (_, sender) in self.sessions.drain() {
+            sender.send(SessionEvent::Terminate(ExitState::default()));
         }
     }
 
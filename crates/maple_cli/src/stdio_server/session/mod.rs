@@ -7,13 +7,21 @@ use std::sync::{atomic::Ordering, Arc};
 use std::time::Duration;
 
 use anyhow::Result;
-use crossbeam_channel::Sender;
 use futures::Future;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
+use crate::metrics::{Outcome, Stage, StageTimer};
 use crate::stdio_server::providers::builtin::on_session_create;
-use crate::stdio_server::{rpc::Call, types::ProviderId, MethodCall};
+use crate::stdio_server::{
+    rpc::{Call, RpcError},
+    types::{GlobalEnv, ProviderId},
+    vim::Vim,
+    MethodCall,
+};
 
 pub use self::context::{SessionContext, SourceScale};
 pub use self::manager::SessionManager;
@@ -33,6 +41,16 @@ pub fn spawn_singleton_job(
     }
 }
 
+/// Same as [`spawn_singleton_job`] but runs the blocking `task` on the blocking thread pool.
+pub fn spawn_singleton_blocking_job(task: impl FnOnce() + Send + 'static, job_id: u64) {
+    if register_job_successfully(job_id) {
+        tokio::task::spawn_blocking(move || {
+            task();
+            note_job_is_finished(job_id)
+        });
+    }
+}
+
 pub fn register_job_successfully(job_id: u64) -> bool {
     let mut background_jobs = BACKGROUND_JOBS.lock();
     if background_jobs.contains(&job_id) {
@@ -50,15 +68,27 @@ pub fn note_job_is_finished(job_id: u64) {
 
 pub type SessionId = u64;
 
-fn process_source_scale(source_scale: SourceScale, context: Arc<SessionContext>) {
+/// Sends the total and initial lines of `source_scale` to Vim and sets it as the source of session.
+pub fn process_source_scale(source_scale: SourceScale, context: Arc<SessionContext>) {
     if let Some(total) = source_scale.total() {
-        let method = "s:set_total_size";
-        utility::println_json_with_length!(total, method);
+        context
+            .vim
+            .write_response(json!({ "total": total, "method": "s:set_total_size" }));
     }
 
     if let Some(lines) = source_scale.initial_lines(100) {
-        printer::decorate_lines::<i64>(lines, context.display_winwidth as usize, context.icon)
-            .print_on_session_create();
+        let printer::DecoratedLines {
+            lines,
+            truncated_map,
+            icon_added,
+            ..
+        } = printer::decorate_lines::<i64>(lines, context.display_winwidth as usize, context.icon);
+        context.vim.write_response(json!({
+            "lines": lines,
+            "truncated_map": truncated_map,
+            "icon_added": icon_added,
+            "method": "s:init_display",
+        }));
     }
 
     context.set_source_scale(source_scale);
@@ -66,25 +96,43 @@ fn process_source_scale(source_scale: SourceScale, context: Arc<SessionContext>)
 
 #[async_trait::async_trait]
 pub trait EventHandle: Send + Sync + 'static {
-    async fn on_create(&mut self, _call: Call, context: Arc<SessionContext>) {
+    async fn on_create(&mut self, call: Call, context: Arc<SessionContext>) {
         const TIMEOUT: Duration = Duration::from_millis(300);
 
+        let timer = StageTimer::start(context.provider_id.as_str(), Stage::Source);
+
         // TODO: blocking on_create for the swift providers like `tags`.
         match tokio::time::timeout(TIMEOUT, on_session_create(context.clone())).await {
             Ok(scale_result) => match scale_result {
-                Ok(scale) => process_source_scale(scale, context),
-                Err(e) => tracing::error!(?e, "Error occurred on creating session"),
+                Ok(Some(scale)) => {
+                    timer.finish(scale.total().unwrap_or_default());
+                    process_source_scale(scale, context)
+                }
+                // The source is collected by a background job, which records its own timing.
+                Ok(None) => {}
+                Err(e) => {
+                    timer.finish_with_outcome(0, Outcome::Error);
+                    tracing::error!(?e, "Error occurred on creating session");
+                    if let Call::MethodCall(method_call) = call {
+                        context.vim.write_failure(method_call.id, e);
+                    }
+                }
             },
             Err(_) => {
+                timer.finish_with_outcome(0, Outcome::Timeout);
                 tracing::debug!(timeout = ?TIMEOUT, "Did not receive value in time");
                 match context.provider_id.as_str() {
                     "grep" | "grep2" => {
-                        let rg_cmd =
-                            crate::command::grep::RgBaseCommand::new(context.cwd.to_path_buf());
+                        let backend = context.env.grep_backend;
+                        let rg_cmd = crate::command::grep::RgBaseCommand::new(
+                            context.cwd.to_path_buf(),
+                            backend,
+                        );
                         let job_id = utility::calculate_hash(&rg_cmd.inner);
-                        spawn_singleton_job(
-                            async move {
-                                let _ = rg_cmd.create_cache().await;
+                        spawn_singleton_blocking_job(
+                            move || {
+                                let _ =
+                                    crate::command::grep::refresh_cache(&rg_cmd.inner.cwd, backend);
                             },
                             job_id,
                         );
@@ -98,23 +146,56 @@ pub trait EventHandle: Send + Sync + 'static {
     async fn on_move(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()>;
 
     async fn on_typed(&mut self, msg: MethodCall, context: Arc<SessionContext>) -> Result<()>;
+
+    /// Sends a page of the current results, only the providers retaining the results support it.
+    async fn on_page(&mut self, msg: MethodCall, _context: Arc<SessionContext>) -> Result<()> {
+        Err(RpcError::UnknownMethod(msg.method).into())
+    }
+
+    /// Sends the locations of the selected lines, only the providers whose lines are addressable
+    /// support it.
+    async fn on_selection(&mut self, msg: MethodCall, _context: Arc<SessionContext>) -> Result<()> {
+        Err(RpcError::UnknownMethod(msg.method).into())
+    }
+
+    /// Restores the query and results saved when the last session of the same provider in the
+    /// same cwd was terminated.
+    async fn on_resume(&mut self, msg: MethodCall, _context: Arc<SessionContext>) -> Result<()> {
+        Err(RpcError::UnknownMethod(msg.method).into())
+    }
+
+    /// Invoked before the session is terminated.
+    async fn on_terminate(&mut self, _context: Arc<SessionContext>, _exit_state: ExitState) {}
 }
 
-#[derive(Debug, Clone)]
+/// Final state of the session sent by Vim on exit.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExitState {
+    /// Query in the input window, which may have never been sent to the session as the results of
+    /// some providers are filtered by the external maple jobs, e.g., grep2.
+    pub query: Option<String>,
+    /// Line number of the cursor in the display window.
+    pub lnum: Option<usize>,
+}
+
+#[derive(Debug)]
 pub struct Session<T> {
     pub session_id: u64,
     pub context: Arc<SessionContext>,
     /// Each Session can have its own message processing logic.
     pub event_handler: T,
-    pub event_recv: crossbeam_channel::Receiver<SessionEvent>,
+    pub event_recv: UnboundedReceiver<SessionEvent>,
 }
 
 #[derive(Debug, Clone)]
 pub enum SessionEvent {
     OnTyped(MethodCall),
     OnMove(MethodCall),
+    OnPage(MethodCall),
+    OnSelection(MethodCall),
+    OnResume(MethodCall),
     Create(Call),
-    Terminate,
+    Terminate(ExitState),
 }
 
 impl SessionEvent {
@@ -123,29 +204,42 @@ impl SessionEvent {
         match self {
             Self::OnTyped(msg) => format!("OnTyped, msg_id: {}", msg.id).into(),
             Self::OnMove(msg) => format!("OnMove, msg_id: {}", msg.id).into(),
+            Self::OnPage(msg) => format!("OnPage, msg_id: {}", msg.id).into(),
+            Self::OnSelection(msg) => format!("OnSelection, msg_id: {}", msg.id).into(),
+            Self::OnResume(msg) => format!("OnResume, msg_id: {}", msg.id).into(),
             Self::Create(_) => "Create".into(),
-            Self::Terminate => "Terminate".into(),
+            Self::Terminate(_) => "Terminate".into(),
         }
     }
 }
 
 impl<T: EventHandle> Session<T> {
-    pub fn new(call: Call, event_handler: T) -> (Self, Sender<SessionEvent>) {
-        let (session_sender, session_receiver) = crossbeam_channel::unbounded();
+    pub fn new(
+        call: Call,
+        event_handler: T,
+        vim: Vim,
+        env: Arc<GlobalEnv>,
+    ) -> Result<(Self, UnboundedSender<SessionEvent>), RpcError> {
+        let (session_sender, session_receiver) = tokio::sync::mpsc::unbounded_channel();
 
         let session = Session {
             session_id: call.session_id(),
-            context: Arc::new(call.into()),
+            context: Arc::new(SessionContext::new(call, vim, env)?),
             event_handler,
             event_recv: session_receiver,
         };
 
-        (session, session_sender)
+        Ok((session, session_sender))
     }
 
-    /// Sets the running signal to false, in case of the forerunner thread is still working.
-    pub fn handle_terminate(&mut self) {
+    /// Sets the running signal to false and cancels the background jobs of this session, in case
+    /// of the forerunner thread is still working.
+    pub async fn handle_terminate(&mut self, exit_state: ExitState) {
         self.context.state.is_running.store(false, Ordering::SeqCst);
+        self.context.state.cancellation_token.cancel();
+        self.event_handler
+            .on_terminate(self.context.clone(), exit_state)
+            .await;
         tracing::debug!(
             session_id = self.session_id,
             provider_id = %self.provider_id(),
@@ -169,23 +263,56 @@ impl<T: EventHandle> Session<T> {
 
     async fn process_event(&mut self, event: SessionEvent) -> Result<()> {
         match event {
-            SessionEvent::Terminate => self.handle_terminate(),
+            SessionEvent::Terminate(exit_state) => self.handle_terminate(exit_state).await,
             SessionEvent::Create(call) => {
                 self.event_handler
                     .on_create(call, self.context.clone())
                     .await
             }
             SessionEvent::OnMove(msg) => {
-                self.event_handler
-                    .on_move(msg, self.context.clone())
-                    .await?;
+                let msg_id = msg.id;
+                if let Err(err) = self.event_handler.on_move(msg, self.context.clone()).await {
+                    tracing::error!(?err, "Error processing SessionEvent::OnMove");
+                    self.context.vim.write_failure(msg_id, err);
+                }
+            }
+            SessionEvent::OnPage(msg) => {
+                let msg_id = msg.id;
+                if let Err(err) = self.event_handler.on_page(msg, self.context.clone()).await {
+                    tracing::error!(?err, "Error processing SessionEvent::OnPage");
+                    self.context.vim.write_failure(msg_id, err);
+                }
+            }
+            SessionEvent::OnSelection(msg) => {
+                let msg_id = msg.id;
+                if let Err(err) = self
+                    .event_handler
+                    .on_selection(msg, self.context.clone())
+                    .await
+                {
+                    tracing::error!(?err, "Error processing SessionEvent::OnSelection");
+                    self.context.vim.write_failure(msg_id, err);
+                }
+            }
+            SessionEvent::OnResume(msg) => {
+                let msg_id = msg.id;
+                if let Err(err) = self
+                    .event_handler
+                    .on_resume(msg, self.context.clone())
+                    .await
+                {
+                    tracing::error!(?err, "Error processing SessionEvent::OnResume");
+                    self.context.vim.write_failure(msg_id, err);
+                }
             }
             SessionEvent::OnTyped(msg) => {
                 // TODO: use a buffered channel here, do not process on every
                 // single char change.
-                self.event_handler
-                    .on_typed(msg, self.context.clone())
-                    .await?;
+                let msg_id = msg.id;
+                if let Err(err) = self.event_handler.on_typed(msg, self.context.clone()).await {
+                    tracing::error!(?err, "Error processing SessionEvent::OnTyped");
+                    self.context.vim.write_failure(msg_id, err);
+                }
             }
         }
         Ok(())
@@ -193,15 +320,15 @@ impl<T: EventHandle> Session<T> {
 
     async fn run_event_loop_without_debounce(mut self) {
         loop {
-            match self.event_recv.recv() {
-                Ok(event) => {
+            match self.event_recv.recv().await {
+                Some(event) => {
                     tracing::debug!(event = ?event.short_display(), "Received an event");
                     if let Err(err) = self.process_event(event).await {
                         tracing::debug!(?err, "Error processing SessionEvent");
                     }
                 }
-                Err(err) => {
-                    tracing::debug!(?err, "The channel is possibly broken");
+                None => {
+                    tracing::debug!("The channel is possibly broken");
                     break;
                 }
             }
@@ -209,8 +336,6 @@ impl<T: EventHandle> Session<T> {
     }
 
     async fn run_event_loop_with_debounce(mut self) {
-        use crossbeam_channel::select;
-
         // https://github.com/denoland/deno/blob/1fb5858009f598ce3f917f9f49c466db81f4d9b0/cli/lsp/diagnostics.rs#L141
         //
         // Debounce timer delay. 150ms between keystrokes is about 45 WPM, so we
@@ -219,9 +344,6 @@ impl<T: EventHandle> Session<T> {
         //
         // Add extra 50ms delay.
         const DELAY: Duration = Duration::from_millis(200 + 50);
-        // If the debounce timer isn't active, it will be set to expire "never",
-        // which is actually just 1 year in the future.
-        const NEVER: Duration = Duration::from_secs(365 * 24 * 60 * 60);
 
         tracing::debug!(
             session_id = self.session_id,
@@ -229,46 +351,38 @@ impl<T: EventHandle> Session<T> {
             "Spawning a new session task",
         );
 
-        let mut pending_on_typed = None;
-        let mut debounce_timer = NEVER;
+        let mut pending_on_typed: Option<MethodCall> = None;
+        let debounce_timer = tokio::time::sleep(DELAY);
+        tokio::pin!(debounce_timer);
 
         loop {
-            select! {
-              recv(self.event_recv) -> maybe_event => {
+            tokio::select! {
+              maybe_event = self.event_recv.recv() => {
                   match maybe_event {
-                      Ok(event) => {
+                      Some(SessionEvent::OnTyped(msg)) => {
+                          tracing::debug!(msg_id = msg.id, "Received an OnTyped event");
+                          pending_on_typed.replace(msg);
+                          debounce_timer
+                              .as_mut()
+                              .reset(tokio::time::Instant::now() + DELAY);
+                      }
+                      Some(event) => {
                           tracing::debug!(event = ?event.short_display(), "Received an event");
-                          match event {
-                              SessionEvent::Terminate => self.handle_terminate(),
-                              SessionEvent::Create(call) => {
-                                  self.event_handler
-                                      .on_create(call, self.context.clone())
-                                      .await
-                              }
-                              SessionEvent::OnMove(msg) => {
-                                  if let Err(err) =
-                                      self.event_handler.on_move(msg, self.context.clone()).await
-                                  {
-                                      tracing::error!(?err, "Error processing SessionEvent::OnMove");
-                                  }
-                              }
-                              SessionEvent::OnTyped(msg) => {
-                                  pending_on_typed.replace(msg);
-                                  debounce_timer = DELAY;
-                              }
+                          if let Err(err) = self.process_event(event).await {
+                              tracing::debug!(?err, "Error processing SessionEvent");
                           }
                       }
-                      Err(err) => {
-                          tracing::debug!(?err, "The channel is possibly broken");
+                      None => {
+                          tracing::debug!("The channel is possibly broken");
                           return;
                       }
                   }
               }
-              default(debounce_timer) => {
-                  debounce_timer = NEVER;
+              // The timer is only polled while an OnTyped is pending.
+              _ = &mut debounce_timer, if pending_on_typed.is_some() => {
                   if let Some(msg) = pending_on_typed.take() {
-                      if let Err(err) = self.event_handler.on_typed(msg, self.context.clone()).await {
-                          tracing::error!(?err, "Error processing SessionEvent::OnTyped");
+                      if let Err(err) = self.process_event(SessionEvent::OnTyped(msg)).await {
+                          tracing::debug!(?err, "Error processing SessionEvent");
                       }
                   }
               }
@@ -3,13 +3,15 @@ use std::sync::Arc;
 use anyhow::Result;
 use crossbeam_channel::Receiver;
 use parking_lot::Mutex;
-use serde_json::{json, Value};
+use serde_json::Value;
 
-use crate::stdio_server::providers::dumb_jump::DumbJumpHandle;
-use crate::stdio_server::rpc::{Call, MethodCall};
+use crate::stdio_server::providers::{
+    dumb_jump::DumbJumpHandle, filer::FilerHandle, recent_files::RecentFilesHandle, BuiltinHandle,
+};
+use crate::stdio_server::rpc::{Call, MethodCall, RpcError};
 use crate::stdio_server::state::State;
 
-use super::session::SessionManager;
+use super::session::{EventHandle, ExitState, SessionManager};
 
 #[derive(Clone)]
 pub struct SessionClient {
@@ -20,22 +22,109 @@ pub struct SessionClient {
 impl SessionClient {
     /// Creates a new instnace of [`SessionClient`].
     pub fn new(state: State) -> Self {
+        let session_manager = SessionManager::new(state.vim.clone());
         Self {
             state_mutex: Arc::new(Mutex::new(state)),
-            session_manager_mutex: Arc::new(Mutex::new(SessionManager::default())),
+            session_manager_mutex: Arc::new(Mutex::new(session_manager)),
         }
     }
 
     /// Entry of the bridge between Vim and Rust.
+    ///
+    /// The messages routed to the [`SessionManager`] are handled one at a time in order, e.g.,
+    /// `on_typed` can only be dispatched after the session has been created by `on_init`, the
+    /// other requests are independent and processed concurrently.
     pub fn loop_call(&self, rx: &Receiver<Call>) {
         for call in rx.iter() {
-            let session_client = self.clone();
-            tokio::spawn(async move {
-                if let Err(e) = session_client.handle_vim_message(call).await {
-                    tracing::error!(?e, "Error handling request");
+            if let Some(call) = self.process_session_call(call) {
+                let session_client = self.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = session_client.handle_vim_message(call).await {
+                        tracing::error!(?e, "Error handling request");
+                    }
+                });
+            }
+        }
+    }
+
+    /// Dispatches the session lifecycle message to the [`SessionManager`].
+    ///
+    /// Returns the call back if it's not a session message.
+    fn process_session_call(&self, call: Call) -> Option<Call> {
+        let msg = match call {
+            Call::Notification(notification) => {
+                match notification.method.as_str() {
+                    "exit" => {
+                        let session_id = notification.session_id;
+                        let exit_state = notification.parse().unwrap_or_else(|e| {
+                            tracing::error!(?e, "Invalid exit state");
+                            ExitState::default()
+                        });
+                        let mut session_manager = self.session_manager_mutex.lock();
+                        session_manager.terminate(session_id, exit_state);
+                    }
+                    "on_init" => {
+                        if let Err(e) = self
+                            .create_session(Call::Notification(notification), BuiltinHandle::new())
+                        {
+                            tracing::error!(?e, "Failed to create a new session");
+                        }
+                    }
+                    // Must be handled before creating the sessions which read it.
+                    "initialize_global_env" => match notification.parse_global_env() {
+                        Ok(env) => {
+                            tracing::debug!(?env, "Client environment initialized");
+                            self.state_mutex.lock().env = Arc::new(env);
+                        }
+                        Err(e) => {
+                            tracing::error!(?e, "Failed to initialize the client environment")
+                        }
+                    },
+                    _ => return Some(Call::Notification(notification)),
                 }
-            });
+                return None;
+            }
+            Call::MethodCall(method_call) => method_call,
+        };
+
+        if msg.method != "init_ext_map" {
+            tracing::debug!(?msg, "==> stdio message(in)");
+        }
+
+        match msg.method.as_str() {
+            "dumb_jump/on_init" => self.new_session(msg, DumbJumpHandle::default()),
+            "recent_files/on_init" => self.new_session(msg, RecentFilesHandle::default()),
+            "filer/on_init" => self.new_session(msg, FilerHandle),
+            "on_init" => self.new_session(msg, BuiltinHandle::new()),
+            _ => return self.send_session_event(msg),
         }
+
+        None
+    }
+
+    /// Sends the event of an existing session, returns the call back if it's not a session event.
+    fn send_session_event(&self, msg: MethodCall) -> Option<Call> {
+        use super::SessionEvent::*;
+
+        let session_id = msg.session_id;
+
+        let event = match msg.method.as_str() {
+            "dumb_jump/on_typed" | "recent_files/on_typed" | "filer/on_typed" | "on_typed" => {
+                OnTyped(msg)
+            }
+            "dumb_jump/on_move" | "recent_files/on_move" | "filer/on_move" | "on_move" => {
+                OnMove(msg)
+            }
+            "page" => OnPage(msg),
+            "dumb_jump/selection" | "selection" => OnSelection(msg),
+            "resume" => OnResume(msg),
+            _ => return Some(Call::MethodCall(msg)),
+        };
+
+        // The session events are responded by the session itself.
+        self.session_manager_mutex.lock().send(session_id, event);
+
+        None
     }
 
     /// Handle the message actively initiated from Vim.
@@ -48,11 +137,13 @@ impl SessionClient {
             }
             Call::MethodCall(method_call) => {
                 let id = method_call.id;
-                let maybe_result = self.process_method_call(method_call).await?;
+                let maybe_result = self.process_method_call(method_call).await;
+                let rpc_client = self.state_mutex.lock().vim.rpc_client.clone();
                 // Send back the result of method call.
-                if let Some(result) = maybe_result {
-                    let state = self.state_mutex.lock();
-                    state.vim.rpc_client.output(id, Ok(result))?;
+                match maybe_result {
+                    Ok(Some(result)) => rpc_client.output(id, Ok(result))?,
+                    Ok(None) => {}
+                    Err(e) => rpc_client.output(id, Err::<Value, _>(e))?,
                 }
             }
         }
@@ -61,55 +152,159 @@ impl SessionClient {
 
     /// Process the method call message from Vim.
     async fn process_method_call(&self, method_call: MethodCall) -> Result<Option<Value>> {
-        use super::SessionEvent::*;
-
         let msg = method_call;
 
-        if msg.method != "init_ext_map" {
-            tracing::debug!(?msg, "==> stdio message(in)");
-        }
-
         let value = match msg.method.as_str() {
-            "init_ext_map" => Some(msg.parse_filetypedetect()),
+            "init_ext_map" => {
+                let rpc_client = self.state_mutex.lock().vim.rpc_client.clone();
+                rpc_client.notify("clap#ext#set", msg.parse_filetypedetect()?)?;
+                None
+            }
             "preview/file" => Some(msg.preview_file().await?),
             "quickfix" => Some(msg.preview_quickfix().await?),
-
-            "dumb_jump/on_init" => {
-                use crate::stdio_server::rpc::Call;
-                let mut session_manager = self.session_manager_mutex.lock();
-                session_manager.new_session(Call::MethodCall(msg), DumbJumpHandle::default());
-                None
+            "query_history" => Some(msg.query_history()?),
+            "stats" => Some(serde_json::json!({ "stages": crate::metrics::summary() })),
+            "health" => {
+                let report = tokio::task::spawn_blocking(|| {
+                    crate::command::doctor::health_report(crate::logger::log_path())
+                })
+                .await?;
+                Some(serde_json::to_value(report)?)
             }
-            "dumb_jump/on_typed" => {
-                let mut session_manager = self.session_manager_mutex.lock();
-                session_manager.send(msg.session_id, OnTyped(msg));
-                None
+            _ => return Err(RpcError::UnknownMethod(msg.method).into()),
+        };
+
+        Ok(value)
+    }
+
+    /// Creates a new session with the current environment of this client.
+    fn create_session(
+        &self,
+        call: Call,
+        session_event_handle: impl EventHandle,
+    ) -> Result<(), RpcError> {
+        let env = self.state_mutex.lock().env.clone();
+        self.session_manager_mutex
+            .lock()
+            .new_session(call, env, session_event_handle)
+    }
+
+    /// Creates a new session, the error is responded immediately.
+    fn new_session(&self, msg: MethodCall, session_event_handle: impl EventHandle) {
+        let id = msg.id;
+        if let Err(e) = self.create_session(Call::MethodCall(msg), session_event_handle) {
+            let rpc_client = self.state_mutex.lock().vim.rpc_client.clone();
+            if let Err(e) = rpc_client.output(id, Err::<Value, _>(e.into())) {
+                tracing::error!(?e, "Failed to send the error response");
             }
-            "dumb_jump/on_move" => {
-                let mut session_manager = self.session_manager_mutex.lock();
-                session_manager.send(msg.session_id, OnMove(msg));
-                None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::{Duration, Instant};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::stdio_server::rpc::RpcClient;
+
+    /// Writer shared with the test for inspecting the messages sent to Vim.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_session_calls_in_order() {
+        let output = SharedBuffer::default();
+        let (sink, _) = crossbeam_channel::unbounded();
+        let rpc_client = Arc::new(RpcClient::new(std::io::empty(), output.clone(), sink));
+        let session_client = SessionClient::new(State::new(rpc_client));
+
+        let call = |value: Value| -> Call { serde_json::from_value(value).unwrap() };
+
+        // The query is empty so that no query history is written on terminate.
+        let (tx, rx) = crossbeam_channel::unbounded();
+        tx.send(call(json!({
+            "id": 1,
+            "method": "on_init",
+            "session_id": 1,
+            "params": {
+                "provider_id": "test",
+                "cwd": std::env::temp_dir(),
+                "no_cache": false,
+                "debounce": false,
+                "source_fpath": "",
+                "source_cmd": "printf 'alpha\\nbeta\\n'",
+            },
+        })))
+        .unwrap();
+        tx.send(call(json!({
+            "id": 2,
+            "method": "on_typed",
+            "session_id": 1,
+            "params": { "query": "" },
+        })))
+        .unwrap();
+        // Exits right after being created.
+        tx.send(call(json!({
+            "id": 3,
+            "method": "on_init",
+            "session_id": 2,
+            "params": {
+                "provider_id": "test",
+                "cwd": std::env::temp_dir(),
+                "no_cache": false,
+                "debounce": false,
+                "source_fpath": "",
+            },
+        })))
+        .unwrap();
+        tx.send(call(
+            json!({ "method": "exit", "session_id": 2, "params": {} }),
+        ))
+        .unwrap();
+
+        let client = session_client.clone();
+        let handle = tokio::task::spawn_blocking(move || client.loop_call(&rx));
+
+        // `on_typed` is dispatched to the session instead of being dropped.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let output = String::from_utf8_lossy(&output.0.lock()).into_owned();
+            if output.contains("s:process_filter_message") {
+                break;
             }
+            assert!(
+                Instant::now() < deadline,
+                "on_typed is not handled: {}",
+                output
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
 
-            /*
-            "recent_files/on_init" => manager.new_session::<RecentFilesSession>(msg),
-            "recent_files/on_typed" => manager.send(msg.session_id, OnTyped(msg)),
-            "recent_files/on_move" => manager.send(msg.session_id, OnMove(msg)),
-
-            "filer" => filer::handle_filer_message(msg),
-            "filer/on_init" => manager.new_session::<FilerSession>(msg),
-            "filer/on_move" => manager.send(msg.session_id, OnMove(msg)),
-
-            "on_init" => manager.new_session::<BuiltinSession>(msg),
-            "on_typed" => manager.send(msg.session_id, OnTyped(msg)),
-            "on_move" => manager.send(msg.session_id, OnMove(msg)),
-            "exit" => manager.terminate(msg.session_id),
-            */
-            _ => Some(json!({
-                "error": format!("Unknown method call: {}", msg.method)
-            })),
-        };
+        // Sent once the results are received as the matching job is cancelled on exit.
+        tx.send(call(
+            json!({ "method": "exit", "session_id": 1, "params": {} }),
+        ))
+        .unwrap();
+        drop(tx);
+        handle.await.unwrap();
 
-        Ok(value)
+        // `exit` is handled after the session is created, otherwise the session is leaked.
+        assert!(!session_client.session_manager_mutex.lock().exists(1));
+        assert!(!session_client.session_manager_mutex.lock().exists(2));
     }
 }
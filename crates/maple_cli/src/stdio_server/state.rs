@@ -1,29 +1,30 @@
 use std::sync::Arc;
 
-use crossbeam_channel::Sender;
 use serde::Serialize;
 
-use crate::stdio_server::rpc::{Call, RpcClient};
+use crate::stdio_server::rpc::RpcClient;
+use crate::stdio_server::types::GlobalEnv;
 use crate::stdio_server::vim::Vim;
 
 /// Current State of Vim/NeoVim client.
 #[derive(Serialize)]
 pub struct State {
     #[serde(skip_serializing)]
-    pub tx: Sender<Call>,
+    pub vim: Vim,
 
+    /// Environment of this client, shared by the sessions created afterwards.
     #[serde(skip_serializing)]
-    pub vim: Vim,
+    pub env: Arc<GlobalEnv>,
 
     /// Highlight match ids.
     pub highlights: Vec<u32>,
 }
 
 impl State {
-    pub fn new(tx: Sender<Call>, client: Arc<RpcClient>) -> Self {
+    pub fn new(client: Arc<RpcClient>) -> Self {
         Self {
-            tx,
             vim: Vim::new(client),
+            env: Default::default(),
             highlights: Default::default(),
         }
     }
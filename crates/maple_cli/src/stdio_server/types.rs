@@ -3,11 +3,17 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Debug, Clone)]
+use super::rpc::RpcError;
+use crate::command::grep::GrepBackend;
+
+/// Environment of a client, initialized by the `initialize_global_env` notification.
+#[derive(Debug, Clone, Default)]
 pub struct GlobalEnv {
     pub is_nvim: bool,
     pub enable_icon: bool,
     pub preview_config: PreviewConfig,
+    /// Program producing the grep2 caches of this client.
+    pub grep_backend: GrepBackend,
 }
 
 #[derive(Debug, Clone)]
@@ -18,16 +24,29 @@ pub enum PreviewConfig {
 
 const DEFAULT_PREVIEW_SIZE: u64 = 5;
 
-impl From<Value> for PreviewConfig {
-    fn from(v: Value) -> Self {
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self::Number(DEFAULT_PREVIEW_SIZE)
+    }
+}
+
+impl TryFrom<Value> for PreviewConfig {
+    type Error = RpcError;
+
+    fn try_from(v: Value) -> Result<Self, Self::Error> {
         if v.is_object() {
-            let m: HashMap<String, u64> = serde_json::from_value(v)
-                .unwrap_or_else(|e| panic!("Failed to deserialize preview_size map: {:?}", e));
-            return Self::Map(m);
+            let m: HashMap<String, u64> = serde_json::from_value(v).map_err(|e| {
+                RpcError::InvalidParams(format!("Failed to deserialize preview_size map: {}", e))
+            })?;
+            return Ok(Self::Map(m));
         }
         match v {
-            Value::Number(number) => Self::Number(number.as_u64().unwrap_or(DEFAULT_PREVIEW_SIZE)),
-            _ => unreachable!("clap_preview_size has to be either Number or Object"),
+            Value::Number(number) => Ok(Self::Number(
+                number.as_u64().unwrap_or(DEFAULT_PREVIEW_SIZE),
+            )),
+            _ => Err(RpcError::InvalidParams(
+                "clap_preview_size has to be either Number or Object".into(),
+            )),
         }
     }
 }
@@ -46,11 +65,17 @@ impl PreviewConfig {
 }
 
 impl GlobalEnv {
-    pub fn new(is_nvim: bool, enable_icon: bool, preview_config: PreviewConfig) -> Self {
+    pub fn new(
+        is_nvim: bool,
+        enable_icon: bool,
+        preview_config: PreviewConfig,
+        grep_backend: GrepBackend,
+    ) -> Self {
         Self {
             is_nvim,
             enable_icon,
             preview_config,
+            grep_backend,
         }
     }
 
@@ -58,6 +83,13 @@ impl GlobalEnv {
     pub fn preview_size_of(&self, provider_id: &str) -> usize {
         self.preview_config.preview_size(provider_id)
     }
+
+    /// Returns true if the raw line of `provider_id` has been decorated with an icon.
+    ///
+    /// We should skip that icon when hoping to get the origin cursorline content.
+    pub fn should_skip_leading_icon(&self, provider_id: &ProviderId) -> bool {
+        self.enable_icon && provider_id.has_icon_support()
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -70,20 +102,6 @@ impl ProviderId {
         &self.0
     }
 
-    /// Returns true if the raw line has been decorated with an icon.
-    ///
-    /// We should skip that icon when hoping to get the origin cursorline content.
-    #[inline]
-    pub fn should_skip_leading_icon(&self) -> bool {
-        super::global().enable_icon && self.has_icon_support()
-    }
-
-    /// Returns the preview size of current provider.
-    #[inline]
-    pub fn get_preview_size(&self) -> usize {
-        super::global().preview_size_of(&self.0)
-    }
-
     /// Returns true if the provider can have icon.
     #[inline]
     pub fn has_icon_support(&self) -> bool {
@@ -110,6 +128,9 @@ mod tests {
     #[test]
     fn test_preview_config_deserialize() {
         let v: Value = serde_json::json!({"filer": 10, "files": 5});
-        let _config: PreviewConfig = v.into();
+        let _config: PreviewConfig = v.try_into().unwrap();
+
+        let v: Value = serde_json::json!("filer");
+        assert!(PreviewConfig::try_from(v).is_err());
     }
 }
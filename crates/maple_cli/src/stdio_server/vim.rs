@@ -6,10 +6,10 @@ use std::sync::Arc;
 use anyhow::Result;
 use once_cell::sync::{Lazy, OnceCell};
 use rayon::prelude::*;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::json;
 
-use crate::stdio_server::rpc::RpcClient;
+use crate::stdio_server::rpc::{RpcClient, RpcError};
 
 /// Map of file extension to vim syntax mapping.
 static SYNTAX_MAP: OnceCell<HashMap<String, String>> = OnceCell::new();
@@ -53,18 +53,13 @@ pub fn syntax_for(path: &Path) -> Option<&str> {
     match path
         .file_name()
         .and_then(|x| x.to_str())
-        .map(|filename| FILENAME_SYNTAX_MAP.deref().get(filename).copied())
-        .flatten()
+        .and_then(|filename| FILENAME_SYNTAX_MAP.deref().get(filename).copied())
     {
-        None => path
-            .extension()
-            .and_then(|x| x.to_str())
-            .map(|ext| {
-                SYNTAX_MAP
-                    .get()
-                    .and_then(|m| m.get(ext).map(|s| s.as_ref()))
-            })
-            .flatten(),
+        None => path.extension().and_then(|x| x.to_str()).and_then(|ext| {
+            SYNTAX_MAP
+                .get()
+                .and_then(|m| m.get(ext).map(|s| s.as_ref()))
+        }),
         Some(s) => Some(s),
     }
 }
@@ -80,7 +75,7 @@ pub fn initialize_syntax_map(output: &str) -> HashMap<&str, &str> {
                 None
             } else {
                 // (mkiv, context)
-                items[0].split('.').last().map(|ext| (ext, items[2]))
+                items[0].split('.').next_back().map(|ext| (ext, items[2]))
             }
         })
         .chain(
@@ -134,7 +129,8 @@ pub fn initialize_syntax_map(output: &str) -> HashMap<&str, &str> {
     ext_map
 }
 
-#[derive(Clone)]
+/// Handle of the connected Vim/NeoVim client.
+#[derive(Debug, Clone)]
 pub struct Vim {
     pub rpc_client: Arc<RpcClient>,
 }
@@ -147,4 +143,16 @@ impl Vim {
     pub fn getbufvar<R: DeserializeOwned>(&self, bufname: &str, var: &str) -> Result<R> {
         self.rpc_client.call("getbufvar", json!([bufname, var]))
     }
+
+    /// Writes the response to this client.
+    pub fn write_response<T: Serialize>(&self, msg: T) {
+        if let Err(error) = self.rpc_client.write(msg) {
+            tracing::error!(?error, "Failed to write the response");
+        }
+    }
+
+    /// Writes the failure of method call `id` to this client.
+    pub fn write_failure(&self, id: u64, error: impl Into<RpcError>) {
+        self.write_response(error.into().into_failure(id));
+    }
 }
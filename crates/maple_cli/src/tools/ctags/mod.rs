@@ -1,16 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
-use std::io::{BufRead, BufReader};
+use std::io::BufReader;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
+use filter::matcher::MatchScope;
 use filter::subprocess::{Exec, NullFile};
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::cache::Fingerprint;
 use crate::paths::AbsPathBuf;
 use crate::process::{rstd::StdCommand, BaseCommand};
 use crate::utils::PROJECT_DIRS;
@@ -39,8 +41,17 @@ pub static CTAGS_HAS_JSON_FEATURE: Lazy<bool> =
     Lazy::new(|| detect_json_feature().unwrap_or(false));
 
 /// Used to specify the language when working with `readtags`.
-pub static LANG_MAPS: Lazy<HashMap<String, String>> =
-    Lazy::new(|| generate_lang_maps().expect("Failed to process the output of `--list-maps`"));
+///
+/// Empty if the ctags executable is unavailable, run `maple doctor` for the details.
+pub static LANG_MAPS: Lazy<HashMap<String, String>> = Lazy::new(|| {
+    generate_lang_maps().unwrap_or_else(|error| {
+        tracing::error!(
+            ?error,
+            "Failed to process the output of `ctags --list-maps`"
+        );
+        HashMap::new()
+    })
+});
 
 pub fn get_language(extension: &str) -> Option<&str> {
     LANG_MAPS.get(extension).map(AsRef::as_ref)
@@ -142,7 +153,7 @@ impl<'a, P: AsRef<Path> + Hash> TagsConfig<'a, P> {
 }
 
 /// Unit type wrapper of [`BaseCommand`] for ctags.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 pub struct CtagsCommand {
     inner: BaseCommand,
 }
@@ -187,9 +198,9 @@ impl CtagsCommand {
 
     /// Returns an iterator of raw line of ctags output.
     fn run(&self) -> Result<impl Iterator<Item = String>> {
-        Ok(BufReader::new(self.inner.stream_stdout()?)
-            .lines()
-            .flatten())
+        Ok(utility::lossy_lines(BufReader::new(
+            self.inner.stream_stdout()?,
+        )))
     }
 
     /// Returns an iterator of tag line in a formatted form.
@@ -203,42 +214,129 @@ impl CtagsCommand {
         }))
     }
 
+    /// Returns the fingerprint of the cwd, see [`BaseCommand::fingerprint`].
+    pub fn fingerprint(&self) -> Option<Fingerprint> {
+        self.inner.fingerprint()
+    }
+
     /// Returns a tuple of (total, cache_path) if the cache exists.
     pub fn ctags_cache(&self) -> Option<(usize, PathBuf)> {
         self.inner.cache_info()
     }
 
+    /// Returns a tuple of (total, cache_path) even if the cache is outdated.
+    pub fn outdated_ctags_cache(&self) -> Option<(usize, PathBuf)> {
+        self.inner.outdated_cache_info()
+    }
+
     /// Runs the command and writes the cache to the disk.
     pub fn create_cache(&self) -> Result<(usize, PathBuf)> {
+        let fingerprint = self.inner.fingerprint();
         let mut total = 0usize;
-        let mut formatted_tags_iter = self.formatted_tags_iter()?.map(|x| {
+        let mut formatted_tags_iter = self.formatted_tags_iter()?.inspect(|_| {
             total += 1;
-            x
         });
         let lines = formatted_tags_iter.join("\n");
 
-        let cache_path = self.inner.clone().create_cache(total, lines.as_bytes())?;
+        let cache_path = self.inner.clone().create_indexed_cache(
+            fingerprint,
+            total,
+            lines.as_bytes(),
+            MatchScope::TagName,
+        )?;
 
         Ok((total, cache_path))
     }
 
     /// Parallel version of [`create_cache`].
     pub fn par_create_cache(&self) -> Result<(usize, PathBuf)> {
+        let fingerprint = self.inner.fingerprint();
         let lines = self.par_formatted_lines()?;
         let total = lines.len();
         let lines = lines.into_iter().join("\n");
 
-        let cache_path = self.inner.clone().create_cache(total, lines.as_bytes())?;
+        let cache_path = self.inner.clone().create_indexed_cache(
+            fingerprint,
+            total,
+            lines.as_bytes(),
+            MatchScope::TagName,
+        )?;
 
         Ok((total, cache_path))
     }
 
-    pub async fn create_cache_async(self, lines: Vec<String>) -> Result<()> {
+    /// Writes the cache of `lines`, `fingerprint` must be captured before generating them, see
+    /// [`BaseCommand::fingerprint`].
+    pub async fn create_cache_async(
+        self,
+        fingerprint: Option<Fingerprint>,
+        lines: Vec<String>,
+    ) -> Result<()> {
         let total = lines.len();
         let lines = lines.into_iter().join("\n");
-        self.inner.create_cache(total, lines.as_bytes())?;
+        self.inner.create_indexed_cache(
+            fingerprint,
+            total,
+            lines.as_bytes(),
+            MatchScope::TagName,
+        )?;
         Ok(())
     }
+
+    /// Updates the cache in place given the `changed` files relative to the cwd, only the tags of
+    /// the files in `existing` are regenerated.
+    ///
+    /// Returns the new total, `None` if there is no cache.
+    pub fn update_cache(
+        &self,
+        changed: &HashSet<String>,
+        existing: &[String],
+    ) -> Result<Option<usize>> {
+        let fingerprint = self.inner.fingerprint();
+        let (old_total, cache_path) = match self.inner.outdated_cache_info() {
+            Some(cache) => cache,
+            None => return Ok(None),
+        };
+
+        let mut lines = utility::lz4::read_to_string(cache_path)?
+            .par_lines()
+            .filter(|line| {
+                !matches!(
+                    pattern::extract_proj_tags(line),
+                    Some((_, path)) if changed.contains(path)
+                )
+            })
+            .map(Into::into)
+            .collect::<Vec<String>>();
+
+        if lines.len() == old_total && existing.is_empty() {
+            return Ok(Some(old_total));
+        }
+
+        if !existing.is_empty() {
+            // `-L -` reads the files to parse from stdin.
+            let output = Exec::shell(format!("{} -L -", self.inner.command))
+                .cwd(&self.inner.cwd)
+                .stdin(existing.join("\n").as_str())
+                .stderr(NullFile)
+                .capture()?;
+            lines.extend(output.stdout_str().lines().filter_map(|tag| {
+                serde_json::from_str::<TagInfo>(tag)
+                    .ok()
+                    .map(|tag| tag.format_proj_tags())
+            }));
+        }
+
+        let total = lines.len();
+        self.inner.clone().create_indexed_cache(
+            fingerprint,
+            total,
+            lines.join("\n").as_bytes(),
+            MatchScope::TagName,
+        )?;
+
+        Ok(Some(total))
+    }
 }
 
 fn detect_json_feature() -> Result<bool> {
@@ -322,7 +420,7 @@ mod tests {
     #[test]
     fn test_deserialize_ctags_line() {
         let data = r#"{"_type": "tag", "name": "Exec", "path": "crates/maple_cli/src/cmd/exec.rs", "pattern": "/^pub struct Exec {$/", "line": 10, "kind": "struct"}"#;
-        let tag: TagInfo = serde_json::from_str(&data).unwrap();
+        let tag: TagInfo = serde_json::from_str(data).unwrap();
         assert_eq!(
             tag,
             TagInfo {
@@ -5,6 +5,7 @@ use serde::Deserialize;
 use super::stats::Stats;
 
 #[derive(Deserialize, Debug, Clone)]
+#[allow(unused)]
 #[serde(tag = "type", content = "data")]
 #[serde(rename_all = "snake_case")]
 pub enum Message {
@@ -15,11 +16,13 @@ pub enum Message {
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[allow(unused)]
 pub struct Begin {
     pub path: Data,
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[allow(unused)]
 pub struct End {
     pub path: Data,
     pub binary_offset: Option<u64>,
@@ -36,6 +39,7 @@ pub struct Match {
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[allow(unused)]
 pub struct Context {
     pub path: Data,
     pub lines: Data,
@@ -74,7 +78,7 @@ pub enum Data {
 }
 
 impl Data {
-    pub fn text(&self) -> Cow<str> {
+    pub fn text(&self) -> Cow<'_, str> {
         match self {
             Self::Text { text } => text.as_str().into(),
             Self::Bytes { bytes } => String::from_utf8_lossy(bytes),
@@ -5,12 +5,29 @@ pub mod util;
 use std::ops::Range;
 use std::{borrow::Cow, convert::TryFrom};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
 
 use crate::utils::display_width;
 
 pub use self::jsont::{Match, Message, SubMatch};
 
+/// If the rg executable supports `--json`, which the regex searcher of dumb_jump relies on.
+pub static RG_HAS_JSON_FEATURE: Lazy<bool> = Lazy::new(|| detect_json_feature().unwrap_or(false));
+
+fn detect_json_feature() -> Result<bool> {
+    let output = std::process::Command::new("rg")
+        .arg("--help")
+        .stderr(std::process::Stdio::inherit())
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    if stdout.contains("--json") {
+        Ok(true)
+    } else {
+        Err(anyhow!("rg executable has no --json option"))
+    }
+}
+
 /// Word represents the input query around by word boundries.
 #[derive(Clone, Debug)]
 pub struct Word {
@@ -74,7 +91,7 @@ impl PartialEq for Match {
 impl Eq for Match {}
 
 impl Match {
-    pub fn path(&self) -> Cow<str> {
+    pub fn path(&self) -> Cow<'_, str> {
         self.path.text()
     }
 
@@ -83,7 +100,7 @@ impl Match {
     }
 
     pub fn column(&self) -> usize {
-        self.submatches.get(0).map(|x| x.start).unwrap_or_default()
+        self.submatches.first().map(|x| x.start).unwrap_or_default()
     }
 
     /// Returns true if the text line starts with `pat`.
@@ -172,7 +189,7 @@ impl Match {
     }
 
     #[inline]
-    pub fn pattern(&self) -> Cow<str> {
+    pub fn pattern(&self) -> Cow<'_, str> {
         self.lines.text()
     }
 
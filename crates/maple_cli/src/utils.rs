@@ -1,5 +1,5 @@
 use std::{
-    io::{BufRead, Lines},
+    io::BufRead,
     path::{Path, PathBuf},
 };
 
@@ -112,24 +112,154 @@ pub fn read_json_as<P: AsRef<Path>, T: serde::de::DeserializeOwned>(path: P) ->
     Ok(deserializd)
 }
 
-pub fn load_json<T: serde::de::DeserializeOwned, P: AsRef<Path>>(path: Option<P>) -> Option<T> {
-    path.and_then(|json_path| {
-        if json_path.as_ref().exists() {
-            crate::utils::read_json_as::<_, T>(json_path).ok()
-        } else {
+/// Returns the path of a sibling file of `path`, e.g., `cache.json.lock` for `cache.json`.
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// Acquires the exclusive advisory lock of `json_path` shared by all the maple processes, which
+/// is released once the returned file is dropped.
+///
+/// A separate lock file is used as the JSON file itself is replaced on each write.
+fn lock_json_file(json_path: &Path) -> std::io::Result<std::fs::File> {
+    use fs2::FileExt;
+
+    let lock_file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(sibling_path(json_path, ".lock"))?;
+    lock_file.lock_exclusive()?;
+    Ok(lock_file)
+}
+
+/// Reads the JSON file `json_path`, the backup of the previous write is read instead if it's
+/// missing or corrupted.
+///
+/// The corrupted file is kept aside as `*.corrupted` rather than being overwritten by the next
+/// write, the caller must hold the lock.
+fn read_json_or_backup<T: serde::de::DeserializeOwned>(json_path: &Path) -> Option<T> {
+    match read_json_as::<_, T>(json_path) {
+        Ok(deserialized) => return Some(deserialized),
+        Err(e) if json_path.exists() => {
+            let corrupted_path = sibling_path(json_path, ".corrupted");
+            tracing::error!(
+                ?e,
+                ?json_path,
+                ?corrupted_path,
+                "Failed to read the JSON file"
+            );
+            if let Err(e) = std::fs::rename(json_path, &corrupted_path) {
+                tracing::error!(?e, "Failed to move aside the corrupted JSON file");
+            }
+        }
+        Err(_) => {}
+    }
+
+    let backup_path = sibling_path(json_path, ".bak");
+    if !backup_path.exists() {
+        return None;
+    }
+    match read_json_as::<_, T>(&backup_path) {
+        Ok(deserialized) => {
+            tracing::debug!(?backup_path, "Recovered from the backup JSON file");
+            Some(deserialized)
+        }
+        Err(e) => {
+            tracing::error!(?e, ?backup_path, "Failed to read the backup JSON file");
             None
         }
-    })
+    }
+}
+
+/// Writes `obj` to a temporary file and renames it to `json_path`, the caller must hold the lock.
+///
+/// The previous content is kept as the backup `*.bak`, which is read if the process crashes
+/// between the two renames.
+fn write_json_atomically<T: serde::Serialize>(obj: &T, json_path: &Path) -> Result<()> {
+    use std::io::Write;
+
+    let tmp_path = sibling_path(json_path, ".tmp");
+    let mut tmp_file = std::fs::File::create(&tmp_path)?;
+    tmp_file.write_all(serde_json::to_string(obj)?.as_bytes())?;
+    tmp_file.sync_all()?;
+
+    if json_path.exists() {
+        std::fs::rename(json_path, sibling_path(json_path, ".bak"))?;
+    }
+    std::fs::rename(&tmp_path, json_path)?;
+
+    // The renames are durable only after the directory entries are flushed as well, which is
+    // unsupported on Windows.
+    #[cfg(unix)]
+    if let Some(parent) = json_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::File::open(parent)?.sync_all()?;
+    }
+
+    Ok(())
 }
 
+/// Loads the JSON file `path`, recovers from the backup if the file is corrupted.
+pub fn load_json<T: serde::de::DeserializeOwned, P: AsRef<Path>>(path: Option<P>) -> Option<T> {
+    let json_path = path?;
+    let json_path = json_path.as_ref();
+
+    // Still try to read it if the lock can not be taken, e.g., the directory is read-only.
+    let _lock = lock_json_file(json_path)
+        .map_err(|e| tracing::error!(?e, ?json_path, "Failed to lock the JSON file"))
+        .ok();
+
+    read_json_or_backup(json_path)
+}
+
+/// Replaces the JSON file `path` with `obj` atomically.
+///
+/// The write is serialized with the other maple processes by an advisory file lock.
 pub fn write_json<T: serde::Serialize, P: AsRef<Path>>(obj: T, path: Option<P>) -> Result<()> {
     if let Some(json_path) = path.as_ref() {
-        utility::create_or_overwrite(json_path, serde_json::to_string(&obj)?.as_bytes())?;
+        let json_path = json_path.as_ref();
+        let _lock = lock_json_file(json_path)?;
+        write_json_atomically(&obj, json_path)?;
     }
 
     Ok(())
 }
 
+/// Replaces the JSON file `path` with the result of `merge` atomically, which is given the
+/// current content on the disk, `None` if it does not exist or is unreadable.
+///
+/// The lock is held from reading to writing, so the updates written by the other maple processes
+/// meanwhile are never lost as long as `merge` keeps them. Returns the written content.
+pub fn merge_json<T, P>(path: Option<P>, merge: impl FnOnce(Option<T>) -> T) -> Result<Option<T>>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+    P: AsRef<Path>,
+{
+    match path.as_ref() {
+        Some(json_path) => {
+            let json_path = json_path.as_ref();
+            let _lock = lock_json_file(json_path)?;
+            let merged = merge(read_json_or_backup(json_path));
+            write_json_atomically(&merged, json_path)?;
+            Ok(Some(merged))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Removes the JSON file `json_path` along with its backup.
+pub fn remove_json(json_path: &Path) -> Result<()> {
+    let _lock = lock_json_file(json_path)?;
+    for path in [json_path.to_path_buf(), sibling_path(json_path, ".bak")] {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 #[allow(unused)]
 pub enum SendResponse {
@@ -218,9 +348,12 @@ pub fn count_lines<R: std::io::Read>(handle: R) -> Result<usize, std::io::Error>
 }
 
 #[inline]
-pub fn lines(cmd: Exec) -> Result<Lines<impl BufRead>> {
+pub fn lines(cmd: Exec) -> Result<impl Iterator<Item = String>> {
     // We usually have a decent amount of RAM nowdays.
-    Ok(std::io::BufReader::with_capacity(8 * 1024 * 1024, cmd.stream_stdout()?).lines())
+    Ok(utility::lossy_lines(std::io::BufReader::with_capacity(
+        8 * 1024 * 1024,
+        cmd.stream_stdout()?,
+    )))
 }
 
 /// Returns the width of displaying `n` on the screen.
@@ -250,4 +383,28 @@ mod tests {
         let f: &[u8] = b"some text\nwith\nfour\nlines\n";
         assert_eq!(count_lines(f).unwrap(), 4);
     }
+
+    #[test]
+    fn test_json_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let json_path = dir.path().join("data.json");
+
+        write_json(vec![1], Some(&json_path)).unwrap();
+        write_json(vec![1, 2], Some(&json_path)).unwrap();
+        assert_eq!(load_json::<Vec<u32>, _>(Some(&json_path)), Some(vec![1, 2]));
+
+        // Truncated by a crash, the previous write is recovered and the broken one is kept aside.
+        std::fs::write(&json_path, "[1,").unwrap();
+        assert_eq!(load_json::<Vec<u32>, _>(Some(&json_path)), Some(vec![1]));
+        assert!(dir.path().join("data.json.corrupted").exists());
+
+        let merged = merge_json(Some(&json_path), |on_disk: Option<Vec<u32>>| {
+            let mut on_disk = on_disk.unwrap();
+            on_disk.push(3);
+            on_disk
+        })
+        .unwrap();
+        assert_eq!(merged, Some(vec![1, 3]));
+        assert_eq!(load_json::<Vec<u32>, _>(Some(&json_path)), Some(vec![1, 3]));
+    }
 }
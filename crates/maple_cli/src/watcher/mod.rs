@@ -0,0 +1,551 @@
+//! Watches the project roots served by the RPC service and keeps their caches fresh in the
+//! background, only supported on Linux.
+//!
+//! The change events are debounced, then the cached rg output and ctags output are updated for
+//! the changed files only, and the gtags DB is updated incrementally.
+
+#![cfg_attr(not(target_os = "linux"), allow(unused))]
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use crossbeam_channel::Sender;
+use filter::{WalkConfig, Walker};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use crate::command::ctags::recursive_tags::build_recursive_ctags_cmd;
+use crate::command::grep::{refresh_cache, GrepBackend, RgBaseCommand};
+use crate::datastore::{merge_unwatched_roots, UNWATCHED_ROOTS_JSON_PATH};
+use crate::find_usages::GtagsSearcher;
+use crate::stdio_server::{note_job_is_finished, register_job_successfully};
+use crate::utils::{load_json, UtcTime};
+
+/// Rebuild the caches entirely instead when more files than this are changed.
+const MAX_INCREMENTAL_FILES: usize = 256;
+
+/// Interval of checking whether the session job updating the same cache has finished.
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static WATCHER: OnceCell<Sender<PathBuf>> = OnceCell::new();
+
+/// Starts watching the roots passed to [`watch`] in a background thread.
+#[cfg(target_os = "linux")]
+pub fn enable() -> Result<()> {
+    let (event_sender, event_receiver) = crossbeam_channel::unbounded();
+    let notify_watcher = notify::recommended_watcher(event_sender)?;
+    let (root_sender, root_receiver) = crossbeam_channel::unbounded();
+    let (update_sender, update_receiver) = crossbeam_channel::unbounded();
+    WATCHER
+        .set(root_sender)
+        .map_err(|_| anyhow::anyhow!("The watcher has already been enabled"))?;
+    // The caches are updated in another thread, otherwise the events would pile up while
+    // rebuilding the caches of a large project.
+    std::thread::Builder::new()
+        .name("watcher-updater".into())
+        .spawn(move || run_updater(update_receiver))?;
+    std::thread::Builder::new()
+        .name("watcher".into())
+        .spawn(move || linux::run(notify_watcher, event_receiver, root_receiver, update_sender))?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn enable() -> Result<()> {
+    Err(anyhow::anyhow!("The watcher is only supported on Linux"))
+}
+
+/// Keeps the caches of project `root` fresh from now on, no-op if the watcher is not enabled.
+pub fn watch(root: &Path) {
+    if let Some(sender) = WATCHER.get() {
+        let _ = sender.send(root.to_path_buf());
+    }
+}
+
+/// Project root that was given up watching, its caches are no longer kept fresh.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnwatchedRoot {
+    pub root: PathBuf,
+    pub reason: String,
+    pub time: UtcTime,
+}
+
+/// Returns the project roots that the watchers of all the maple processes have given up.
+pub fn unwatched_roots() -> Vec<UnwatchedRoot> {
+    load_json(UNWATCHED_ROOTS_JSON_PATH.as_deref()).unwrap_or_default()
+}
+
+/// Records `root` in the disk so that it can be reported by `maple doctor`.
+fn record_unwatched(root: &Path, reason: String) {
+    let unwatched = UnwatchedRoot {
+        root: root.to_path_buf(),
+        reason,
+        time: chrono::Utc::now(),
+    };
+    if let Err(e) = merge_unwatched_roots(|on_disk| {
+        let mut roots = on_disk.unwrap_or_default();
+        roots.retain(|r| r.root != root);
+        roots.push(unwatched);
+        roots
+    }) {
+        tracing::error!(?e, "Failed to record the unwatched root");
+    }
+}
+
+/// Removes `root` from the records once it's watched successfully.
+fn clear_unwatched(root: &Path) {
+    if unwatched_roots().iter().any(|r| r.root == root) {
+        if let Err(e) = merge_unwatched_roots(|on_disk| {
+            let mut roots = on_disk.unwrap_or_default();
+            roots.retain(|r| r.root != root);
+            roots
+        }) {
+            tracing::error!(?e, "Failed to clear the unwatched root");
+        }
+    }
+}
+
+/// Changes of a project root since the caches were updated.
+#[derive(Debug, Default)]
+struct PendingChanges {
+    /// Changed files relative to the root.
+    files: HashSet<String>,
+    /// Rebuild the caches entirely, e.g., a directory was changed or some events were lost.
+    rebuild: bool,
+}
+
+impl PendingChanges {
+    fn merge(&mut self, other: Self) {
+        self.files.extend(other.files);
+        self.rebuild |= other.rebuild;
+    }
+}
+
+/// Returns the `files` relative to `root` that still exist and are not ignored.
+fn included_files(root: &Path, files: &HashSet<String>) -> Vec<String> {
+    let parents = files
+        .iter()
+        .map(|file| Path::new(file).parent().unwrap_or_else(|| Path::new("")))
+        .collect::<HashSet<_>>();
+
+    parents
+        .into_iter()
+        .flat_map(|parent| {
+            let config = WalkConfig {
+                max_depth: Some(1),
+                ..Default::default()
+            };
+            Walker::new(root.join(parent), config)
+                .lines()
+                .into_iter()
+                .map(move |name| parent.join(name).to_string_lossy().into_owned())
+        })
+        .filter(|file| files.contains(file))
+        .collect()
+}
+
+/// Runs `update` once the cache is no longer being updated by a session, the same `job_id` is
+/// registered meanwhile so that the sessions do not refresh it concurrently.
+fn run_exclusively(job_id: u64, update: impl FnOnce()) {
+    while !register_job_successfully(job_id) {
+        std::thread::sleep(JOB_POLL_INTERVAL);
+    }
+    update();
+    note_job_is_finished(job_id);
+}
+
+/// Updates the grep2, proj_tags and gtags caches of `root` if they exist.
+fn update_caches(root: &Path, changes: PendingChanges) {
+    let rebuild = changes.rebuild || changes.files.len() > MAX_INCREMENTAL_FILES;
+
+    tracing::debug!(
+        ?root,
+        rebuild,
+        changed = changes.files.len(),
+        "Updating the caches"
+    );
+
+    // The caches of each backend are separate, the clients may use different backends.
+    let rg_cmds = [GrepBackend::Native, GrepBackend::Ripgrep]
+        .map(|backend| RgBaseCommand::new(root.to_path_buf(), backend));
+    let ctags_cmd = build_recursive_ctags_cmd(root.to_path_buf());
+    let gtags_searcher = GtagsSearcher::new(root.to_path_buf());
+
+    let ctags_job_id = utility::calculate_hash(&ctags_cmd);
+
+    if rebuild {
+        for rg_cmd in rg_cmds.iter() {
+            if rg_cmd.outdated_cache_info().is_some() {
+                run_exclusively(utility::calculate_hash(&rg_cmd.inner), || {
+                    if let Err(e) = refresh_cache(root, rg_cmd.backend()) {
+                        tracing::error!(?e, "Failed to rebuild the grep cache");
+                    }
+                });
+            }
+        }
+        if ctags_cmd.outdated_ctags_cache().is_some() {
+            run_exclusively(ctags_job_id, || {
+                if let Err(e) = ctags_cmd.par_create_cache() {
+                    tracing::error!(?e, "Failed to rebuild the tags cache");
+                }
+            });
+        }
+        if gtags_searcher.db_path.exists() {
+            if let Err(e) = gtags_searcher.update_tags() {
+                tracing::error!(?e, "Failed to update the gtags DB");
+            }
+        }
+        return;
+    }
+
+    let existing = included_files(root, &changes.files);
+
+    for rg_cmd in rg_cmds.iter() {
+        run_exclusively(utility::calculate_hash(&rg_cmd.inner), || {
+            if let Err(e) = rg_cmd.update_cache(&changes.files, &existing) {
+                tracing::error!(?e, "Failed to update the grep cache");
+            }
+        });
+    }
+    run_exclusively(ctags_job_id, || {
+        if let Err(e) = ctags_cmd.update_cache(&changes.files, &existing) {
+            tracing::error!(?e, "Failed to update the tags cache");
+        }
+    });
+    if gtags_searcher.db_path.exists() {
+        let files = changes.files.into_iter().collect::<Vec<_>>();
+        if let Err(e) = gtags_searcher.update_files(&files) {
+            tracing::error!(?e, "Failed to update the gtags DB");
+        }
+    }
+}
+
+/// Updates the caches of the changed roots sent by the watcher thread.
+///
+/// The changes received while updating are merged so that each root is updated at most once.
+fn run_updater(changes: crossbeam_channel::Receiver<(PathBuf, PendingChanges)>) {
+    while let Ok((root, root_changes)) = changes.recv() {
+        let mut pending = std::collections::HashMap::new();
+        pending.insert(root, root_changes);
+        for (root, root_changes) in changes.try_iter() {
+            pending
+                .entry(root)
+                .or_insert_with(PendingChanges::default)
+                .merge(root_changes);
+        }
+        for (root, root_changes) in pending {
+            update_caches(&root, root_changes);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::{HashMap, HashSet};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    use crossbeam_channel::{Receiver, Sender};
+    use filter::{WalkConfig, Walker};
+    use notify::event::{AccessKind, AccessMode, ModifyKind};
+    use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+    use super::{clear_unwatched, record_unwatched, PendingChanges};
+
+    /// Update the caches once no more change happens in this duration.
+    const DEBOUNCE: Duration = Duration::from_secs(1);
+
+    /// Update the caches at least this often when the changes keep coming.
+    const MAX_DELAY: Duration = Duration::from_secs(10);
+
+    /// Interval of checking whether the pending changes are due.
+    const POLL_TIMEOUT: Duration = Duration::from_millis(200);
+
+    /// Maximum number of the watched directories, the default `max_user_watches` is 8192 on the
+    /// older kernels.
+    const MAX_WATCHES: usize = 8192;
+
+    struct Watcher {
+        notify_watcher: RecommendedWatcher,
+        roots: Vec<PathBuf>,
+        /// Roots that have been given up in this process, never retried.
+        unwatched_roots: HashSet<PathBuf>,
+        /// All the directories that have been watched.
+        watched_dirs: HashSet<PathBuf>,
+        pending: HashMap<PathBuf, PendingChanges>,
+    }
+
+    /// Returns true if the directory `dir` is not ignored.
+    fn is_included_dir(dir: &Path) -> bool {
+        let parent = match dir.parent() {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let config = WalkConfig {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        Walker::new(parent, config).dirs().any(|d| d == dir)
+    }
+
+    /// Returns true if the change of an entry is relevant to the caches.
+    fn is_content_change(kind: &EventKind) -> bool {
+        matches!(
+            kind,
+            EventKind::Create(_)
+                | EventKind::Remove(_)
+                | EventKind::Modify(ModifyKind::Any | ModifyKind::Data(_) | ModifyKind::Name(_))
+                | EventKind::Access(AccessKind::Close(AccessMode::Write))
+        )
+    }
+
+    impl Watcher {
+        fn add_root(&mut self, root: PathBuf) {
+            if self.roots.contains(&root) || self.unwatched_roots.contains(&root) {
+                return;
+            }
+            tracing::debug!(?root, "Watching the project root");
+            self.roots.push(root.clone());
+            if self.watch_tree(&root) {
+                clear_unwatched(&root);
+            }
+        }
+
+        /// Watches `dir` and all its subdirectories that are not ignored.
+        ///
+        /// Returns false if the project root of `dir` has been given up.
+        fn watch_tree(&mut self, dir: &Path) -> bool {
+            for dir in Walker::new(dir, WalkConfig::default()).dirs() {
+                if self.watched_dirs.contains(&dir) {
+                    continue;
+                }
+                if self.watched_dirs.len() >= MAX_WATCHES {
+                    let reason = format!("more than {} directories to watch", MAX_WATCHES);
+                    self.unwatch_root_of(&dir, reason);
+                    return false;
+                }
+                match self.notify_watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => {
+                        self.watched_dirs.insert(dir);
+                    }
+                    Err(error) if matches!(error.kind, notify::ErrorKind::MaxFilesWatch) => {
+                        let reason = "reached the inotify limit fs.inotify.max_user_watches";
+                        self.unwatch_root_of(&dir, reason.into());
+                        return false;
+                    }
+                    Err(error) => {
+                        // The directory may have been removed meanwhile.
+                        tracing::error!(?error, ?dir, "Failed to watch the directory");
+                    }
+                }
+            }
+            true
+        }
+
+        /// Stops watching the project roots containing `dir` as their caches can not be kept
+        /// fresh reliably.
+        fn unwatch_root_of(&mut self, dir: &Path, reason: String) {
+            let (given_up, roots): (Vec<_>, Vec<_>) = std::mem::take(&mut self.roots)
+                .into_iter()
+                .partition(|root| dir.starts_with(root));
+            self.roots = roots;
+
+            for root in given_up {
+                tracing::warn!(?root, %reason, "Stopped watching the project root");
+                self.pending.remove(&root);
+                let roots = self.roots.clone();
+                // The nested or outer roots that are still watched share the directories.
+                self.unwatch_dirs(|dir| {
+                    dir.starts_with(&root) && !roots.iter().any(|r| dir.starts_with(r))
+                });
+                record_unwatched(&root, reason.clone());
+                self.unwatched_roots.insert(root);
+            }
+        }
+
+        /// Stops watching the directories matching `predicate`.
+        fn unwatch_dirs(&mut self, predicate: impl Fn(&Path) -> bool) {
+            let unwatched = self
+                .watched_dirs
+                .iter()
+                .filter(|dir| predicate(dir))
+                .cloned()
+                .collect::<Vec<_>>();
+            for dir in unwatched {
+                // The watch has been removed already if the directory itself was removed.
+                let _ = self.notify_watcher.unwatch(&dir);
+                self.watched_dirs.remove(&dir);
+            }
+        }
+
+        fn mark_rebuild(&mut self, path: Option<&Path>) {
+            for root in &self.roots {
+                if path.map(|path| path.starts_with(root)).unwrap_or(true) {
+                    self.pending.entry(root.clone()).or_default().rebuild = true;
+                }
+            }
+        }
+
+        /// Returns true if the event is relevant to the caches.
+        fn on_event(&mut self, event: Event) -> bool {
+            if event.need_rescan() {
+                self.mark_rebuild(None);
+                return true;
+            }
+
+            if !is_content_change(&event.kind) {
+                return false;
+            }
+
+            for path in event.paths {
+                if path.is_dir() {
+                    if !self.watched_dirs.contains(&path) && is_included_dir(&path) {
+                        self.mark_rebuild(Some(&path));
+                        self.watch_tree(&path);
+                    }
+                } else if self.watched_dirs.contains(&path) {
+                    // Removed or renamed.
+                    self.mark_rebuild(Some(&path));
+                    self.unwatch_dirs(|dir| dir.starts_with(&path));
+                } else {
+                    for root in &self.roots {
+                        if let Ok(relative) = path.strip_prefix(root) {
+                            self.pending
+                                .entry(root.clone())
+                                .or_default()
+                                .files
+                                .insert(relative.to_string_lossy().into_owned());
+                        }
+                    }
+                }
+            }
+
+            true
+        }
+    }
+
+    pub(super) fn run(
+        notify_watcher: RecommendedWatcher,
+        events: Receiver<notify::Result<Event>>,
+        new_roots: Receiver<PathBuf>,
+        updater: Sender<(PathBuf, PendingChanges)>,
+    ) {
+        let mut watcher = Watcher {
+            notify_watcher,
+            roots: Vec::new(),
+            unwatched_roots: HashSet::new(),
+            watched_dirs: HashSet::new(),
+            pending: HashMap::new(),
+        };
+
+        let mut first_change: Option<Instant> = None;
+        let mut last_change = Instant::now();
+
+        loop {
+            crossbeam_channel::select! {
+                recv(new_roots) -> root => match root {
+                    Ok(root) => watcher.add_root(root),
+                    Err(_) => return,
+                },
+                recv(events) -> event => match event {
+                    Ok(Ok(event)) => {
+                        if watcher.on_event(event) {
+                            last_change = Instant::now();
+                            first_change.get_or_insert(last_change);
+                        }
+                    }
+                    Ok(Err(error)) => tracing::error!(?error, "Error from the file watcher"),
+                    Err(_) => {
+                        tracing::error!("The file watcher is gone, watcher stopped");
+                        return;
+                    }
+                },
+                default(POLL_TIMEOUT) => {}
+            }
+
+            if let Some(first) = first_change {
+                if last_change.elapsed() >= DEBOUNCE || first.elapsed() >= MAX_DELAY {
+                    first_change = None;
+                    for (root, changes) in std::mem::take(&mut watcher.pending) {
+                        if updater.send((root, changes)).is_err() {
+                            tracing::error!("The cache updater is gone, watcher stopped");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn drain_events(watcher: &mut Watcher, events: &Receiver<notify::Result<Event>>) {
+            while let Ok(event) = events.recv_timeout(Duration::from_millis(500)) {
+                watcher.on_event(event.unwrap());
+            }
+        }
+
+        #[test]
+        fn test_watch_changes() {
+            let tmp = tempfile::tempdir().unwrap();
+            let root = tmp.path().to_path_buf();
+            std::fs::create_dir_all(root.join("sub")).unwrap();
+
+            let (sender, events) = crossbeam_channel::unbounded();
+            let mut watcher = Watcher {
+                notify_watcher: notify::recommended_watcher(sender).unwrap(),
+                roots: Vec::new(),
+                unwatched_roots: HashSet::new(),
+                watched_dirs: HashSet::new(),
+                pending: HashMap::new(),
+            };
+            watcher.add_root(root.clone());
+            assert_eq!(watcher.watched_dirs.len(), 2);
+
+            std::fs::write(root.join("sub/a.txt"), "").unwrap();
+            std::fs::create_dir(root.join("new")).unwrap();
+            drain_events(&mut watcher, &events);
+
+            let changes = watcher.pending.remove(&root).unwrap();
+            assert!(changes.files.contains("sub/a.txt"));
+            assert!(changes.rebuild);
+            assert!(watcher.watched_dirs.contains(&root.join("new")));
+
+            std::fs::remove_dir(root.join("new")).unwrap();
+            drain_events(&mut watcher, &events);
+
+            assert!(watcher.pending.remove(&root).unwrap().rebuild);
+            assert!(!watcher.watched_dirs.contains(&root.join("new")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_included_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+
+        for file in ["a.txt", "b.log", ".hidden", "sub/c.txt"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        std::fs::write(root.join(".ignore"), "*.log\n").unwrap();
+
+        let changed = ["a.txt", "b.log", ".hidden", "sub/c.txt", "removed.txt"]
+            .iter()
+            .map(|file| file.to_string())
+            .collect::<HashSet<_>>();
+
+        let mut included = included_files(root, &changed);
+        included.sort();
+        assert_eq!(included, vec!["a.txt", "sub/c.txt"]);
+    }
+}
@@ -1,16 +1,20 @@
 pub mod fzy;
 pub mod skim;
 pub mod substring;
+pub mod typo;
 
 use types::{CaseMatching, FuzzyText, MatchScope, MatchingText};
 
 use crate::MatchResult;
 
 /// Supported fuzzy match algorithm.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub enum FuzzyAlgorithm {
     Skim,
+    #[default]
     Fzy,
+    /// Fzy tolerating a few typos, see [`typo`].
+    Typo,
 }
 
 impl std::str::FromStr for FuzzyAlgorithm {
@@ -25,17 +29,12 @@ impl<T: AsRef<str>> From<T> for FuzzyAlgorithm {
         match algo.as_ref().to_lowercase().as_str() {
             "skim" => Self::Skim,
             "fzy" => Self::Fzy,
+            "typo" => Self::Typo,
             _ => Self::Fzy,
         }
     }
 }
 
-impl Default for FuzzyAlgorithm {
-    fn default() -> Self {
-        Self::Fzy
-    }
-}
-
 impl FuzzyAlgorithm {
     /// Does the fuzzy match against the match text.
     pub fn fuzzy_match<T: MatchingText>(
@@ -53,6 +52,7 @@ impl FuzzyAlgorithm {
                 let res = match self {
                     Self::Fzy => fzy::fuzzy_indices(text, query, case_matching),
                     Self::Skim => skim::fuzzy_indices(text, query, case_matching),
+                    Self::Typo => typo::fuzzy_indices(text, query, case_matching),
                 };
                 res.map(|MatchResult { score, indices }| {
                     let mut indices = indices;
@@ -0,0 +1,262 @@
+//! Typo-tolerant subsequence matching.
+//!
+//! The query is matched as a subsequence of the text like fzy, in addition, a bounded number of
+//! typos are tolerated, each typo is either a query char absent from the text(substitution or
+//! insertion) or two adjacent query chars in the wrong order(transposition), e.g., `recieve`
+//! matches `receive` with one transposition.
+
+use std::cell::RefCell;
+
+use types::CaseMatching;
+
+use crate::{algo::fzy, MatchResult, Score};
+
+/// Score deducted for each typo, the typo-tolerant match always has a lower score than the exact
+/// subsequence match of the same text.
+const TYPO_PENALTY: Score = 100;
+
+/// Returns the maximum number of typos allowed for a query of `query_len` chars.
+fn max_typos(query_len: usize) -> usize {
+    match query_len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Cost of (typos, dropped query chars).
+type Cost = (usize, usize);
+
+const MATCH: Cost = (0, 0);
+const DROP: Cost = (1, 1);
+const TRANSPOSE: Cost = (1, 0);
+
+fn add((a, b): Cost, (c, d): Cost) -> Cost {
+    (a + c, b + d)
+}
+
+fn is_transposed(text: &[char], query: &[char], i: usize, j: usize) -> bool {
+    i > 1
+        && j > 1
+        && query[i - 1] == text[j - 2]
+        && query[i - 2] == text[j - 1]
+        && query[i - 1] != query[i - 2]
+}
+
+/// Fills `row`, the minimum costs of matching `query[..i]` within `text[..j]` for each `j`,
+/// given the rows of `query[..i - 2]` and `query[..i - 1]`.
+fn fill_row(
+    text: &[char],
+    query: &[char],
+    i: usize,
+    prev2: &[Cost],
+    prev: &[Cost],
+    row: &mut [Cost],
+) {
+    for j in 0..=text.len() {
+        let mut best = add(prev[j], DROP);
+        if j > 0 {
+            // Skip the text char.
+            best = best.min(row[j - 1]);
+            if query[i - 1] == text[j - 1] {
+                best = best.min(prev[j - 1]);
+            }
+            if is_transposed(text, query, i, j) {
+                best = best.min(add(prev2[j - 2], TRANSPOSE));
+            }
+        }
+        row[j] = best;
+    }
+}
+
+/// Returns true if `query` can be matched within `text` with at most `max_typos` typos.
+///
+/// Only the last rows of the alignment table are kept, most of the lines rejected by fzy are
+/// rejected here too.
+fn is_within_typos(text: &[char], query: &[char], max_typos: usize) -> bool {
+    let n = text.len();
+    let mut prev2 = vec![MATCH; n + 1];
+    let mut prev = vec![MATCH; n + 1];
+    let mut row = vec![MATCH; n + 1];
+
+    for i in 1..=query.len() {
+        fill_row(text, query, i, &prev2, &prev, &mut row);
+        // The last cost is the minimum of the row, which never decreases in the following rows.
+        if row[n].0 > max_typos {
+            return false;
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut row);
+    }
+
+    true
+}
+
+thread_local! {
+    /// Alignment table reused across the candidates matched on the same thread.
+    static TABLE: RefCell<Vec<Cost>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Maximum number of the cells kept by [`TABLE`] after an alignment, the table of an unusually
+/// long line is not retained.
+const MAX_RETAINED_CELLS: usize = 64 * 1024;
+
+/// Returns the matched char positions in `text` and the number of typos of the best alignment,
+/// `None` if there are more typos than allowed.
+///
+/// The best alignment has the fewest typos, then the fewest dropped query chars.
+fn align(text: &[char], query: &[char], max_typos: usize) -> Option<(Vec<usize>, usize)> {
+    if !is_within_typos(text, query, max_typos) {
+        return None;
+    }
+
+    TABLE.with(|table| {
+        let mut table = table.borrow_mut();
+        let alignment = align_in(text, query, &mut table);
+        if table.capacity() > MAX_RETAINED_CELLS {
+            table.clear();
+            table.shrink_to(MAX_RETAINED_CELLS);
+        }
+        Some(alignment)
+    })
+}
+
+/// Aligns `query` within `text` using `table` as the whole alignment table.
+fn align_in(text: &[char], query: &[char], table: &mut Vec<Cost>) -> (Vec<usize>, usize) {
+    let (n, m) = (text.len(), query.len());
+    let width = n + 1;
+
+    // The row `i` of the table: the minimum costs of matching `query[..i]` within `text[..j]` for
+    // each `j`, the whole table is kept for tracing back the positions.
+    table.clear();
+    table.resize((m + 1) * width, MATCH);
+
+    for i in 1..=m {
+        let (filled, rest) = table.split_at_mut(i * width);
+        let (prev2, prev) = (i.saturating_sub(2) * width, (i - 1) * width);
+        fill_row(
+            text,
+            query,
+            i,
+            &filled[prev2..prev2 + width],
+            &filled[prev..prev + width],
+            &mut rest[..width],
+        );
+    }
+
+    let dp = |i: usize, j: usize| table[i * width + j];
+
+    let (typos, _) = dp(m, n);
+
+    let mut positions = Vec::with_capacity(m);
+    let (mut i, mut j) = (m, n);
+    while i > 0 {
+        if j > 0 && dp(i, j) == dp(i, j - 1) {
+            j -= 1;
+        } else if j > 0 && query[i - 1] == text[j - 1] && dp(i, j) == dp(i - 1, j - 1) {
+            positions.push(j - 1);
+            i -= 1;
+            j -= 1;
+        } else if is_transposed(text, query, i, j) && dp(i, j) == add(dp(i - 2, j - 2), TRANSPOSE) {
+            positions.push(j - 1);
+            positions.push(j - 2);
+            i -= 2;
+            j -= 2;
+        } else {
+            i -= 1;
+        }
+    }
+    positions.reverse();
+
+    (positions, typos)
+}
+
+pub fn fuzzy_indices(text: &str, query: &str, case_matching: CaseMatching) -> Option<MatchResult> {
+    if let Some(exact_match) = fzy::fuzzy_indices(text, query, case_matching) {
+        return Some(exact_match);
+    }
+
+    let query_chars: Vec<char> = query.chars().collect();
+    let max_typos = max_typos(query_chars.len());
+    if max_typos == 0 {
+        return None;
+    }
+
+    let (text_chars, query_chars): (Vec<char>, Vec<char>) =
+        if case_matching.is_case_sensitive(query) {
+            (text.chars().collect(), query_chars)
+        } else {
+            (
+                text.chars().flat_map(char::to_lowercase).collect(),
+                query.chars().flat_map(char::to_lowercase).collect(),
+            )
+        };
+
+    // Lowercasing can change the number of chars, the positions would be invalid then.
+    if text_chars.len() != text.chars().count() {
+        return None;
+    }
+
+    let (positions, typos) = align(&text_chars, &query_chars, max_typos)?;
+
+    if positions.is_empty() {
+        return None;
+    }
+
+    // Score the corrected query, which is an exact subsequence of the text.
+    let corrected: String = text
+        .chars()
+        .enumerate()
+        .filter(|(idx, _)| positions.binary_search(idx).is_ok())
+        .map(|(_, c)| c)
+        .collect();
+
+    fzy::fuzzy_indices(text, &corrected, CaseMatching::Respect).map(
+        |MatchResult { score, indices }| {
+            MatchResult::new(score - TYPO_PENALTY * typos as Score, indices)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typo_tolerant_match() {
+        let text = "fn receive_message()";
+
+        let exact = fuzzy_indices(text, "receive", CaseMatching::Smart).unwrap();
+        assert_eq!(exact.indices, (3..10).collect::<Vec<_>>());
+
+        // Transposition.
+        let transposed = fuzzy_indices(text, "recieve", CaseMatching::Smart).unwrap();
+        assert_eq!(transposed.indices, exact.indices);
+        assert!(transposed.score < exact.score);
+
+        // Substitution.
+        let substituted = fuzzy_indices(text, "recaive", CaseMatching::Smart).unwrap();
+        assert_eq!(substituted.indices, [3, 4, 5, 7, 8, 9].to_vec());
+        assert!(substituted.score < exact.score);
+
+        // A query of at least 4 chars tolerates one typo.
+        assert!(fuzzy_indices(text, "rcv_x", CaseMatching::Smart).is_some());
+        assert!(fuzzy_indices(text, "rcvx", CaseMatching::Smart).is_some());
+        // No typo is tolerated in a query shorter than 4 chars, nor more typos than allowed.
+        assert!(fuzzy_indices(text, "rcx", CaseMatching::Smart).is_none());
+        assert!(fuzzy_indices(text, "xyzw", CaseMatching::Smart).is_none());
+
+        // The reused table of a longer text does not affect the following alignments.
+        let long_text = "r".repeat(1000) + text;
+        assert!(fuzzy_indices(&long_text, "recieve", CaseMatching::Smart).is_some());
+        assert_eq!(
+            fuzzy_indices(text, "recieve", CaseMatching::Smart).unwrap(),
+            transposed
+        );
+
+        // A longer query tolerates more typos, the matches of a query are not necessarily a
+        // superset of the matches of its refinement.
+        assert!(fuzzy_indices("abXd", "abc", CaseMatching::Smart).is_none());
+        assert!(fuzzy_indices("abXd", "abcd", CaseMatching::Smart).is_some());
+    }
+}
@@ -0,0 +1,73 @@
+//! Add a bonus score for the frequently and recently opened files.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use pattern::extract_fpath_from_grep_line;
+
+use crate::Score;
+
+/// Used for files, git_files and grep2 providers.
+///
+/// Maps the path relative to the cwd to its frecent score, the entry of these providers is
+/// either a relative file path or a grep line.
+#[derive(Clone, Debug, Default)]
+pub struct Frecency {
+    scores: Arc<HashMap<String, u64>>,
+    max_score: u64,
+}
+
+impl From<HashMap<String, u64>> for Frecency {
+    fn from(scores: HashMap<String, u64>) -> Self {
+        let max_score = scores.values().copied().max().unwrap_or_default();
+        Self {
+            scores: Arc::new(scores),
+            max_score,
+        }
+    }
+}
+
+impl Frecency {
+    fn frecent_score(&self, path: &str) -> Option<u64> {
+        self.scores
+            .get(path.strip_prefix("./").unwrap_or(path))
+            .copied()
+    }
+
+    /// The bonus is proportional to the frecent score, up to half of `base_score`.
+    pub fn calc_bonus(&self, bonus_text: &str, base_score: Score) -> Score {
+        if self.max_score == 0 {
+            return 0;
+        }
+
+        let frecent_score = self.frecent_score(bonus_text).or_else(|| {
+            extract_fpath_from_grep_line(bonus_text).and_then(|path| self.frecent_score(path))
+        });
+
+        match frecent_score {
+            Some(frecent_score) => {
+                base_score * frecent_score as Score / (2 * self.max_score as Score)
+            }
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frecency_bonus() {
+        let frecency: Frecency = vec![("src/lib.rs".to_string(), 8), ("README.md".into(), 2)]
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+            .into();
+
+        assert_eq!(frecency.calc_bonus("src/lib.rs", 100), 50);
+        assert_eq!(frecency.calc_bonus("./README.md", 100), 12);
+        assert_eq!(frecency.calc_bonus("src/lib.rs:1:1:use std::fs;", 100), 50);
+        assert_eq!(frecency.calc_bonus("src/main.rs", 100), 0);
+        assert_eq!(Frecency::default().calc_bonus("src/lib.rs", 100), 0);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod cwd;
 pub mod filename;
+pub mod frecency;
 pub mod language;
 pub mod recent_files;
 
@@ -7,13 +8,14 @@ use types::MatchingText;
 
 use self::cwd::Cwd;
 use self::filename::calc_bonus_file_name;
+use self::frecency::Frecency;
 use self::language::Language;
 use self::recent_files::RecentFiles;
 
 use crate::Score;
 
 /// Tweak the matching score calculated by the base match algorithm.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum Bonus {
     /// Give a bonus if the needle matches in the basename of the haystack.
     ///
@@ -29,16 +31,14 @@ pub enum Bonus {
     /// Give a bonus if the entry is an absolute file path and matches the cwd.
     Cwd(Cwd),
 
+    /// Give a bonus proportional to the frecent score of the file path in the entry.
+    Frecency(Frecency),
+
     /// No additional bonus.
+    #[default]
     None,
 }
 
-impl Default for Bonus {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 impl<T: AsRef<str>> From<T> for Bonus {
     fn from(s: T) -> Self {
         match s.as_ref().to_lowercase().as_str() {
@@ -68,6 +68,7 @@ impl Bonus {
             Self::RecentFiles(recent_files) => recent_files.calc_bonus(bonus_text, score),
             Self::Language(language) => language.calc_bonus(bonus_text, score),
             Self::Cwd(cwd) => cwd.calc_bonus(bonus_text, score),
+            Self::Frecency(frecency) => frecency.calc_bonus(bonus_text, score),
             Self::None => 0,
         }
     }
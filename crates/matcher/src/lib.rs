@@ -26,15 +26,16 @@
 mod algo;
 mod bonus;
 
-pub use self::algo::{fzy, skim, substring, FuzzyAlgorithm};
+pub use self::algo::{fzy, skim, substring, typo, FuzzyAlgorithm};
 pub use self::bonus::cwd::Cwd;
+pub use self::bonus::frecency::Frecency;
 pub use self::bonus::language::Language;
 pub use self::bonus::Bonus;
-use types::{CaseMatching, FilteredItem};
+use types::{CaseMatching, FilteredItem, FuzzyText};
 // Re-export types
 pub use types::{
-    ExactTerm, ExactTermType, FuzzyTermType, MatchScope, MatchingText, Query, SearchTerm,
-    SourceItem, TermType,
+    ExactTerm, ExactTermType, FieldTerm, FuzzyTermType, MatchScope, MatchingText, Query, RegexTerm,
+    RegexTermType, SearchField, SearchTerm, SourceItem, TermType,
 };
 
 /// Score of base matching algorithm(fzy, skim, etc).
@@ -126,6 +127,32 @@ pub fn match_exact_terms<'a>(
     Some((exact_score, indices))
 }
 
+/// Returns an optional tuple of (score, indices) if all the regex searching terms are satisfied.
+pub fn match_regex_terms<'a>(
+    terms: impl Iterator<Item = &'a RegexTerm>,
+    full_search_line: &str,
+) -> Option<(Score, Vec<usize>)> {
+    let mut indices = Vec::<usize>::new();
+    let mut regex_score = Score::default();
+
+    for term in terms {
+        match term.ty {
+            RegexTermType::Regex => {
+                let sub_indices = term.match_indices(full_search_line)?;
+                regex_score += sub_indices.len() as Score;
+                indices.extend_from_slice(&sub_indices);
+            }
+            RegexTermType::InverseRegex => {
+                if term.re.is_match(full_search_line) {
+                    return None;
+                }
+            }
+        }
+    }
+
+    Some((regex_score, indices))
+}
+
 /// `Matcher` is composed of two components:
 ///
 ///   * `match_scope`: represents the way of extracting the matching piece from the raw line.
@@ -169,11 +196,20 @@ impl Matcher {
         self
     }
 
+    pub fn set_fuzzy_algo(mut self, fuzzy_algo: FuzzyAlgorithm) -> Self {
+        self.fuzzy_algo = fuzzy_algo;
+        self
+    }
+
     pub fn set_match_scope(mut self, match_scope: MatchScope) -> Self {
         self.match_scope = match_scope;
         self
     }
 
+    pub fn match_scope(&self) -> MatchScope {
+        self.match_scope
+    }
+
     pub fn set_case_matching(mut self, case_matching: CaseMatching) -> Self {
         self.case_matching = case_matching;
         self
@@ -199,8 +235,15 @@ impl Matcher {
             .sum()
     }
 
-    /// Actually performs the matching algorithm.
-    pub fn match_query<T: MatchingText>(&self, item: &T, query: &Query) -> Option<MatchResult> {
+    /// Matches all the search terms of `query` except the OR-groups, without the bonus.
+    ///
+    /// Returns the score and indices of the exact, regex and field terms, followed by the ones of
+    /// the fuzzy terms.
+    fn match_terms<T: MatchingText>(
+        &self,
+        item: &T,
+        query: &Query,
+    ) -> Option<(Score, Vec<usize>, Score, Vec<usize>)> {
         // Try the inverse terms against the full search line.
         for inverse_term in query.inverse_terms.iter() {
             if inverse_term.match_full_line(item.full_text()) {
@@ -208,6 +251,10 @@ impl Matcher {
             }
         }
 
+        // Try the regex terms against the full search line.
+        let (regex_score, regex_indices) =
+            match_regex_terms(query.regex_terms.iter(), item.full_text())?;
+
         // Try the exact terms against the full search line.
         let (exact_score, mut indices) = match_exact_terms(
             query.exact_terms.iter(),
@@ -215,6 +262,16 @@ impl Matcher {
             self.case_matching,
         )?;
 
+        let mut exact_score = exact_score + regex_score;
+        indices.extend_from_slice(&regex_indices);
+
+        // Try the field terms against the corresponding fields.
+        for field_term in query.field_terms.iter() {
+            let (field_score, field_indices) = self.match_field_term(item, field_term)?;
+            exact_score += field_score;
+            indices.extend_from_slice(&field_indices);
+        }
+
         // Try the fuzzy terms against the matched text.
         let mut fuzzy_indices = Vec::with_capacity(query.fuzzy_len());
         let mut fuzzy_score = Score::default();
@@ -229,6 +286,59 @@ impl Matcher {
             }
         }
 
+        Some((exact_score, indices, fuzzy_score, fuzzy_indices))
+    }
+
+    /// Returns the score and indices of `field_term` applied on the field text of `item`.
+    fn match_field_term<T: MatchingText>(
+        &self,
+        item: &T,
+        field_term: &FieldTerm,
+    ) -> Option<(Score, Vec<usize>)> {
+        let FuzzyText {
+            text,
+            matching_start,
+        } = field_term
+            .field
+            .extract(item.full_text(), &self.match_scope)?;
+
+        let (exact_score, mut indices, fuzzy_score, fuzzy_indices) =
+            self.match_terms(&text, &field_term.query)?;
+        indices.extend_from_slice(&fuzzy_indices);
+        indices.iter_mut().for_each(|x| *x += matching_start);
+
+        Some((exact_score + fuzzy_score, indices))
+    }
+
+    /// Returns the score and indices of the best matched branch of an OR-group.
+    fn match_or_group<T: MatchingText>(
+        &self,
+        item: &T,
+        group: &[Query],
+    ) -> Option<(Score, Vec<usize>)> {
+        group
+            .iter()
+            .filter_map(|branch| {
+                let (exact_score, mut indices, fuzzy_score, fuzzy_indices) =
+                    self.match_terms(item, branch)?;
+                indices.extend_from_slice(&fuzzy_indices);
+                Some((exact_score + fuzzy_score, indices))
+            })
+            .max_by_key(|(score, _)| *score)
+    }
+
+    /// Actually performs the matching algorithm.
+    pub fn match_query<T: MatchingText>(&self, item: &T, query: &Query) -> Option<MatchResult> {
+        let (mut exact_score, mut indices, fuzzy_score, mut fuzzy_indices) =
+            self.match_terms(item, query)?;
+
+        // Every OR-group has to be satisfied by at least one of its branches.
+        for group in query.or_groups.iter() {
+            let (group_score, group_indices) = self.match_or_group(item, group)?;
+            exact_score += group_score;
+            indices.extend_from_slice(&group_indices);
+        }
+
         if fuzzy_indices.is_empty() {
             let bonus_score = self.calc_bonus(item, exact_score, &indices);
 
@@ -332,7 +442,7 @@ mod tests {
 
     #[test]
     fn test_language_keyword_bonus() {
-        let lines = vec!["hellorsr foo", "function foo"];
+        let lines = ["hellorsr foo", "function foo"];
         let matcher = Matcher::new(
             Bonus::Language("vim".into()),
             FuzzyAlgorithm::Fzy,
@@ -347,7 +457,7 @@ mod tests {
 
     #[test]
     fn test_exact_search_term_bonus() {
-        let lines = vec!["function foo qwer", "function foo"];
+        let lines = ["function foo qwer", "function foo"];
         let matcher = Matcher::new(Default::default(), FuzzyAlgorithm::Fzy, MatchScope::Full);
         let query: Query = "'fo".into();
         let match_result1 = matcher.match_query(&lines[0], &query).unwrap();
@@ -437,4 +547,115 @@ mod tests {
             matched_results
         );
     }
+
+    #[test]
+    fn test_regex_search_term() {
+        let items: Vec<SourceItem> = vec![
+            "src/lib.rs:10:1:fn test_regex() {".into(),
+            "src/lib.rs:20:1:fn regex_test() {".into(),
+            "tests/it.rs:5:1:fn test_it() {".into(),
+        ];
+
+        let matcher = Matcher::default();
+        let match_indices = |query: &str| {
+            let query: Query = query.into();
+            items
+                .iter()
+                .map(|item| {
+                    matcher
+                        .match_query(item, &query)
+                        .map(|MatchResult { indices, .. }| indices)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            match_indices(r"/\bfn\s+test_/"),
+            vec![Some((16..24).collect()), None, Some((16..24).collect())]
+        );
+        assert_eq!(
+            match_indices(r"/\bfn\s+test_/ !/^tests/"),
+            vec![Some((16..24).collect()), None, None]
+        );
+    }
+
+    #[test]
+    fn test_or_groups() {
+        let items: Vec<SourceItem> = vec![
+            "crates/matcher/src/lib.rs".into(),
+            "crates/matcher/Cargo.toml".into(),
+            "README.md".into(),
+        ];
+
+        let matcher = Matcher::default();
+        let match_indices = |query: &str| {
+            let query: Query = query.into();
+            items
+                .iter()
+                .map(|item| {
+                    matcher
+                        .match_query(item, &query)
+                        .map(|MatchResult { indices, .. }| indices)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            match_indices(".rs$ | .toml$"),
+            vec![Some(vec![22, 23, 24]), Some(vec![20, 21, 22, 23, 24]), None]
+        );
+        assert_eq!(
+            match_indices("'matcher .rs$ | .toml$ | ^README"),
+            vec![
+                Some([7, 8, 9, 10, 11, 12, 13, 22, 23, 24].to_vec()),
+                Some([7, 8, 9, 10, 11, 12, 13, 20, 21, 22, 23, 24].to_vec()),
+                None
+            ]
+        );
+        assert_eq!(
+            match_indices("^README | !crates"),
+            vec![None, None, Some(vec![0, 1, 2, 3, 4, 5])]
+        );
+    }
+
+    #[test]
+    fn test_field_terms() {
+        let items: Vec<SourceItem> = vec![
+            "crates/matcher/src/lib.rs:1:1:pub fn match_query()".into(),
+            "README.md:10:1:how to match_query in src/".into(),
+        ];
+
+        let matcher = Matcher::default().set_match_scope(MatchScope::GrepLine);
+        let match_indices = |query: &str| {
+            let query: Query = query.into();
+            items
+                .iter()
+                .map(|item| {
+                    matcher
+                        .match_query(item, &query)
+                        .map(|MatchResult { indices, .. }| indices)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            match_indices("path:'src/ query"),
+            vec![Some([15, 16, 17, 18, 43, 44, 45, 46, 47].to_vec()), None]
+        );
+        assert_eq!(
+            match_indices("path:!src/ query"),
+            vec![None, Some([28, 29, 30, 31, 32].to_vec())]
+        );
+
+        let line = "match_query:228                [function@crates/matcher/src/lib.rs]  pub fn";
+        let matcher = Matcher::default().set_match_scope(MatchScope::TagName);
+        let query: Query = "kind:^func path:lib.rs$".into();
+        assert_eq!(
+            matcher.match_query(&line, &query).map(|r| r.indices),
+            Some([32, 33, 34, 35, 60, 61, 62, 63, 64, 65].to_vec())
+        );
+        assert!(matcher
+            .match_query(&line, &"kind:variable".into())
+            .is_none());
+    }
 }
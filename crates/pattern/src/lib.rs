@@ -23,6 +23,9 @@ static PROJ_TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(.*):(\d+).*\[(.*)@(.
 static COMMIT_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^.*\d{4}-\d{2}-\d{2}\s+([0-9a-z]+)\s+").unwrap());
 
+// match the author at the end of commit line.
+static COMMIT_AUTHOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"\(([^()]*)\)\s*$").unwrap());
+
 static GTAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(.*)\s+(\d+)\s+(.*)").unwrap());
 
 pub fn parse_gtags(line: &str) -> Option<(usize, &str, &str)> {
@@ -117,6 +120,15 @@ pub fn parse_rev(line: &str) -> Option<&str> {
     cap.get(1).map(|x| x.as_str())
 }
 
+/// Returns the author of commit line, e.g., `2019-10-18 8ed4391 Add foo (#65) (Liu-Cheng Xu)`.
+pub fn extract_commit_author(line: &str) -> Option<&str> {
+    if !COMMIT_RE.is_match(line) {
+        return None;
+    }
+    let cap = COMMIT_AUTHOR.captures(line)?;
+    cap.get(1).map(|x| x.as_str())
+}
+
 pub fn extract_proj_tags(line: &str) -> Option<(usize, &str)> {
     let cap = PROJ_TAGS.captures(line)?;
     let lnum = cap.get(2).map(|x| x.as_str()).and_then(parse_lnum)?;
@@ -233,6 +245,8 @@ mod tests {
         assert_eq!(parse_rev(line), Some("8ed4391"));
         let line = "2019-12-29 3f0d00c Add forerunner job status sign and a delay timer for running maple (#184) (Liu-Cheng Xu)";
         assert_eq!(parse_rev(line), Some("3f0d00c"));
+        assert_eq!(extract_commit_author(line), Some("Liu-Cheng Xu"));
+        assert_eq!(extract_commit_author("foo (bar)"), None);
     }
 
     #[test]
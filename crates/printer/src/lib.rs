@@ -73,17 +73,6 @@ impl DecoratedLines {
             println_json!(lines, indices, truncated_map, icon_added, total);
         }
     }
-
-    pub fn print_on_session_create(&self) {
-        let Self {
-            lines,
-            truncated_map,
-            icon_added,
-            ..
-        } = self;
-        let method = "s:init_display";
-        println_json_with_length!(lines, truncated_map, icon_added, method);
-    }
 }
 
 /// Returns the info of the truncated top items ranked by the filtering score.
@@ -102,7 +91,7 @@ pub fn decorate_lines<T>(
                 let iconized = if let Some(origin_text) = truncated_map.get(&(idx + 1)) {
                     format!("{} {}", painter.icon(origin_text), text)
                 } else {
-                    painter.paint(&text)
+                    painter.paint(text)
                 };
                 (iconized, filtered_item.shifted_indices(ICON_LEN))
             })
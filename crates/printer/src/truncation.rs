@@ -70,7 +70,7 @@ pub fn truncate_long_matched_lines<T>(
 ) -> LinesTruncatedMap {
     let mut truncated_map = HashMap::new();
     let winwidth = winwidth - WINWIDTH_OFFSET;
-    items.enumerate().for_each(|(lnum, mut filtered_item)| {
+    items.enumerate().for_each(|(lnum, filtered_item)| {
         let origin_display_text = filtered_item.source_item.display_text();
 
         // Truncate the text simply if it's too long.
@@ -131,7 +131,7 @@ pub fn truncate_grep_lines(
     let winwidth = winwidth - WINWIDTH_OFFSET;
     let (lines, indices): (Vec<String>, Vec<Vec<usize>>) = lines
         .into_iter()
-        .zip(indices.into_iter())
+        .zip(indices)
         .map(|(line, mut indices)| {
             lnum += 1;
 
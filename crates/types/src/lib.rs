@@ -2,12 +2,14 @@ mod query;
 mod search_term;
 mod source_item;
 
-pub use self::query::Query;
+pub use self::query::{FieldTerm, Query};
 pub use self::search_term::{
-    ExactTerm, ExactTermType, FuzzyTerm, FuzzyTermType, InverseTerm, InverseTermType, SearchTerm,
-    TermType,
+    ExactTerm, ExactTermType, FuzzyTerm, FuzzyTermType, InverseTerm, InverseTermType, RegexTerm,
+    RegexTermType, SearchTerm, TermType,
+};
+pub use self::source_item::{
+    FilteredItem, FuzzyText, MatchScope, MatchingText, SearchField, SourceItem,
 };
-pub use self::source_item::{FilteredItem, FuzzyText, MatchScope, MatchingText, SourceItem};
 
 /// The preview content is usually part of a file.
 #[derive(Clone, Debug)]
@@ -20,19 +22,14 @@ pub struct PreviewInfo {
     pub lines: Vec<String>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub enum CaseMatching {
     Ignore,
     Respect,
+    #[default]
     Smart,
 }
 
-impl Default for CaseMatching {
-    fn default() -> Self {
-        Self::Smart
-    }
-}
-
 impl std::str::FromStr for CaseMatching {
     type Err = ();
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -1,40 +1,182 @@
-use crate::search_term::{ExactTerm, FuzzyTerm, InverseTerm, SearchTerm, TermType};
+use crate::search_term::{ExactTerm, FuzzyTerm, InverseTerm, RegexTerm, SearchTerm, TermType};
+use crate::source_item::SearchField;
 
+/// Token separating the alternatives of an OR-group, e.g., `.rs$ | .toml$`.
+const OR_OPERATOR: &str = "|";
+
+/// Search term which is only applied to a field of the line, e.g., `path:src/`.
 #[derive(Debug, Clone)]
+pub struct FieldTerm {
+    pub field: SearchField,
+    /// Query composed of the single search term following the field prefix.
+    pub query: Query,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Query {
     pub fuzzy_terms: Vec<FuzzyTerm>,
     pub exact_terms: Vec<ExactTerm>,
     pub inverse_terms: Vec<InverseTerm>,
+    pub regex_terms: Vec<RegexTerm>,
+    pub field_terms: Vec<FieldTerm>,
+    /// Each group is satisfied if any of its branches matches.
+    ///
+    /// Every branch contains exactly one search term.
+    pub or_groups: Vec<Vec<Query>>,
 }
 
 impl<T: AsRef<str>> From<T> for Query {
     fn from(query: T) -> Self {
         let query = query.as_ref();
 
-        let mut fuzzy_terms = Vec::new();
-        let mut exact_terms = Vec::new();
-        let mut inverse_terms = Vec::new();
+        // Consecutive terms joined by `|` form a group.
+        let mut groups: Vec<Vec<&str>> = Vec::new();
+        let mut pending_or = false;
 
         for token in query.split_whitespace() {
-            let SearchTerm { ty, word } = token.into();
+            if token == OR_OPERATOR {
+                pending_or = !groups.is_empty();
+                continue;
+            }
 
-            match ty {
-                TermType::Fuzzy(term_ty) => fuzzy_terms.push(FuzzyTerm::new(term_ty, word)),
-                TermType::Exact(term_ty) => exact_terms.push(ExactTerm::new(term_ty, word)),
-                TermType::Inverse(term_ty) => inverse_terms.push(InverseTerm::new(term_ty, word)),
+            match groups.last_mut() {
+                Some(group) if pending_or => group.push(token),
+                _ => groups.push(vec![token]),
             }
+            pending_or = false;
         }
 
-        Self {
-            fuzzy_terms,
-            exact_terms,
-            inverse_terms,
+        let mut this = Self::default();
+
+        for group in groups {
+            if group.len() == 1 {
+                this.push_token(group[0]);
+            } else {
+                this.or_groups.push(
+                    group
+                        .into_iter()
+                        .map(|token| {
+                            let mut branch = Self::default();
+                            branch.push_token(token);
+                            branch
+                        })
+                        .collect(),
+                );
+            }
         }
+
+        this
     }
 }
 
 impl Query {
+    fn push_token(&mut self, token: &str) {
+        match SearchField::split_token(token) {
+            Some((field, term)) => self.field_terms.push(FieldTerm {
+                field,
+                query: term.into(),
+            }),
+            None => self.push_term(token.into()),
+        }
+    }
+
+    fn push_term(&mut self, SearchTerm { ty, word }: SearchTerm) {
+        match ty {
+            TermType::Fuzzy(term_ty) => self.fuzzy_terms.push(FuzzyTerm::new(term_ty, word)),
+            TermType::Exact(term_ty) => self.exact_terms.push(ExactTerm::new(term_ty, word)),
+            TermType::Inverse(term_ty) => self.inverse_terms.push(InverseTerm::new(term_ty, word)),
+            TermType::Regex(term_ty) => self.regex_terms.push(RegexTerm::new(term_ty, word)),
+        }
+    }
+
     pub fn fuzzy_len(&self) -> usize {
         self.fuzzy_terms.iter().map(|f| f.len()).sum()
     }
+
+    /// Returns `true` if the results of applying `self` is a superset of
+    /// applying `other` on the same source, i.e., `other` is a refinement of `self`.
+    ///
+    /// Each term of `self` has to be implied by some term of `other`, the OR-groups of `self`
+    /// are not compared for simplicity.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        self.or_groups.is_empty()
+            && self
+                .exact_terms
+                .iter()
+                .all(|term| other.exact_terms.iter().any(|o| term.is_superset(o)))
+            && self
+                .inverse_terms
+                .iter()
+                .all(|term| other.inverse_terms.iter().any(|o| term.is_superset(o)))
+            && self
+                .fuzzy_terms
+                .iter()
+                .all(|term| other.fuzzy_terms.iter().any(|o| term.is_superset(o)))
+            && self
+                .regex_terms
+                .iter()
+                .all(|term| other.regex_terms.iter().any(|o| term.is_superset(o)))
+            && self.field_terms.iter().all(|term| {
+                other
+                    .field_terms
+                    .iter()
+                    .any(|o| term.field == o.field && term.query.is_superset(&o.query))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_is_superset() {
+        let is_superset = |q1: &str, q2: &str| Query::from(q1).is_superset(&Query::from(q2));
+
+        assert!(is_superset("", "foo"));
+        assert!(is_superset("fo", "foo"));
+        assert!(is_superset("foo 'ba", "foo 'bar"));
+        assert!(is_superset("foo", "foo .rs$"));
+        assert!(is_superset(".rs$", "x.rs$"));
+        assert!(is_superset("!bar", "!ba"));
+        assert!(is_superset("foo bar", "bar foo"));
+
+        assert!(!is_superset("foo", "fo"));
+        assert!(!is_superset("foo .rs$", "foo"));
+        assert!(!is_superset(".rs$", ".rsx$"));
+        assert!(!is_superset("!ba", "!bar"));
+        assert!(!is_superset("!^bar", "!foo"));
+        assert!(is_superset("/fo+/", "/fo+/ bar"));
+        assert!(!is_superset("/fo+/", "/fo+b/"));
+        assert!(is_superset("foo", "foo .rs$ | .toml$"));
+        assert!(!is_superset("foo .rs$ | .toml$", "foo .rs$ | .toml$"));
+        assert!(is_superset("path:src", "foo path:src/"));
+        assert!(!is_superset("path:src", "foo kind:src"));
+    }
+
+    #[test]
+    fn test_parse_or_groups() {
+        let query: Query = "| foo .rs$ | .toml$ | !test bar |".into();
+
+        assert_eq!(query.fuzzy_terms.len(), 2);
+        assert_eq!(query.or_groups.len(), 1);
+
+        let group = &query.or_groups[0];
+        assert_eq!(group.len(), 3);
+        assert_eq!(group[0].exact_terms[0].word, ".rs");
+        assert_eq!(group[1].exact_terms[0].word, ".toml");
+        assert_eq!(group[2].inverse_terms[0].word, "test");
+    }
+
+    #[test]
+    fn test_parse_field_terms() {
+        let query: Query = "path:^src/ kind:function foo: author:".into();
+
+        assert_eq!(query.field_terms.len(), 2);
+        assert_eq!(query.field_terms[0].field, SearchField::Path);
+        assert_eq!(query.field_terms[0].query.exact_terms[0].word, "src/");
+        assert_eq!(query.field_terms[1].field, SearchField::Kind);
+        assert_eq!(query.field_terms[1].query.fuzzy_terms[0].word, "function");
+        assert_eq!(query.fuzzy_terms.len(), 2);
+    }
 }
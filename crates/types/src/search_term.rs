@@ -1,3 +1,5 @@
+use regex::Regex;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ExactTermType {
     /// exact-match.
@@ -31,11 +33,11 @@ impl ExactTerm {
         use ExactTermType::*;
 
         match (&self.ty, &other.ty) {
-            (Exact, Exact) | (PrefixExact, PrefixExact) | (SuffixExact, SuffixExact) => {
-                // Comparing with `'hello`, `'he` has more results.
-                other.word.starts_with(&self.word)
-            }
-            (Exact, PrefixExact) | (Exact, SuffixExact) => true,
+            // Comparing with `'hello`, `'he` has more results.
+            (Exact, Exact) => other.word.contains(&self.word),
+            (PrefixExact, PrefixExact) => other.word.starts_with(&self.word),
+            (SuffixExact, SuffixExact) => other.word.ends_with(&self.word),
+            (Exact, PrefixExact) | (Exact, SuffixExact) => other.word.contains(&self.word),
             _ => false,
         }
     }
@@ -77,10 +79,12 @@ impl InverseTerm {
         // In order to have a superset results, `self.word` needs to be longer.
 
         match (&self.ty, &other.ty) {
-            (InverseExact, InverseExact)
-            | (InversePrefixExact, InversePrefixExact)
-            | (InverseSuffixExact, InverseSuffixExact) => self.word.starts_with(&other.word),
-            (InversePrefixExact, InverseExact) | (InverseSuffixExact, InverseExact) => true,
+            (InverseExact, InverseExact) => self.word.contains(&other.word),
+            (InversePrefixExact, InversePrefixExact) => self.word.starts_with(&other.word),
+            (InverseSuffixExact, InverseSuffixExact) => self.word.ends_with(&other.word),
+            (InversePrefixExact, InverseExact) | (InverseSuffixExact, InverseExact) => {
+                self.word.contains(&other.word)
+            }
             _ => false,
         }
     }
@@ -120,11 +124,80 @@ impl FuzzyTerm {
         self.word.len()
     }
 
+    /// Returns `true` if the results of applying `self`
+    /// is a superset of applying `other` on the same source.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        // Comparing with `hello`, `he` has more results.
+        other.word.starts_with(&self.word)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.word.is_empty()
     }
 }
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RegexTermType {
+    /// regex-match
+    ///
+    /// `/^fn\s+test_/`: Items that match the regular expression.
+    Regex,
+    /// inverse-regex-match
+    ///
+    /// `!/^fn\s+test_/`: Items that do not match the regular expression.
+    InverseRegex,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegexTerm {
+    pub ty: RegexTermType,
+    pub word: String,
+    pub re: Regex,
+}
+
+impl RegexTerm {
+    /// Creates a new regex term, `word` is matched literally if it's not a valid regex,
+    /// which is common when the user is still typing.
+    pub fn new(ty: RegexTermType, word: String) -> Self {
+        let re = Regex::new(&word).unwrap_or_else(|_| {
+            Regex::new(&regex::escape(&word)).expect("Escaped regex must be valid")
+        });
+        Self { ty, word, re }
+    }
+
+    pub fn is_inverse(&self) -> bool {
+        matches!(self.ty, RegexTermType::InverseRegex)
+    }
+
+    /// Returns `true` if the results of applying `self`
+    /// is a superset of applying `other` on the same source.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        self.ty == other.ty && self.word == other.word
+    }
+
+    /// Returns the char indices of all the matches in `full_search_line` if there is any.
+    pub fn match_indices(&self, full_search_line: &str) -> Option<Vec<usize>> {
+        let byte_ranges = self
+            .re
+            .find_iter(full_search_line)
+            .map(|m| m.range())
+            .collect::<Vec<_>>();
+
+        if byte_ranges.is_empty() {
+            return None;
+        }
+
+        let indices = full_search_line
+            .char_indices()
+            .enumerate()
+            .filter(|(_, (byte_idx, _))| byte_ranges.iter().any(|r| r.contains(byte_idx)))
+            .map(|(char_idx, _)| char_idx)
+            .collect();
+
+        Some(indices)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TermType {
     /// Items that match in fuzzy.
@@ -133,6 +206,8 @@ pub enum TermType {
     Exact(ExactTermType),
     /// Items that do not match something.
     Inverse(InverseTermType),
+    /// Items that match or do not match a regular expression.
+    Regex(RegexTermType),
 }
 
 impl TermType {
@@ -165,9 +240,20 @@ impl SearchTerm {
     }
 }
 
+/// Returns the regex between the slashes if `s` is of the form `/regex/`.
+fn strip_slashes(s: &str) -> Option<&str> {
+    s.strip_prefix('/')
+        .and_then(|s| s.strip_suffix('/'))
+        .filter(|re| !re.is_empty())
+}
+
 impl From<&str> for SearchTerm {
     fn from(s: &str) -> Self {
-        let (ty, word) = if let Some(stripped) = s.strip_prefix('\'') {
+        let (ty, word) = if let Some(re) = strip_slashes(s) {
+            (TermType::Regex(RegexTermType::Regex), re)
+        } else if let Some(re) = s.strip_prefix('!').and_then(strip_slashes) {
+            (TermType::Regex(RegexTermType::InverseRegex), re)
+        } else if let Some(stripped) = s.strip_prefix('\'') {
             (TermType::Exact(ExactTermType::Exact), stripped)
         } else if let Some(stripped) = s.strip_prefix('^') {
             (TermType::Exact(ExactTermType::PrefixExact), stripped)
@@ -202,13 +288,48 @@ impl From<&str> for SearchTerm {
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_regex_term() {
+        use TermType::*;
+
+        let terms = r"/\bfn\s+test_/ !/^use/ // /a"
+            .split_whitespace()
+            .map(Into::into)
+            .collect::<Vec<SearchTerm>>();
+
+        assert_eq!(
+            terms,
+            vec![
+                SearchTerm::new(Regex(RegexTermType::Regex), r"\bfn\s+test_".into()),
+                SearchTerm::new(Regex(RegexTermType::InverseRegex), "^use".into()),
+                SearchTerm::new(Fuzzy(FuzzyTermType::Fuzzy), "//".into()),
+                SearchTerm::new(Fuzzy(FuzzyTermType::Fuzzy), "/a".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn regex_term_match_indices() {
+        let term = RegexTerm::new(RegexTermType::Regex, r"te?st".into());
+        assert_eq!(
+            term.match_indices("a test, tst"),
+            Some(vec![2, 3, 4, 5, 8, 9, 10])
+        );
+        assert_eq!(term.match_indices("测试 test"), Some(vec![3, 4, 5, 6]));
+        assert_eq!(term.match_indices("nothing"), None);
+
+        // Invalid regex is matched literally.
+        let term = RegexTerm::new(RegexTermType::Regex, "fn(".into());
+        assert_eq!(term.match_indices("fn(a)"), Some(vec![0, 1, 2]));
+    }
+
     #[test]
     fn parse_term_should_work() {
         use TermType::*;
         let query = "aaa 'bbb ^ccc ddd$ !eee !'fff !^ggg !hhh$";
         let terms = query.split_whitespace().map(Into::into).collect::<Vec<_>>();
 
-        let expected = vec![
+        let expected = [
             SearchTerm::new(Fuzzy(FuzzyTermType::Fuzzy), "aaa".into()),
             SearchTerm::new(Exact(ExactTermType::Exact), "bbb".into()),
             SearchTerm::new(Exact(ExactTermType::PrefixExact), "ccc".into()),
@@ -1,4 +1,8 @@
-use pattern::{extract_file_name, extract_grep_pattern, extract_tag_name};
+use pattern::{
+    extract_buffer_tags_kind, extract_commit_author, extract_file_name,
+    extract_fpath_from_grep_line, extract_grep_pattern, extract_proj_tags, extract_proj_tags_kind,
+    extract_tag_name,
+};
 
 /// A tuple of match text piece (matching_text, offset_of_matching_text).
 #[derive(Debug, Clone)]
@@ -28,8 +32,9 @@ impl<'a> From<(&'a str, usize)> for FuzzyText<'a> {
 /// The location that a match should look in.
 ///
 /// Given a query, the match scope can refer to a full string or a substring.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum MatchScope {
+    #[default]
     Full,
     /// `:Clap tags`, `:Clap proj_tags`
     TagName,
@@ -39,9 +44,15 @@ pub enum MatchScope {
     GrepLine,
 }
 
-impl Default for MatchScope {
-    fn default() -> Self {
-        Self::Full
+impl MatchScope {
+    /// Extracts the text to apply the fuzzy match algorithm to from the full text `line`.
+    pub fn extract<'a>(&self, line: &'a str) -> Option<FuzzyText<'a>> {
+        match self {
+            Self::Full => Some(FuzzyText::new(line, 0)),
+            Self::TagName => extract_tag_name(line).map(|s| FuzzyText::new(s, 0)),
+            Self::FileName => extract_file_name(line).map(Into::into),
+            Self::GrepLine => extract_grep_pattern(line).map(Into::into),
+        }
     }
 }
 
@@ -64,6 +75,59 @@ impl<T: AsRef<str>> From<T> for MatchScope {
     }
 }
 
+/// Named part of the line that a field-scoped search term is applied to, e.g., `path:src/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    /// File path of the line in `grep2` and `proj_tags`, or the whole line in `files`.
+    Path,
+    /// Tag kind of the line in `tags` and `proj_tags`.
+    Kind,
+    /// Author of the line in `commits` and `bcommits`.
+    Author,
+}
+
+impl SearchField {
+    /// Splits a token like `path:src/` into the field and the rest search term.
+    pub fn split_token(token: &str) -> Option<(Self, &str)> {
+        let (name, term) = token.split_once(':')?;
+        let field = match name {
+            "path" => Self::Path,
+            "kind" => Self::Kind,
+            "author" => Self::Author,
+            _ => return None,
+        };
+        if term.is_empty() {
+            None
+        } else {
+            Some((field, term))
+        }
+    }
+
+    /// Extracts the text of this field from `line`, the offset of returned [`FuzzyText`] is the
+    /// char offset in `line`.
+    pub fn extract<'a>(&self, line: &'a str, match_scope: &MatchScope) -> Option<FuzzyText<'a>> {
+        let text = match (self, match_scope) {
+            (Self::Path, MatchScope::GrepLine) => extract_fpath_from_grep_line(line)?,
+            (Self::Path, MatchScope::TagName) => extract_proj_tags(line).map(|(_, path)| path)?,
+            (Self::Path, _) => line,
+            (Self::Kind, MatchScope::TagName) => {
+                extract_proj_tags_kind(line).or_else(|| extract_buffer_tags_kind(line))?
+            }
+            (Self::Kind, _) => return None,
+            (Self::Author, _) => extract_commit_author(line)?,
+        };
+
+        if text.is_empty() {
+            return None;
+        }
+
+        // All the extracted text is a slice of `line`.
+        let byte_offset = text.as_ptr() as usize - line.as_ptr() as usize;
+
+        Some(FuzzyText::new(text, line[..byte_offset].chars().count()))
+    }
+}
+
 /// Text used in the matching algorithm.
 pub trait MatchingText {
     /// Initial full text.
@@ -77,7 +141,7 @@ pub trait MatchingText {
     /// Text for applying the fuzzy match algorithm.
     ///
     /// The fuzzy matching process only happens when Some(_) is returned.
-    fn fuzzy_text(&self, match_scope: &MatchScope) -> Option<FuzzyText>;
+    fn fuzzy_text(&self, match_scope: &MatchScope) -> Option<FuzzyText<'_>>;
 }
 
 impl MatchingText for SourceItem {
@@ -85,7 +149,7 @@ impl MatchingText for SourceItem {
         &self.raw
     }
 
-    fn fuzzy_text(&self, match_scope: &MatchScope) -> Option<FuzzyText> {
+    fn fuzzy_text(&self, match_scope: &MatchScope) -> Option<FuzzyText<'_>> {
         self.get_fuzzy_text(match_scope)
     }
 }
@@ -95,7 +159,7 @@ impl MatchingText for &str {
         self
     }
 
-    fn fuzzy_text(&self, _match_scope: &MatchScope) -> Option<FuzzyText> {
+    fn fuzzy_text(&self, _match_scope: &MatchScope) -> Option<FuzzyText<'_>> {
         Some(FuzzyText {
             text: self,
             matching_start: 0,
@@ -163,17 +227,11 @@ impl SourceItem {
         }
     }
 
-    pub fn get_fuzzy_text(&self, match_scope: &MatchScope) -> Option<FuzzyText> {
+    pub fn get_fuzzy_text(&self, match_scope: &MatchScope) -> Option<FuzzyText<'_>> {
         if let Some((ref text, offset)) = self.fuzzy_text {
             return Some(FuzzyText::new(text, offset));
         }
-        let full = self.raw.as_str();
-        match match_scope {
-            MatchScope::Full => Some(FuzzyText::new(full, 0)),
-            MatchScope::TagName => extract_tag_name(full).map(|s| FuzzyText::new(s, 0)),
-            MatchScope::FileName => extract_file_name(full).map(Into::into),
-            MatchScope::GrepLine => extract_grep_pattern(full).map(Into::into),
-        }
+        match_scope.extract(&self.raw)
     }
 }
 
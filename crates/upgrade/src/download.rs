@@ -27,7 +27,7 @@ pub fn download_prebuilt_binary_to_a_tempfile(version: &str) -> Result<PathBuf>
         let fname = response
             .url()
             .path_segments()
-            .and_then(|segments| segments.last())
+            .and_then(|mut segments| segments.next_back())
             .and_then(|name| if name.is_empty() { None } else { Some(name) })
             .unwrap_or("tmp.bin");
 
@@ -50,7 +50,7 @@ pub(super) async fn download_prebuilt_binary_to_a_tempfile_async(version: &str)
     use tokio::{fs, io::AsyncWriteExt};
 
     let asset_name = asset_name()?;
-    let total_size = retrieve_asset_size(&asset_name, version)?;
+    let total_size = retrieve_asset_size(asset_name, version)?;
 
     let client = reqwest::Client::new();
     let request = client.get(&download_url(version)?);
@@ -89,6 +89,6 @@ mod tests {
     #[test]
     #[ignore]
     fn test_retrieve_asset_size() {
-        println!("{:?}", retrieve_asset_size(&asset_name().unwrap(), "v0.14"));
+        println!("{:?}", retrieve_asset_size(asset_name().unwrap(), "v0.14"));
     }
 }
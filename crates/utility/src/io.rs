@@ -32,16 +32,36 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-/// Returns the first number lines given the file path.
+/// Returns an iterator over the lines of `reader`, the invalid UTF-8 sequences are replaced with
+/// `U+FFFD` instead of failing the line as [`BufRead::lines`] does.
+///
+/// The iteration stops on the first I/O error.
+pub fn lossy_lines<R: BufRead>(mut reader: R) -> impl Iterator<Item = String> {
+    let mut buf = Vec::new();
+    std::iter::from_fn(move || {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => {
+                if buf.last() == Some(&b'\n') {
+                    buf.pop();
+                    if buf.last() == Some(&b'\r') {
+                        buf.pop();
+                    }
+                }
+                Some(String::from_utf8_lossy(&buf).into_owned())
+            }
+        }
+    })
+}
+
+/// Returns the first number lines given the file path, the file can be compressed.
 pub fn read_first_lines<P: AsRef<Path>>(
     path: P,
     number: usize,
 ) -> io::Result<impl Iterator<Item = String>> {
-    let file = File::open(path)?;
-    Ok(io::BufReader::new(file)
-        .lines()
-        .filter_map(|i| i.ok())
-        .take(number))
+    let file = crate::lz4::open_decompressed(path)?;
+    Ok(lossy_lines(io::BufReader::new(file)).take(number))
 }
 
 #[inline]
@@ -52,8 +72,7 @@ pub fn clap_cache_dir() -> io::Result<PathBuf> {
 
         Ok(cache_dir.to_path_buf())
     } else {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
+        Err(io::Error::other(
             "Couldn't create Vim Clap project directory",
         ))
     }
@@ -120,8 +139,7 @@ fn read_preview_lines_impl<P: AsRef<Path>>(
 
             let filesize = initial_buffer_size(&file);
             if filesize > MEGABYTE {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
+                return Err(std::io::Error::other(
                     "maximum preview file buffer size reached",
                 ));
             }
@@ -131,7 +149,6 @@ fn read_preview_lines_impl<P: AsRef<Path>>(
         })
         .map(|_| {
             let lines = ByteLines::new(&filebuf)
-                .into_iter()
                 .skip(start)
                 .take(end - start)
                 .map(|l| l.to_string())
@@ -198,4 +215,13 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_lossy_lines() {
+        let bytes = b"foo\r\nb\xffar\n\nbaz".as_slice();
+        assert_eq!(
+            lossy_lines(bytes).collect::<Vec<_>>(),
+            ["foo", "b\u{fffd}ar", "", "baz"]
+        );
+    }
 }
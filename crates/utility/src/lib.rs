@@ -8,11 +8,12 @@ use std::process::{Command, Output};
 
 pub mod bytelines;
 mod io;
+pub mod lz4;
 mod macros;
 
 pub use self::io::{
-    clap_cache_dir, create_or_overwrite, read_first_lines, read_lines, read_lines_from,
-    read_preview_lines, remove_dir_contents,
+    clap_cache_dir, create_or_overwrite, lossy_lines, read_first_lines, read_lines,
+    read_lines_from, read_preview_lines, remove_dir_contents,
 };
 
 /// Returns true if the `dir` is a git repo, including git submodule.
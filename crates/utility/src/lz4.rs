@@ -0,0 +1,99 @@
+//! Compression in the [LZ4 frame format](https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md),
+//! the compressed files can also be decompressed by the `lz4` command line tool.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use lz4_flex::frame::{FrameDecoder, FrameEncoder, FrameInfo};
+
+/// Magic number of the LZ4 frame, little endian.
+const MAGIC: [u8; 4] = [0x04, 0x22, 0x4D, 0x18];
+
+/// Returns true if `bytes` starts with the magic number of LZ4 frame.
+pub fn is_lz4_frame(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Compresses `src` into a LZ4 frame, the content checksum is included for detecting the
+/// corrupted cache.
+pub fn compress(src: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = FrameEncoder::with_frame_info(
+        FrameInfo::new().content_checksum(true),
+        Vec::with_capacity(src.len() / 2),
+    );
+    encoder.write_all(src)?;
+    Ok(encoder.finish()?)
+}
+
+/// Opens the file at `path`, which is decompressed transparently if it's compressed.
+///
+/// The blocks are decompressed one by one, the memory usage is bounded by the block size
+/// declared in the frame, at most 4MiB.
+pub fn open_decompressed<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read + Send>> {
+    let mut file = File::open(path)?;
+
+    let mut magic = [0u8; 4];
+    let is_compressed = match file.read_exact(&mut magic) {
+        Ok(()) => is_lz4_frame(&magic),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => false,
+        Err(e) => return Err(e),
+    };
+    file.seek(SeekFrom::Start(0))?;
+
+    if is_compressed {
+        Ok(Box::new(FrameDecoder::new(BufReader::new(file))))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Reads the whole content of file `path`, which is decompressed transparently if it's
+/// compressed.
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut content = String::new();
+    open_decompressed(path)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lz4_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache");
+
+        let lines = (0..20_000)
+            .map(|i| {
+                format!(
+                    "crates/maple_cli/src/lib.rs:{}:1:fn foo_{}() {{}}",
+                    i,
+                    i % 37
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        for src in ["", "a", "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", lines.as_str()] {
+            let compressed = compress(src.as_bytes()).unwrap();
+            assert!(is_lz4_frame(&compressed));
+
+            std::fs::write(&path, &compressed).unwrap();
+            assert_eq!(read_to_string(&path).unwrap(), src);
+
+            // The uncompressed file is read as is.
+            std::fs::write(&path, src).unwrap();
+            assert_eq!(read_to_string(&path).unwrap(), src);
+        }
+
+        let mut compressed = compress(lines.as_bytes()).unwrap();
+        assert!(compressed.len() < lines.len() / 4);
+
+        // Corrupt the data of the first block.
+        compressed[20] ^= 0xFF;
+        std::fs::write(&path, &compressed).unwrap();
+        assert!(read_to_string(&path).is_err());
+    }
+}
@@ -67,6 +67,9 @@ inoremap <silent> <buffer> <A-u>       <C-R>=clap#handler#back_action()<CR>
 inoremap <silent> <buffer> <C-j> <C-R>=clap#navigation#linewise('down')<CR>
 inoremap <silent> <buffer> <C-k> <C-R>=clap#navigation#linewise('up')<CR>
 
+inoremap <silent> <buffer> <C-p> <C-R>=clap#query_history#recall(1)<CR>
+inoremap <silent> <buffer> <C-n> <C-R>=clap#query_history#recall(-1)<CR>
+
 call clap#util#define_open_action_mappings()
 
 if g:clap_insert_mode_only